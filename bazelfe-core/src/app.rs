@@ -1,273 +1,307 @@
-use crate::util::{RandomSignal, SinSignal, StatefulList, TabsState};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
 
-const TASKS: [&str; 24] = [
-    "Item1", "Item2", "Item3", "Item4", "Item5", "Item6", "Item7", "Item8", "Item9", "Item10",
-    "Item11", "Item12", "Item13", "Item14", "Item15", "Item16", "Item17", "Item18", "Item19",
-    "Item20", "Item21", "Item22", "Item23", "Item24",
-];
+use bazelfe_protos::build_event_stream;
+use lazy_static::lazy_static;
+use regex::Regex;
 
-const ACTION_LOGS: [(&str, &str); 3] = [
-    (
-        "ACTION",
-        "//src/main/scala/com/example/foo/foo/foo_blah.scala",
-    ),
-    ("TARGET", "//src/main/scala/com/example/foo/foo:foo"),
-    ("TEST", "//src/test/scala/com/example/foo:foo_test"),
-];
-const LOGS: [(&str, &str); 26] = [
-    ("Event1", "INFO"),
-    ("Event2", "INFO"),
-    ("Event3", "CRITICAL"),
-    ("Event4", "ERROR"),
-    ("Event5", "INFO"),
-    ("Event6", "INFO"),
-    ("Event7", "WARNING"),
-    ("Event8", "INFO"),
-    ("Event9", "INFO"),
-    ("Event10", "INFO"),
-    ("Event11", "CRITICAL"),
-    ("Event12", "INFO"),
-    ("Event13", "INFO"),
-    ("Event14", "INFO"),
-    ("Event15", "INFO"),
-    ("Event16", "INFO"),
-    ("Event17", "ERROR"),
-    ("Event18", "ERROR"),
-    ("Event19", "INFO"),
-    ("Event20", "INFO"),
-    ("Event21", "WARNING"),
-    ("Event22", "INFO"),
-    ("Event23", "INFO"),
-    ("Event24", "WARNING"),
-    ("Event25", "INFO"),
-    ("Event26", "INFO"),
-];
+use crate::bazel_runner::action_event_stream::AppliedCorrection;
+use crate::bazel_runner::session::SessionUpdate;
+use crate::build_events::hydrated_stream::HydratedInfo;
+use crate::util::{StatefulList, TabsState};
 
-const EVENTS: [(&str, u64); 24] = [
-    ("B1", 9),
-    ("B2", 12),
-    ("B3", 5),
-    ("B4", 8),
-    ("B5", 2),
-    ("B6", 4),
-    ("B7", 5),
-    ("B8", 9),
-    ("B9", 14),
-    ("B10", 15),
-    ("B11", 1),
-    ("B12", 0),
-    ("B13", 4),
-    ("B14", 6),
-    ("B15", 4),
-    ("B16", 6),
-    ("B17", 4),
-    ("B18", 7),
-    ("B19", 13),
-    ("B20", 8),
-    ("B21", 11),
-    ("B22", 9),
-    ("B23", 3),
-    ("B24", 5),
-];
-
-const NEMONICS: [(&str, u64); 3] = [("Scalac", 9), ("Javac", 200), ("ijar", 4)];
-
-pub struct Signal<S: Iterator> {
-    source: S,
-    pub points: Vec<S::Item>,
-    tick_rate: usize,
+pub struct FailedAction {
+    pub label: String,
+    pub mnemonic: Option<String>,
+    pub attempt: u16,
+    pub output_files: Vec<build_event_stream::file::File>,
 }
 
-impl<S> Signal<S>
-where
-    S: Iterator,
-{
-    fn on_tick(&mut self) {
-        for _ in 0..self.tick_rate {
-            self.points.remove(0);
+impl FailedAction {
+    // The action's stderr, along with its stdout if it had any, read from disk unless it was
+    // inlined into the event
+    pub fn read_output(&self) -> String {
+        let mut results = Vec::default();
+        for e in self.output_files.iter() {
+            match e {
+                build_event_stream::file::File::Uri(uri) => match uri.strip_prefix("file://") {
+                    Some(path) => match std::fs::read_to_string(PathBuf::from(path)) {
+                        Ok(content) => results.push(content),
+                        Err(err) => results.push(format!("Unable to read {}: {}", path, err)),
+                    },
+                    None => results.push(format!("Unable to read {}, it isn't a file", uri)),
+                },
+                build_event_stream::file::File::Contents(contents) => {
+                    results.push(String::from_utf8_lossy(contents).to_string());
+                }
+            }
         }
-        self.points
-            .extend(self.source.by_ref().take(self.tick_rate));
+        strip_color_codes(&results.join("\n"))
     }
 }
 
-pub struct Signals {
-    pub sin1: Signal<SinSignal>,
-    pub sin2: Signal<SinSignal>,
-    pub window: [f64; 2],
+// Bazel is run with --color yes, which the outputs of its actions keep
+fn strip_color_codes(text: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap();
+    }
+    RE.replace_all(text, "").to_string()
 }
 
-impl Signals {
-    fn on_tick(&mut self) {
-        self.sin1.on_tick();
-        self.sin2.on_tick();
-        self.window[0] += 1.0;
-        self.window[1] += 1.0;
-    }
+pub struct AppliedFix {
+    pub attempt: u16,
+    pub correction: AppliedCorrection,
 }
 
-pub struct Server<'a> {
-    pub name: &'a str,
-    pub location: &'a str,
-    pub coords: (f64, f64),
-    pub status: &'a str,
+// The output of a failed action being looked at
+pub struct Inspecting {
+    pub label: String,
+    pub output: String,
+    pub scroll: u16,
 }
 
 pub struct App<'a> {
     pub title: &'a str,
     pub should_quit: bool,
     pub tabs: TabsState<'a>,
-    pub show_chart: bool,
-    pub progress: f64,
-    pub sparkline: Signal<RandomSignal>,
-    pub tasks: StatefulList<&'a str>,
-    pub action_logs: StatefulList<(&'a str, &'a str, u16)>,
-    pub logs: StatefulList<(&'a str, &'a str)>,
-    pub signals: Signals,
-    pub barchart: Vec<(&'a str, u64)>,
-    pub completed_actions: Vec<(&'a str, u64)>,
-    pub nemonics: Vec<(&'a str, u64)>,
-    pub servers: Vec<Server<'a>>,
+    pub attempt: u16,
+    // Bazel's exit code, once the session is over
+    pub exit_code: Option<i32>,
+    // Targets with actions seen in this attempt that haven't completed yet
+    pub running_targets: BTreeSet<String>,
+    pub failed_targets: BTreeSet<String>,
+    pub completed_targets: u64,
+    pub action_counts: BTreeMap<String, u64>,
+    pub failed_actions: StatefulList<FailedAction>,
+    pub fixes: StatefulList<AppliedFix>,
+    pub inspecting: Option<Inspecting>,
     pub enhanced_graphics: bool,
 }
 
 impl<'a> App<'a> {
     pub fn new(title: &'a str, enhanced_graphics: bool) -> App<'a> {
-        let mut rand_signal = RandomSignal::new(0, 100);
-        let sparkline_points = rand_signal.by_ref().take(300).collect();
-        let mut sin_signal = SinSignal::new(0.2, 3.0, 18.0);
-        let sin1_points = sin_signal.by_ref().take(100).collect();
-        let mut sin_signal2 = SinSignal::new(0.1, 2.0, 10.0);
-        let sin2_points = sin_signal2.by_ref().take(200).collect();
         App {
             title,
             should_quit: false,
-            tabs: TabsState::new(vec!["Build Activity", "To be built"]),
-            show_chart: true,
-            progress: 0.0,
-            sparkline: Signal {
-                source: rand_signal,
-                points: sparkline_points,
-                tick_rate: 1,
-            },
-            tasks: StatefulList::with_items(TASKS.to_vec()),
-            action_logs: StatefulList::with_items(
-                ACTION_LOGS
-                    .iter()
-                    .map(|(k, v)| (k.clone(), v.clone(), 0))
-                    .chain(ACTION_LOGS.iter().map(|(k, v)| (k.clone(), v.clone(), 0)))
-                    .collect(),
-            ),
-            logs: StatefulList::with_items(LOGS.to_vec()),
-            signals: Signals {
-                sin1: Signal {
-                    source: sin_signal,
-                    points: sin1_points,
-                    tick_rate: 5,
-                },
-                sin2: Signal {
-                    source: sin_signal2,
-                    points: sin2_points,
-                    tick_rate: 10,
-                },
-                window: [0.0, 20.0],
-            },
-            barchart: EVENTS.to_vec(),
-            completed_actions: NEMONICS.to_vec(),
-            nemonics: NEMONICS.to_vec(),
-            servers: vec![
-                Server {
-                    name: "NorthAmerica-1",
-                    location: "New York City",
-                    coords: (40.71, -74.00),
-                    status: "Up",
-                },
-                Server {
-                    name: "Europe-1",
-                    location: "Paris",
-                    coords: (48.85, 2.35),
-                    status: "Failure",
-                },
-                Server {
-                    name: "SouthAmerica-1",
-                    location: "São Paulo",
-                    coords: (-23.54, -46.62),
-                    status: "Up",
-                },
-                Server {
-                    name: "Asia-1",
-                    location: "Singapore",
-                    coords: (1.35, 103.86),
-                    status: "Up",
-                },
-            ],
+            tabs: TabsState::new(vec!["Build Activity", "Failed Actions", "Fixes Applied"]),
+            attempt: 0,
+            exit_code: None,
+            running_targets: BTreeSet::new(),
+            failed_targets: BTreeSet::new(),
+            completed_targets: 0,
+            action_counts: BTreeMap::new(),
+            failed_actions: StatefulList::new(),
+            fixes: StatefulList::new(),
+            inspecting: None,
             enhanced_graphics,
         }
     }
 
+    fn count_action(&mut self, label: &str, mnemonic: &Option<String>) {
+        let mnemonic = mnemonic.as_deref().unwrap_or("Other");
+        *self.action_counts.entry(mnemonic.to_string()).or_insert(0) += 1;
+        if !self.failed_targets.contains(label) {
+            self.running_targets.insert(label.to_string());
+        }
+    }
+
+    pub fn on_session_update(&mut self, update: SessionUpdate) {
+        match update {
+            SessionUpdate::AttemptStarted(attempt) => {
+                // Targets are built again each attempt, so only the counts for this one are shown
+                self.attempt = attempt;
+                self.running_targets.clear();
+                self.failed_targets.clear();
+                self.completed_targets = 0;
+                self.action_counts.clear();
+            }
+            SessionUpdate::Hydrated(HydratedInfo::ActionSuccess(info)) => {
+                self.count_action(&info.label, &info.mnemonic);
+            }
+            SessionUpdate::Hydrated(HydratedInfo::ActionFailed(info)) => {
+                self.count_action(&info.label, &info.mnemonic);
+                self.running_targets.remove(&info.label);
+                self.failed_targets.insert(info.label.clone());
+                self.failed_actions.items.push(FailedAction {
+                    label: info.label,
+                    mnemonic: info.mnemonic,
+                    attempt: self.attempt,
+                    output_files: info.output_files,
+                });
+            }
+            SessionUpdate::Hydrated(HydratedInfo::TargetComplete(info)) => {
+                self.running_targets.remove(&info.label);
+                if info.success {
+                    self.completed_targets += 1;
+                } else {
+                    self.failed_targets.insert(info.label);
+                }
+            }
+            SessionUpdate::Hydrated(HydratedInfo::BazelAbort(info)) => {
+                if let Some(label) = info.label {
+                    self.running_targets.remove(&label);
+                    self.failed_targets.insert(label);
+                }
+            }
+            SessionUpdate::Hydrated(HydratedInfo::Progress(_)) => (),
+            SessionUpdate::CorrectionsApplied(corrections) => {
+                let attempt = self.attempt;
+                self.fixes
+                    .items
+                    .extend(corrections.into_iter().map(|correction| AppliedFix {
+                        attempt,
+                        correction,
+                    }));
+            }
+            SessionUpdate::AttemptFinished { .. } => {
+                self.running_targets.clear();
+            }
+            SessionUpdate::SessionFinished { exit_code, .. } => {
+                self.exit_code = Some(exit_code);
+            }
+        }
+    }
+
     pub fn on_up(&mut self) {
-        self.tasks.previous();
+        if let Some(inspecting) = self.inspecting.as_mut() {
+            inspecting.scroll = inspecting.scroll.saturating_sub(1);
+            return;
+        }
+        match self.tabs.index {
+            1 => self.failed_actions.previous(),
+            2 => self.fixes.previous(),
+            _ => {}
+        }
     }
 
     pub fn on_down(&mut self) {
-        self.tasks.next();
+        if let Some(inspecting) = self.inspecting.as_mut() {
+            inspecting.scroll = inspecting.scroll.saturating_add(1);
+            return;
+        }
+        match self.tabs.index {
+            1 => self.failed_actions.next(),
+            2 => self.fixes.next(),
+            _ => {}
+        }
     }
 
     pub fn on_right(&mut self) {
+        self.inspecting = None;
         self.tabs.next();
     }
 
     pub fn on_left(&mut self) {
+        self.inspecting = None;
         self.tabs.previous();
     }
 
+    // Shows the output of the selected failed action
+    pub fn on_enter(&mut self) {
+        if self.tabs.index != 1 {
+            return;
+        }
+        if let Some(idx) = self.failed_actions.state.selected() {
+            if let Some(action) = self.failed_actions.items.get(idx) {
+                self.inspecting = Some(Inspecting {
+                    label: action.label.clone(),
+                    output: action.read_output(),
+                    scroll: 0,
+                });
+            }
+        }
+    }
+
+    pub fn on_escape(&mut self) {
+        self.inspecting = None;
+    }
+
     pub fn on_key(&mut self, c: char) {
         match c {
             'q' => {
                 self.should_quit = true;
             }
-            't' => {
-                self.show_chart = !self.show_chart;
-            }
+            'k' => self.on_up(),
+            'j' => self.on_down(),
+            '\n' => self.on_enter(),
             _ => {}
         }
     }
+}
 
-    pub fn on_tick(&mut self) {
-        // Update progress
-        self.progress += 0.001;
-        if self.progress > 1.0 {
-            self.progress = 0.0;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_events::hydrated_stream::{
+        ActionFailedErrorInfo, ActionSuccessInfo, TargetCompleteInfo,
+    };
+    use crate::buildozer_driver::edit_plan::BuildozerEdit;
 
-        self.sparkline.on_tick();
-        self.signals.on_tick();
+    fn action_success(label: &str, mnemonic: &str) -> SessionUpdate {
+        SessionUpdate::Hydrated(HydratedInfo::ActionSuccess(ActionSuccessInfo {
+            label: String::from(label),
+            mnemonic: Some(String::from(mnemonic)),
+            stdout: None,
+            stderr: None,
+            target_kind: Some(String::from("scala_library")),
+        }))
+    }
 
-        let (a, b, _) = self.action_logs.items.pop().unwrap();
-        self.action_logs
-            .items
-            .insert(0, (a, b, rand::random::<u16>()));
+    #[test]
+    fn test_session_updates() {
+        let mut app = App::new("bazel-fe", true);
+        app.on_session_update(SessionUpdate::AttemptStarted(0));
+        app.on_session_update(action_success("//src/main/scala/com/example/a:a", "Scalac"));
+        app.on_session_update(action_success("//src/main/scala/com/example/b:b", "Scalac"));
+        app.on_session_update(action_success("//src/main/scala/com/example/b:b", "Javac"));
+        app.on_session_update(SessionUpdate::Hydrated(HydratedInfo::ActionFailed(
+            ActionFailedErrorInfo {
+                label: String::from("//src/main/scala/com/example/a:a"),
+                mnemonic: Some(String::from("Scalac")),
+                output_files: vec![build_event_stream::file::File::Contents(
+                    b"\x1b[31merror:\x1b[0m not found: type Foo".to_vec(),
+                )],
+                target_kind: Some(String::from("scala_library")),
+            },
+        )));
+        app.on_session_update(SessionUpdate::Hydrated(HydratedInfo::TargetComplete(
+            TargetCompleteInfo {
+                label: String::from("//src/main/scala/com/example/b:b"),
+                success: true,
+                target_kind: Some(String::from("scala_library")),
+                output_files: vec![],
+                hidden_output_files: vec![],
+            },
+        )));
 
-        let log = self.logs.items.pop().unwrap();
-        self.logs.items.insert(0, log);
+        assert_eq!(app.action_counts.get("Scalac"), Some(&3));
+        assert_eq!(app.action_counts.get("Javac"), Some(&1));
+        assert!(app.running_targets.is_empty());
+        assert!(app
+            .failed_targets
+            .contains("//src/main/scala/com/example/a:a"));
+        assert_eq!(app.completed_targets, 1);
 
-        let event = self.barchart.pop().unwrap();
-        self.barchart.insert(0, event);
+        app.on_session_update(SessionUpdate::CorrectionsApplied(vec![AppliedCorrection {
+            edit: BuildozerEdit::AddDependency {
+                target: String::from("//src/main/scala/com/example/a:a"),
+                label: String::from("//src/main/scala/com/example/foo:foo"),
+            },
+            src_fn: String::from("scala::type_not_found"),
+        }]));
+        app.on_session_update(SessionUpdate::AttemptStarted(1));
+        assert_eq!(app.attempt, 1);
+        assert!(app.failed_targets.is_empty());
+        assert!(app.action_counts.is_empty());
+        assert_eq!(app.failed_actions.items.len(), 1);
+        assert_eq!(app.fixes.items.len(), 1);
+        assert_eq!(app.fixes.items[0].attempt, 0);
 
-        self.completed_actions.iter_mut().for_each(|(_, v)| {
-            if rand::random::<bool>() {
-                *v = *v + 1
-            }
-        });
-        self.nemonics.iter_mut().for_each(|(_, v)| {
-            if *v == 0 {
-                *v = 50 as u64;
-            }
-            if rand::random::<bool>() {
-                *v = *v + 1
-            } else {
-                *v = *v - 1
-            }
-        })
+        app.on_right();
+        app.on_down();
+        app.on_enter();
+        let inspecting = app.inspecting.as_ref().unwrap();
+        assert_eq!(inspecting.label, "//src/main/scala/com/example/a:a");
+        assert_eq!(inspecting.output, "error: not found: type Foo");
     }
 }
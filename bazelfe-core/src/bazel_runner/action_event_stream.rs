@@ -3,8 +3,16 @@ use std::{path::PathBuf, sync::Arc};
 use crate::build_events::hydrated_stream;
 
 use super::super::index_table;
-use crate::buildozer_driver::Buildozer;
+use super::config::Config;
+use super::event_log::{EventLog, SessionEvent};
+use super::process_unused_dependencies::{self, UnusedDepsMode};
+use super::session::{SessionUpdate, SessionUpdateSender};
+use crate::buildozer_driver::{
+    edit_plan::{BuildozerEdit, EditPlan},
+    Buildozer,
+};
 use dashmap::{DashMap, DashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::mpsc;
 use tokio::sync::RwLock;
 
@@ -12,11 +20,29 @@ pub trait ExtractClassData<U> {
     fn paths(&self) -> Vec<PathBuf>;
     fn id_info(&self) -> U;
 }
+
+// A BUILD file edit made by the pipeline, along with the extractor that motivated it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AppliedCorrection {
+    pub edit: BuildozerEdit,
+    pub src_fn: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct ActionEventStream<T: Buildozer + Send + Sync + Clone + 'static> {
     index_input_location: Option<PathBuf>,
     index_table: Arc<RwLock<Option<index_table::IndexTable>>>,
+    config: Arc<Config>,
+    index_updated: Arc<AtomicBool>,
     previous_global_seen: Arc<DashMap<String, DashSet<String>>>,
+    unused_deps: UnusedDepsMode,
+    proposed_removals: EditPlan,
+    // Removals which broke the build, so were put back and shouldn't be made again
+    rejected_removals: EditPlan,
+    // Print how the deps for each failed action were looked for
+    explain: bool,
+    event_log: Option<EventLog>,
+    session_updates: Option<SessionUpdateSender>,
     buildozer: T,
 }
 
@@ -28,11 +54,78 @@ where
         Self {
             index_input_location: index_input_location,
             index_table: Arc::new(RwLock::new(None)),
+            config: Arc::new(Config::default()),
+            index_updated: Arc::new(AtomicBool::new(false)),
             previous_global_seen: Arc::new(DashMap::new()),
+            unused_deps: UnusedDepsMode::Ignore,
+            proposed_removals: EditPlan::new(),
+            rejected_removals: EditPlan::new(),
+            explain: false,
+            event_log: None,
+            session_updates: None,
             buildozer: buildozer,
         }
     }
 
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Arc::new(config);
+        self
+    }
+
+    pub fn with_explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    pub fn with_event_log(mut self, event_log: Option<EventLog>) -> Self {
+        self.event_log = event_log;
+        self
+    }
+
+    pub fn event_log(&self) -> Option<&EventLog> {
+        self.event_log.as_ref()
+    }
+
+    pub fn with_session_updates(mut self, session_updates: Option<SessionUpdateSender>) -> Self {
+        self.session_updates = session_updates;
+        self
+    }
+
+    // Nobody watching the session shouldn't stop it, so a closed channel is ignored
+    pub fn send_session_update(&self, update: SessionUpdate) {
+        if let Some(session_updates) = &self.session_updates {
+            let _ = session_updates.send(update);
+        }
+    }
+
+    pub fn with_unused_deps(mut self, unused_deps: UnusedDepsMode) -> Self {
+        self.unused_deps = unused_deps;
+        self
+    }
+
+    // Keeps the index, but forgets the deps already tried for each target, as a dry run's
+    // never get added
+    pub fn with_fresh_previous_seen(mut self) -> Self {
+        self.previous_global_seen = Arc::new(DashMap::new());
+        self
+    }
+
+    // Removals of unused deps found when proposing them, rather than removing them
+    pub fn proposed_removals(&self) -> Vec<BuildozerEdit> {
+        self.proposed_removals.edits()
+    }
+
+    // As with proposed_removals, but clearing them for the next session
+    pub fn take_proposed_removals(&self) -> Vec<BuildozerEdit> {
+        self.proposed_removals.take()
+    }
+
+    pub fn reject_removal(&self, edit: BuildozerEdit) {
+        if !self.rejected_removals.edits().contains(&edit) {
+            self.rejected_removals.push(edit);
+        }
+    }
+
     pub async fn ensure_table_loaded(self) -> () {
         let tbl = Arc::clone(&self.index_table);
         let v = tbl.read().await;
@@ -44,8 +137,7 @@ where
                     let index_tbl = match &self.index_input_location {
                         Some(p) => {
                             if p.exists() {
-                                let content = std::fs::read_to_string(p).unwrap();
-                                index_table::parse_file(&content).unwrap()
+                                index_table::load_file(p).unwrap()
                             } else {
                                 index_table::IndexTable::new()
                             }
@@ -62,10 +154,89 @@ where
         ()
     }
 
+    // Writes the index back to where it was loaded from, if targets built since
+    // have added anything to it. Returns whether it was written.
+    pub async fn persist_index(&self) -> std::io::Result<bool> {
+        let path = match &self.index_input_location {
+            Some(p) => p,
+            None => return Ok(false),
+        };
+        if !self.index_updated.swap(false, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let tbl = self.index_table.read().await;
+        match tbl.as_ref() {
+            Some(index_tbl) => {
+                index_tbl.persist(path)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn index_target_complete(&self, tce: &hydrated_stream::TargetCompleteInfo) {
+        let indexed = match &tce.target_kind {
+            Some(target_kind) => self.config.is_indexed(target_kind),
+            None => false,
+        };
+        if !tce.success || !indexed {
+            return;
+        }
+        let found_classes =
+            crate::jvm_indexer::indexer_action_event_stream::classes_from_output_files(
+                &tce.output_files,
+            );
+        if found_classes.is_empty() {
+            return;
+        }
+        let mut tbl = self.index_table.write().await;
+        if let Some(index_tbl) = tbl.as_mut() {
+            if index_tbl.add_target_classes(&tce.label, &found_classes) {
+                debug!("Indexed {} classes from {}", found_classes.len(), tce.label);
+                self.index_updated.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    async fn process_unused_dependencies(
+        &self,
+        tce: &hydrated_stream::TargetCompleteInfo,
+    ) -> Vec<AppliedCorrection> {
+        if self.unused_deps == UnusedDepsMode::Ignore {
+            return Vec::default();
+        }
+        let removals =
+            process_unused_dependencies::find_unused_dependencies(&self.buildozer, tce).await;
+
+        let mut actions_completed = Vec::default();
+        let rejected_removals = self.rejected_removals.edits();
+        for edit in removals.into_iter() {
+            if rejected_removals.contains(&edit) {
+                continue;
+            }
+            if self.unused_deps == UnusedDepsMode::Propose {
+                // Targets are reported complete again on each attempt
+                if !self.proposed_removals.edits().contains(&edit) {
+                    self.proposed_removals.push(edit);
+                }
+                continue;
+            }
+            info!("Buildozer action: {}", edit.to_buildozer_command());
+            match edit.apply(&self.buildozer).await {
+                Ok(_) => actions_completed.push(AppliedCorrection {
+                    edit,
+                    src_fn: String::from("jdeps::unused_dependency"),
+                }),
+                Err(e) => warn!("Failed to remove unused dependency: {:?}", e),
+            }
+        }
+        actions_completed
+    }
+
     pub fn build_action_pipeline(
         &self,
         mut rx: mpsc::Receiver<Option<hydrated_stream::HydratedInfo>>,
-    ) -> mpsc::Receiver<Option<u32>> {
+    ) -> mpsc::Receiver<Option<Vec<AppliedCorrection>>> {
         let (mut tx, next_rx) = mpsc::channel(4096);
 
         let self_d: ActionEventStream<T> = self.clone();
@@ -78,6 +249,7 @@ where
                         tx.send(None).await.unwrap();
                     }
                     Some(e) => {
+                        self_d.send_session_update(SessionUpdate::Hydrated(e.clone()));
                         if !done_load {
                             let nxt = self_d.clone();
                             nxt.ensure_table_loaded().await;
@@ -101,14 +273,28 @@ where
                                     let prev_data =
                                         arc.get(&action_failed_error_info.label).unwrap();
 
-                                    let actions_completed = super::process_missing_dependency_errors::process_missing_dependency_errors(
+                                    let (actions_completed, explanation) = super::process_missing_dependency_errors::process_missing_dependency_errors(
                                             &prev_data,
                                             self_d.buildozer,
                                             &action_failed_error_info,
                                             v.as_ref().unwrap(),
+                                            &self_d.config,
                                         ).await;
+                                    if let Some(event_log) = &self_d.event_log {
+                                        event_log.log(SessionEvent::ActionFailed {
+                                            label: explanation.label.clone(),
+                                            target_kind: explanation.target_kind.clone(),
+                                            class_import_requests: explanation
+                                                .class_import_requests
+                                                .clone(),
+                                            suffix_requests: explanation.suffix_requests.clone(),
+                                        });
+                                    }
+                                    if self_d.explain {
+                                        eprint!("{}", explanation);
+                                    }
 
-                                    if actions_completed > 0 {
+                                    if !actions_completed.is_empty() {
                                         tx.send(Some(actions_completed)).await.unwrap();
                                     }
                                 }
@@ -116,16 +302,37 @@ where
                                 hydrated_stream::HydratedInfo::BazelAbort(
                                     bazel_abort_error_info,
                                 ) => {
+                                    if let Some(event_log) = &self_d.event_log {
+                                        event_log.log(SessionEvent::BazelAborted {
+                                            label: bazel_abort_error_info.label.clone(),
+                                            description: bazel_abort_error_info.description.clone(),
+                                        });
+                                    }
                                     let actions_completed = super::process_build_abort_errors::process_build_abort_errors(
                                             self_d.buildozer,
                                             &bazel_abort_error_info
                                         ).await;
 
-                                    if actions_completed > 0 {
+                                    if !actions_completed.is_empty() {
+                                        tx.send(Some(actions_completed)).await.unwrap();
+                                    }
+                                }
+                                hydrated_stream::HydratedInfo::TargetComplete(tce) => {
+                                    if let Some(event_log) = &self_d.event_log {
+                                        event_log.log(SessionEvent::TargetComplete {
+                                            label: tce.label.clone(),
+                                            target_kind: tce.target_kind.clone(),
+                                            success: tce.success,
+                                        });
+                                    }
+                                    self_d.index_target_complete(&tce).await;
+
+                                    let actions_completed =
+                                        self_d.process_unused_dependencies(&tce).await;
+                                    if !actions_completed.is_empty() {
                                         tx.send(Some(actions_completed)).await.unwrap();
                                     }
                                 }
-                                hydrated_stream::HydratedInfo::TargetComplete(_) => {}
                                 hydrated_stream::HydratedInfo::ActionSuccess(_) => (),
                                 hydrated_stream::HydratedInfo::Progress(progress_info) => {
                                     let tbl = Arc::clone(&self_d.previous_global_seen);
@@ -138,7 +345,7 @@ where
                                         )
                                         .await;
 
-                                    if actions_completed > 0 {
+                                    if !actions_completed.is_empty() {
                                         tx.send(Some(actions_completed)).await.unwrap();
                                     }
                                 }
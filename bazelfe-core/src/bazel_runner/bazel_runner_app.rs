@@ -5,21 +5,22 @@ use clap::{AppSettings, Clap};
 use std::path::PathBuf;
 
 use std::env;
-use std::sync::atomic::Ordering;
-use tonic::transport::Server;
-
-use bazelfe_protos::*;
-use std::ffi::OsString;
 
 use bazelfe_core::bazel_runner;
-use bazelfe_core::build_events::build_event_server::bazel_event;
-use bazelfe_core::build_events::build_event_server::BuildEventAction;
-use bazelfe_core::build_events::hydrated_stream::HydratedInfo;
+use bazelfe_core::bazel_runner::action_event_stream::AppliedCorrection;
+use bazelfe_core::bazel_runner::config::{self, Config};
+use bazelfe_core::bazel_runner::daemon::{Daemon, DaemonClient};
+use bazelfe_core::bazel_runner::event_log::{EventLog, SessionEvent};
+use bazelfe_core::bazel_runner::journal::{self, Journal};
+use bazelfe_core::bazel_runner::process_unused_dependencies::UnusedDepsMode;
+use bazelfe_core::bazel_runner::replay;
+use bazelfe_core::bazel_runner::session::{self, BuildEventSender};
+use bazelfe_core::bazel_runner::ExecuteOptions;
+use bazelfe_core::build_events::recording;
+use bazelfe_core::build_events::upstream::Upstream;
 use bazelfe_core::buildozer_driver;
-use google::devtools::build::v1::publish_build_event_server::PublishBuildEventServer;
-use rand::Rng;
-use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use bazelfe_core::buildozer_driver::batching::BatchingBuildozer;
+use bazelfe_core::buildozer_driver::edit_plan::{EditPlan, PlanningBuildozer};
 
 #[derive(Clap, Debug)]
 #[clap(name = "basic", setting = AppSettings::TrailingVarArg)]
@@ -30,101 +31,122 @@ struct Opt {
     #[clap(long, env = "INDEX_INPUT_LOCATION", parse(from_os_str))]
     index_input_location: Option<PathBuf>,
 
+    /// Write classes found in the targets built this session back to the index input location
+    #[clap(long)]
+    persist_index: bool,
+
+    /// Use this buildozer binary to edit BUILD files, rather than editing them in process
     #[clap(long, env = "BUILDOZER_PATH", parse(from_os_str))]
-    buildozer_path: PathBuf,
+    buildozer_path: Option<PathBuf>,
+
+    /// Don't modify any BUILD files, instead print the edits that would have been made as a buildozer script
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Collect the edits proposed in each attempt and only apply them once confirmed on stdin
+    #[clap(long)]
+    confirm_edits: bool,
+
+    /// What to do with deps that targets built don't use, going by the .jdeps files in their outputs: ignore, propose or remove
+    #[clap(long, default_value = "ignore")]
+    unused_deps: UnusedDepsMode,
+
+    /// Print the errors recognized in each failed action, and how the deps added for them were chosen
+    #[clap(long)]
+    explain: bool,
+
+    /// Write a JSON line for each attempt, failed action and edit made to this file, or to an inherited descriptor given as fd:N
+    #[clap(long, env = "BAZEL_FE_EVENT_LOG")]
+    event_log: Option<String>,
+
+    /// Record every BUILD file edit made to this file, so they can be reverted with `bazel-runner undo`
+    #[clap(long, env = "BAZEL_FE_JOURNAL_PATH", parse(from_os_str))]
+    journal_path: Option<PathBuf>,
+
+    /// Record the build events bazel publishes to this file, for use with `bazel-runner replay`
+    #[clap(long, parse(from_os_str))]
+    record_build_events: Option<PathBuf>,
+
+    /// Proxy the build events bazel publishes to this grpc:// backend, in place of any --bes_backend in the command
+    #[clap(long, env = "BAZEL_FE_BES_UPSTREAM")]
+    bes_upstream: Option<String>,
+
+    /// Forward the command to the daemon listening on this socket, see `bazel-runner daemon`
+    #[clap(long, env = "BAZEL_FE_DAEMON_SOCKET", parse(from_os_str))]
+    daemon_socket: Option<PathBuf>,
 
     #[clap(required = true, min_values = 1)]
     passthrough_args: Vec<String>,
 }
-// BuildEventService<bazel_event::BazelBuildEvent>,
-// Arc<Mutex<Option<broadcast::Sender<BuildEventAction<bazel_event::BazelBuildEvent>>>>>,
-// broadcast::Receiver<BuildEventAction<bazel_event::BazelBuildEvent>>,
-
-async fn spawn_bazel_attempt<T>(
-    sender_arc: &Arc<
-        Mutex<Option<broadcast::Sender<BuildEventAction<bazel_event::BazelBuildEvent>>>>,
-    >,
-    aes: &bazel_runner::action_event_stream::ActionEventStream<T>,
-    bes_port: u16,
-    passthrough_args: &Vec<String>,
-) -> (u32, bazel_runner::ExecuteResult)
-where
-    T: bazelfe_core::buildozer_driver::Buildozer + Send + Clone + Sync + 'static,
-{
-    let (tx, rx) = broadcast::channel(8192);
-    let _ = {
-        let mut locked = sender_arc.lock().await;
-        *locked = Some(tx);
-    };
-    let error_stream = HydratedInfo::build_transformer(rx);
 
-    let mut target_extracted_stream = aes.build_action_pipeline(error_stream);
+#[derive(Clap, Debug)]
+#[clap(name = "replay")]
+struct ReplayOpt {
+    /// A recording made with `--record-build-events`
+    #[clap(parse(from_os_str))]
+    recording: PathBuf,
 
-    let actions_completed: Arc<std::sync::atomic::AtomicU32> =
-        Arc::new(std::sync::atomic::AtomicU32::new(0));
+    #[clap(long, env = "INDEX_INPUT_LOCATION", parse(from_os_str))]
+    index_input_location: Option<PathBuf>,
 
-    let recv_ver = Arc::clone(&actions_completed);
-    let recv_task = tokio::spawn(async move {
-        while let Some(action) = target_extracted_stream.recv().await {
-            match action {
-                None => (),
-                Some(err_info) => {
-                    recv_ver.fetch_add(err_info, Ordering::Relaxed);
-                }
-            }
-        }
-    });
-    let res = bazel_runner::execute_bazel(passthrough_args.clone(), bes_port).await;
+    #[clap(long, env = "BUILDOZER_PATH", parse(from_os_str))]
+    buildozer_path: Option<PathBuf>,
 
-    info!("Bazel completed with state: {:?}", res);
-    let _ = {
-        let mut locked = sender_arc.lock().await;
-        locked.take();
-    };
+    /// Print the errors recognized in each failed action, and how the deps added for them were chosen
+    #[clap(long)]
+    explain: bool,
+}
+
+#[derive(Clap, Debug)]
+#[clap(name = "daemon")]
+struct DaemonOpt {
+    /// Listen for commands forwarded by bazel-runner on this unix socket
+    #[clap(long, env = "BAZEL_FE_DAEMON_SOCKET", parse(from_os_str))]
+    socket_path: PathBuf,
+
+    #[clap(long, env = "BIND_ADDRESS")]
+    bind_address: Option<String>,
+
+    #[clap(long, env = "INDEX_INPUT_LOCATION", parse(from_os_str))]
+    index_input_location: Option<PathBuf>,
+
+    #[clap(long, env = "BUILDOZER_PATH", parse(from_os_str))]
+    buildozer_path: Option<PathBuf>,
 
-    recv_task.await.unwrap();
-    info!("Receive task done");
-    (actions_completed.fetch_add(0, Ordering::Relaxed), res)
+    #[clap(long, env = "BAZEL_FE_JOURNAL_PATH", parse(from_os_str))]
+    journal_path: Option<PathBuf>,
+
+    /// Write classes found in the targets built back to the index input location after each command
+    #[clap(long)]
+    persist_index: bool,
 }
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let opt = Opt::parse();
-
-    // If someone is using a bes backend we need to nope out so we don't conflict.
-    // This also means our other tools can call in using our same utilities
-    // with this already set to make this app passthrough
-    if opt
-        .passthrough_args
-        .contains(&String::from("--bes_backend"))
-    {
-        let application: OsString = opt
-            .passthrough_args
-            .first()
-            .map(|a| {
-                let a: String = a.clone().into();
-                a
-            })
-            .expect("Should have had at least one arg the bazel process itself.")
-            .into();
-
-        let remaining_args: Vec<OsString> = opt
-            .passthrough_args
-            .iter()
-            .skip(1)
-            .map(|str_ref| {
-                let a: String = str_ref.clone().into();
-                let a: OsString = a.into();
-                a
-            })
-            .collect();
-
-        let resp = ::exec::Command::new(application)
-            .args(&remaining_args)
-            .exec();
-        panic!("Should be unreachable: {:#?}", resp);
+
+#[derive(Clap, Debug)]
+#[clap(name = "undo")]
+struct UndoOpt {
+    #[clap(long, env = "BAZEL_FE_JOURNAL_PATH", parse(from_os_str))]
+    journal_path: PathBuf,
+
+    #[clap(long, env = "BUILDOZER_PATH", parse(from_os_str))]
+    buildozer_path: Option<PathBuf>,
+
+    /// Only undo this action, rather than every action from the most recent session
+    #[clap(long)]
+    action_id: Option<u64>,
+}
+fn confirm_edits_on_stdin(edit_plan: &EditPlan) -> bool {
+    eprint!("Apply the {} edits above? [y/N] ", edit_plan.len());
+    let mut response = String::new();
+    match std::io::stdin().read_line(&mut response) {
+        Ok(_) => {
+            let response = response.trim().to_lowercase();
+            response == "y" || response == "yes"
+        }
+        Err(_) => false,
     }
+}
 
-    let mut rng = rand::thread_rng();
+fn init_logger() {
     let mut builder = pretty_env_logger::formatted_timed_builder();
     builder.format_timestamp_nanos();
     builder.target(env_logger::fmt::Target::Stderr);
@@ -133,56 +155,309 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     builder.init();
+}
 
-    bazel_runner::register_ctrlc_handler();
+async fn run_undo(opt: UndoOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let journal = Journal::new(opt.journal_path);
+    let reverted = match opt.buildozer_path {
+        Some(buildozer_path) => {
+            let buildozer = buildozer_driver::from_binary_path(buildozer_path);
+            journal::undo(&buildozer, &journal, opt.action_id).await?
+        }
+        None => {
+            let buildozer = buildozer_driver::from_workspace_root(config::workspace_root()?);
+            journal::undo(&buildozer, &journal, opt.action_id).await?
+        }
+    };
+    println!("Reverted {} BUILD file edits", reverted);
+    Ok(())
+}
 
+async fn replay_recording<T>(opt: ReplayOpt, buildozer: T) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: bazelfe_core::buildozer_driver::Buildozer + Send + Clone + Sync + 'static,
+{
+    let requests = recording::read_recording(&opt.recording)?;
     let aes = bazel_runner::action_event_stream::ActionEventStream::new(
         opt.index_input_location,
-        buildozer_driver::from_binary_path(opt.buildozer_path),
+        PlanningBuildozer::new(buildozer, EditPlan::new()),
+    )
+    .with_config(Config::load(&config::workspace_root()?)?)
+    .with_explain(opt.explain);
+    let corrections = replay::replay(requests, &aes).await;
+    if corrections.is_empty() {
+        println!("No BUILD file edits would have been made");
+    }
+    for correction in corrections.iter() {
+        println!(
+            "{}\t{}",
+            correction.src_fn,
+            correction.edit.to_buildozer_command()
+        );
+    }
+    Ok(())
+}
+
+async fn run_replay(opt: ReplayOpt) -> Result<(), Box<dyn std::error::Error>> {
+    match opt.buildozer_path.clone() {
+        Some(buildozer_path) => {
+            let buildozer = buildozer_driver::from_binary_path(buildozer_path);
+            replay_recording(opt, buildozer).await
+        }
+        None => {
+            let buildozer = buildozer_driver::from_workspace_root(config::workspace_root()?);
+            replay_recording(opt, buildozer).await
+        }
+    }
+}
+
+async fn run_daemon(opt: DaemonOpt) -> Result<(), Box<dyn std::error::Error>> {
+    bazel_runner::register_ctrlc_handler();
+    let workspace_root = config::workspace_root()?;
+    let (sender_arc, bes_port) = session::start_build_event_service(opt.bind_address, None, None)?;
+    let journal = opt.journal_path.map(Journal::new);
+
+    info!(
+        "Daemon serving {:?} on {:?}",
+        workspace_root, opt.socket_path
     );
+    match opt.buildozer_path {
+        Some(buildozer_path) => {
+            let buildozer = buildozer_driver::from_binary_path(buildozer_path);
+            Daemon::new(
+                sender_arc,
+                bes_port,
+                workspace_root,
+                opt.index_input_location,
+                buildozer,
+                journal,
+                opt.persist_index,
+            )?
+            .serve(&opt.socket_path)
+            .await
+        }
+        None => {
+            let buildozer = buildozer_driver::from_workspace_root(workspace_root.clone());
+            Daemon::new(
+                sender_arc,
+                bes_port,
+                workspace_root,
+                opt.index_input_location,
+                buildozer,
+                journal,
+                opt.persist_index,
+            )?
+            .serve(&opt.socket_path)
+            .await
+        }
+    }
+}
 
-    let default_port = {
-        let rand_v: u16 = rng.gen();
-        40000 + (rand_v % 3000)
+async fn run_attempts<T>(
+    opt: Opt,
+    config: Config,
+    event_log: Option<EventLog>,
+    buildozer: T,
+    sender_arc: &BuildEventSender,
+    bes_port: u16,
+) -> i32
+where
+    T: bazelfe_core::buildozer_driver::Buildozer + Send + Clone + Sync + 'static,
+{
+    let journal = opt.journal_path.map(Journal::new);
+    let options = ExecuteOptions {
+        build_event_file: bazel_runner::build_event_file_for(&opt.passthrough_args),
+        unused_deps: opt.unused_deps,
+        ..Default::default()
     };
 
-    let addr: std::net::SocketAddr = opt
-        .bind_address
-        .map(|s| s.to_owned())
-        .or(env::var("BIND_ADDRESS").ok())
-        .unwrap_or_else(|| format!("127.0.0.1:{}", default_port).into())
-        .parse()
-        .expect("can't parse BIND_ADDRESS variable");
+    if opt.dry_run || opt.confirm_edits {
+        let session_id = session::new_session_id();
+        let mut attempts: u16 = 0;
+        let mut final_exit_code = 0;
+        let edit_plan = EditPlan::new();
+        // Edits are only shown for failed builds, so removals are reported separately
+        let unused_deps = match opt.unused_deps {
+            UnusedDepsMode::Remove => UnusedDepsMode::Propose,
+            other => other,
+        };
+        let aes = bazel_runner::action_event_stream::ActionEventStream::new(
+            opt.index_input_location,
+            PlanningBuildozer::new(buildozer.clone(), edit_plan.clone()),
+        )
+        .with_config(config)
+        .with_explain(opt.explain)
+        .with_event_log(event_log.clone())
+        .with_unused_deps(unused_deps);
+        if let Some(event_log) = &event_log {
+            event_log.start_session(session_id, &opt.passthrough_args);
+        }
+        while attempts < session::MAX_ATTEMPTS {
+            if let Some(event_log) = &event_log {
+                event_log.start_attempt(attempts);
+            }
+            let (proposed_corrections, bazel_result) = session::spawn_bazel_attempt(
+                sender_arc,
+                &aes,
+                bes_port,
+                &opt.passthrough_args,
+                &options,
+            )
+            .await;
+            final_exit_code = bazel_result.exit_code;
+            if let Some(event_log) = &event_log {
+                event_log.log(SessionEvent::AttemptFinished {
+                    exit_code: bazel_result.exit_code,
+                });
+            }
+            if bazel_result.exit_code == 0 || edit_plan.is_empty() {
+                break;
+            }
 
-    let passthrough_args = opt.passthrough_args.clone();
-    info!("Services listening on {}", addr);
+            println!("Proposed BUILD file edits:");
+            print!("{}", edit_plan.to_buildozer_script());
 
-    let (bes, sender_arc, _) =
-    bazelfe_core::build_events::build_event_server::build_bazel_build_events_service();
+            if opt.dry_run || !confirm_edits_on_stdin(&edit_plan) {
+                break;
+            }
+            let applied = edit_plan.apply(&buildozer).await;
+            info!("Applied {} edits", applied.len());
+            if applied.is_empty() {
+                break;
+            }
+            let applied_corrections: Vec<AppliedCorrection> = proposed_corrections
+                .into_iter()
+                .filter(|c| applied.contains(&c.edit))
+                .collect();
+            session::record_in_journal(&journal, session_id, attempts, &applied_corrections);
+            if let Some(event_log) = &event_log {
+                event_log.log_edits(&applied_corrections);
+            }
+            attempts += 1;
+        }
+        if let Some(event_log) = &event_log {
+            event_log.log(SessionEvent::SessionFinished {
+                exit_code: final_exit_code,
+                attempts,
+            });
+        }
+        if opt.persist_index {
+            session::persist_index(&aes).await;
+        }
+        session::report_proposed_removals(&aes);
+        info!("Attempts/build cycles: {:?}", attempts);
+        final_exit_code
+    } else {
+        let batching_buildozer = BatchingBuildozer::new(buildozer);
+        let aes = bazel_runner::action_event_stream::ActionEventStream::new(
+            opt.index_input_location,
+            batching_buildozer.clone(),
+        )
+        .with_config(config)
+        .with_explain(opt.explain)
+        .with_event_log(event_log)
+        .with_unused_deps(opt.unused_deps);
+        let final_exit_code = session::run_batched_attempts(
+            sender_arc,
+            &aes,
+            &batching_buildozer,
+            bes_port,
+            &opt.passthrough_args,
+            &journal,
+            &options,
+        )
+        .await;
+        if opt.persist_index {
+            session::persist_index(&aes).await;
+        }
+        session::report_proposed_removals(&aes);
+        final_exit_code
+    }
+}
 
-    let bes_port: u16 = addr.port();
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if env::args().nth(1).as_deref() == Some("undo") {
+        init_logger();
+        return run_undo(UndoOpt::parse_from(env::args().skip(1))).await;
+    }
+    if env::args().nth(1).as_deref() == Some("replay") {
+        init_logger();
+        return run_replay(ReplayOpt::parse_from(env::args().skip(1))).await;
+    }
+    if env::args().nth(1).as_deref() == Some("daemon") {
+        init_logger();
+        return run_daemon(DaemonOpt::parse_from(env::args().skip(1))).await;
+    }
 
-    let _service_fut = tokio::spawn(async move {
-        Server::builder()
-            .add_service(PublishBuildEventServer::new(bes))
-            .serve(addr)
-            .await
-            .unwrap();
-    });
-
-    let mut attempts: u16 = 0;
-
-    let mut final_exit_code = 0;
-    while attempts < 15 {
-        let (actions_corrected, bazel_result) =
-            spawn_bazel_attempt(&sender_arc, &aes, bes_port, &passthrough_args).await;
-        final_exit_code = bazel_result.exit_code;
-        if bazel_result.exit_code == 0 || actions_corrected == 0 {
-            break;
-        }
-        attempts += 1;
+    let mut opt = Opt::parse();
+
+    init_logger();
+
+    // The daemon has its own build event service and terminal, so anything needing either
+    // of ours is run in process
+    let needs_in_process = opt.confirm_edits
+        || opt.explain
+        || opt.event_log.is_some()
+        || opt.record_build_events.is_some()
+        || opt.bes_upstream.is_some();
+    match &opt.daemon_socket {
+        Some(daemon_socket) if needs_in_process => info!(
+            "Not using the daemon at {:?}, as --confirm-edits, --explain, --event-log, --record-build-events and --bes-upstream need running in process",
+            daemon_socket
+        ),
+        Some(daemon_socket) => match DaemonClient::connect(daemon_socket).await {
+            Ok(mut client) => {
+                let exit_code = client
+                    .run(
+                        opt.passthrough_args.clone(),
+                        &env::current_dir()?,
+                        opt.dry_run,
+                        opt.unused_deps,
+                    )
+                    .await?;
+                std::process::exit(exit_code);
+            }
+            Err(e) => warn!(
+                "Unable to reach the daemon at {:?}, running in process: {:?}",
+                daemon_socket, e
+            ),
+        },
+        None => (),
     }
 
-    info!("Attempts/build cycles: {:?}", attempts);
+    bazel_runner::register_ctrlc_handler();
+
+    let workspace_root = config::workspace_root()?;
+    let config = Config::load(&workspace_root)?;
+    let event_log = match opt.event_log.as_ref() {
+        Some(destination) => Some(EventLog::open(destination)?),
+        None => None,
+    };
+    let upstream = match opt.bes_upstream.as_ref() {
+        Some(backend) => {
+            // Bazel has to publish to us, with us passing its events on to the backend
+            opt.passthrough_args =
+                bazel_runner::without_flag(&opt.passthrough_args, "--bes_backend");
+            Some(Upstream::new(backend)?)
+        }
+        None => None,
+    };
+    let (sender_arc, bes_port) = session::start_build_event_service(
+        opt.bind_address.clone(),
+        opt.record_build_events.clone(),
+        upstream,
+    )?;
+
+    let final_exit_code = match opt.buildozer_path.clone() {
+        Some(buildozer_path) => {
+            let buildozer = buildozer_driver::from_binary_path(buildozer_path);
+            run_attempts(opt, config, event_log, buildozer, &sender_arc, bes_port).await
+        }
+        None => {
+            let buildozer = buildozer_driver::from_workspace_root(workspace_root);
+            run_attempts(opt, config, event_log, buildozer, &sender_arc, bes_port).await
+        }
+    };
     std::process::exit(final_exit_code);
 }
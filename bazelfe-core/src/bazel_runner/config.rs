@@ -0,0 +1,256 @@
+// Per workspace settings, read from .bazel-fe.toml in the workspace root. This is where
+// rules we don't know about, such as macros wrapping the rules we do, are described:
+//
+// [rule_kinds.company_scala_library]
+// extractor = "scala"
+// forbidden_deps = ["@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library"]
+// indexed = true
+//
+// [rule_kinds.java_binary]
+// extractor = "java"
+//
+// Where targets are guessed to be when the index doesn't know a class can also be set, with
+// {package_path} standing for the class's package as a path and {last_segment} for the last
+// part of it:
+//
+// guess_templates = [
+//   "//java/{package_path}:{last_segment}",
+//   "//scala/{package_path}:{last_segment}",
+//   "//src/test/scala/{package_path}:{last_segment}",
+// ]
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error_extraction::ExtractorFamily;
+
+pub const CONFIG_FILE_NAME: &str = ".bazel-fe.toml";
+
+// The closest directory at or above the one given with a WORKSPACE file, as bazel finds it
+pub fn find_workspace_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join("WORKSPACE").exists() || dir.join("WORKSPACE.bazel").exists())
+        .map(|dir| dir.to_path_buf())
+}
+
+// Where `bazel run` started us from, otherwise the workspace we're in. Outside of any
+// workspace it's the current directory.
+pub fn workspace_root() -> io::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("BUILD_WORKSPACE_DIRECTORY") {
+        return Ok(PathBuf::from(dir));
+    }
+    let current_dir = std::env::current_dir()?;
+    Ok(find_workspace_root(&current_dir).unwrap_or(current_dir))
+}
+
+pub const DEFAULT_GUESS_TEMPLATES: &[&str] = &[
+    "//src/main/scala/{package_path}:{last_segment}",
+    "//src/main/java/{package_path}:{last_segment}",
+];
+
+// These are things that are already implicit dependencies so we should ensure they are not
+// included, unless the workspace says otherwise.
+fn default_forbidden_deps(rule_kind: &str) -> &'static [&'static str] {
+    match rule_kind {
+        "scala_library" => &["@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library"],
+        "scala_test" => &[
+            "@third_party_jvm//3rdparty/jvm/org/scalatest",
+            "@third_party_jvm//3rdparty/jvm/org/scalatest:scalatest",
+            "@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library",
+        ],
+        _ => &[],
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleKindConfig {
+    // Whose compiler output failed actions of the rule are read as
+    pub extractor: Option<ExtractorFamily>,
+    // Deps the rule adds itself, which we should never add. Replaces any defaults for the rule.
+    pub forbidden_deps: Option<Vec<String>>,
+    // Whether the classes in the rule's jars are indexed when it builds
+    pub indexed: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rule_kinds: HashMap<String, RuleKindConfig>,
+    pub guess_templates: Option<Vec<String>>,
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    // Workspaces without a config file get the defaults
+    pub fn load(workspace_root: &Path) -> io::Result<Config> {
+        let path = workspace_root.join(CONFIG_FILE_NAME);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        Config::parse(&contents).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unable to parse {:?}: {}", path, e),
+            )
+        })
+    }
+
+    pub fn extractor_for(&self, rule_kind: &str) -> Option<ExtractorFamily> {
+        self.rule_kinds
+            .get(rule_kind)
+            .and_then(|c| c.extractor)
+            .or_else(|| ExtractorFamily::for_builtin_rule_kind(rule_kind))
+    }
+
+    pub fn is_forbidden_dep(&self, rule_kind: &str, label: &str) -> bool {
+        match self
+            .rule_kinds
+            .get(rule_kind)
+            .and_then(|c| c.forbidden_deps.as_ref())
+        {
+            Some(forbidden_deps) => forbidden_deps.iter().any(|e| e == label),
+            None => default_forbidden_deps(rule_kind).contains(&label),
+        }
+    }
+
+    pub fn guess_templates(&self) -> Vec<String> {
+        match &self.guess_templates {
+            Some(templates) => templates.clone(),
+            None => DEFAULT_GUESS_TEMPLATES
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn is_indexed(&self, rule_kind: &str) -> bool {
+        self.rule_kinds
+            .get(rule_kind)
+            .and_then(|c| c.indexed)
+            .unwrap_or_else(|| crate::jvm_indexer::DEFAULT_INDEXED_RULE_KINDS.contains(&rule_kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+[rule_kinds.company_scala_library]
+extractor = "scala"
+forbidden_deps = ["@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library"]
+indexed = true
+
+[rule_kinds.java_library]
+indexed = false
+
+[rule_kinds.scala_junit_test]
+extractor = "scala"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.extractor_for("company_scala_library"),
+            Some(ExtractorFamily::Scala)
+        );
+        assert_eq!(
+            config.extractor_for("scala_junit_test"),
+            Some(ExtractorFamily::Scala)
+        );
+        assert_eq!(
+            config.extractor_for("java_library"),
+            Some(ExtractorFamily::Java)
+        );
+        assert_eq!(config.extractor_for("genrule"), None);
+
+        assert!(config.is_forbidden_dep(
+            "company_scala_library",
+            "@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library"
+        ));
+        assert!(!config.is_forbidden_dep(
+            "scala_junit_test",
+            "@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library"
+        ));
+
+        assert!(config.is_indexed("company_scala_library"));
+        assert!(!config.is_indexed("java_library"));
+        assert!(!config.is_indexed("scala_junit_test"));
+        assert!(config.is_indexed("scala_library"));
+    }
+
+    #[test]
+    fn test_defaults_are_replaced() {
+        let scala_library = "@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library";
+        let config = Config::default();
+        assert!(config.is_forbidden_dep("scala_library", scala_library));
+        assert!(config.is_forbidden_dep("scala_test", scala_library));
+        assert_eq!(
+            config.guess_templates(),
+            vec![
+                String::from("//src/main/scala/{package_path}:{last_segment}"),
+                String::from("//src/main/java/{package_path}:{last_segment}"),
+            ]
+        );
+
+        let config = Config::parse(
+            r#"
+guess_templates = ["//scala/{package_path}:{last_segment}"]
+
+[rule_kinds.scala_library]
+forbidden_deps = ["@maven//:org_scala_lang_scala_library"]
+"#,
+        )
+        .unwrap();
+        assert!(!config.is_forbidden_dep("scala_library", scala_library));
+        assert!(config.is_forbidden_dep("scala_library", "@maven//:org_scala_lang_scala_library"));
+        assert!(config.is_forbidden_dep("scala_test", scala_library));
+        assert_eq!(
+            config.guess_templates(),
+            vec![String::from("//scala/{package_path}:{last_segment}")]
+        );
+    }
+
+    #[test]
+    fn test_load_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Config::load(dir.path()).unwrap(), Config::default());
+
+        std::fs::write(
+            dir.path().join(CONFIG_FILE_NAME),
+            "[rule_kinds.java_binary]\nextractor = \"groovy\"\n",
+        )
+        .unwrap();
+        assert!(Config::load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_find_workspace_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("src/main/java/com/example");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        assert_eq!(find_workspace_root(&package_dir), None);
+
+        std::fs::write(dir.path().join("WORKSPACE.bazel"), "").unwrap();
+        assert_eq!(
+            find_workspace_root(&package_dir),
+            Some(dir.path().to_path_buf())
+        );
+        assert_eq!(
+            find_workspace_root(dir.path()),
+            Some(dir.path().to_path_buf())
+        );
+    }
+}
@@ -0,0 +1,465 @@
+// A long lived bazel-runner, serving over a unix socket. It keeps the index, the state
+// carried between attempts and the build event service warm, so invocations forwarded to
+// it by a thin client don't pay to set them up each time.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bazelfe_protos::bazel_tools::daemon_service_client::DaemonServiceClient;
+use bazelfe_protos::bazel_tools::daemon_service_server::{DaemonService, DaemonServiceServer};
+use bazelfe_protos::bazel_tools::{run_command_response, RunCommandRequest, RunCommandResponse};
+use futures::TryStreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, Mutex};
+use tonic::transport::{Channel, Endpoint, Server, Uri};
+use tonic::{Request, Response, Status};
+use tower::service_fn;
+
+use super::action_event_stream::ActionEventStream;
+use super::config::Config;
+use super::journal::Journal;
+use super::process_unused_dependencies::UnusedDepsMode;
+use super::session::{self, BuildEventSender};
+use super::{ExecuteOptions, OutputSink, ProcessOutput};
+use crate::buildozer_driver::{batching::BatchingBuildozer, Buildozer};
+
+#[derive(Debug)]
+struct DaemonState<T: Buildozer + Send + Sync + Clone + 'static> {
+    sender_arc: BuildEventSender,
+    bes_port: u16,
+    workspace_root: PathBuf,
+    aes: ActionEventStream<BatchingBuildozer<T>>,
+    batching_buildozer: BatchingBuildozer<T>,
+    journal: Option<Journal>,
+    persist_index: bool,
+    // The build event service feeds a single pipeline, so commands are run one at a time
+    run_lock: Mutex<()>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Daemon<T: Buildozer + Send + Sync + Clone + 'static> {
+    state: Arc<DaemonState<T>>,
+}
+
+impl<T> Daemon<T>
+where
+    T: Buildozer + Send + Sync + Clone + 'static,
+{
+    // The workspace's config is read once, so the daemon needs restarting to pick up changes
+    pub fn new(
+        sender_arc: BuildEventSender,
+        bes_port: u16,
+        workspace_root: PathBuf,
+        index_input_location: Option<PathBuf>,
+        buildozer: T,
+        journal: Option<Journal>,
+        persist_index: bool,
+    ) -> std::io::Result<Self> {
+        let config = Config::load(&workspace_root)?;
+        let batching_buildozer = BatchingBuildozer::new(buildozer);
+        let aes = ActionEventStream::new(index_input_location, batching_buildozer.clone())
+            .with_config(config);
+        Ok(Self {
+            state: Arc::new(DaemonState {
+                sender_arc,
+                bes_port,
+                workspace_root,
+                aes,
+                batching_buildozer,
+                journal,
+                persist_index,
+                run_lock: Mutex::new(()),
+            }),
+        })
+    }
+
+    // A dry run makes a single attempt, reporting the edits it would have made rather than
+    // applying them, as bazel-runner --dry-run does
+    pub async fn run(
+        &self,
+        args: Vec<String>,
+        options: ExecuteOptions,
+        dry_run: bool,
+        unused_deps: UnusedDepsMode,
+    ) -> i32 {
+        let state = &self.state;
+        let _guard = state.run_lock.lock().await;
+        // Edits are only shown for failed builds, so removals are reported separately
+        let unused_deps = match unused_deps {
+            UnusedDepsMode::Remove if dry_run => UnusedDepsMode::Propose,
+            other => other,
+        };
+        // Clones share the warm index and what earlier commands have seen
+        let mut aes = state.aes.clone().with_unused_deps(unused_deps);
+        if dry_run {
+            aes = aes.with_fresh_previous_seen();
+        }
+        let exit_code = if dry_run {
+            let (_, bazel_result) = session::spawn_bazel_attempt(
+                &state.sender_arc,
+                &aes,
+                state.bes_port,
+                &args,
+                &options,
+            )
+            .await;
+            let pending = state.batching_buildozer.pending();
+            if bazel_result.exit_code != 0 && !pending.is_empty() {
+                let script = pending.to_buildozer_script();
+                write_stdout(
+                    &options.output,
+                    format!("Proposed BUILD file edits:\n{}", script),
+                )
+                .await;
+            }
+            pending.take();
+            bazel_result.exit_code
+        } else {
+            session::run_batched_attempts(
+                &state.sender_arc,
+                &aes,
+                &state.batching_buildozer,
+                state.bes_port,
+                &args,
+                &state.journal,
+                &options,
+            )
+            .await
+        };
+        if state.persist_index {
+            session::persist_index(&aes).await;
+        }
+        let report = session::proposed_removals_report(&aes.take_proposed_removals());
+        write_stdout(&options.output, report).await;
+        exit_code
+    }
+
+    // Serves requests on the socket until the server fails, replacing any stale socket
+    // left behind by a previous daemon.
+    pub async fn serve(self, socket_path: &Path) -> Result<(), Box<dyn Error>> {
+        if socket_path.exists() {
+            std::fs::remove_file(socket_path)?;
+        }
+        let mut uds = UnixListener::bind(socket_path)?;
+        Server::builder()
+            .add_service(DaemonServiceServer::new(self))
+            .serve_with_incoming(uds.incoming().map_ok(crate::tokioext::unix::UnixStream))
+            .await?;
+        Ok(())
+    }
+}
+
+// Reports from the daemon go to the client along with bazel's output
+async fn write_stdout(output: &OutputSink, text: String) {
+    if text.is_empty() {
+        return;
+    }
+    match output {
+        OutputSink::Terminal => print!("{}", text),
+        OutputSink::Discard => (),
+        OutputSink::Channel(tx) => {
+            let _ = tx
+                .clone()
+                .send(ProcessOutput::Stdout(text.into_bytes()))
+                .await;
+        }
+    }
+}
+
+fn output_response(output: ProcessOutput) -> RunCommandResponse {
+    let payload = match output {
+        ProcessOutput::Stdout(bytes) => run_command_response::Payload::Stdout(bytes),
+        ProcessOutput::Stderr(bytes) => run_command_response::Payload::Stderr(bytes),
+    };
+    RunCommandResponse {
+        payload: Some(payload),
+    }
+}
+
+#[tonic::async_trait]
+impl<T> DaemonService for Daemon<T>
+where
+    T: Buildozer + Send + Sync + Clone + 'static,
+{
+    type RunCommandStream = mpsc::Receiver<Result<RunCommandResponse, Status>>;
+
+    async fn run_command(
+        &self,
+        request: Request<RunCommandRequest>,
+    ) -> Result<Response<Self::RunCommandStream>, Status> {
+        let request = request.into_inner();
+        if request.args.is_empty() {
+            return Err(Status::invalid_argument(
+                "Expected at least the bazel binary to run",
+            ));
+        }
+        let working_directory = PathBuf::from(&request.working_directory);
+        if !working_directory.starts_with(&self.state.workspace_root) {
+            return Err(Status::failed_precondition(format!(
+                "This daemon serves {:?}, which doesn't contain {:?}",
+                self.state.workspace_root, working_directory
+            )));
+        }
+        let unused_deps = if request.unused_deps.is_empty() {
+            UnusedDepsMode::Ignore
+        } else {
+            request
+                .unused_deps
+                .parse::<UnusedDepsMode>()
+                .map_err(Status::invalid_argument)?
+        };
+        let dry_run = request.dry_run;
+
+        let (mut tx, rx) = mpsc::channel(256);
+        let daemon = self.clone();
+        tokio::spawn(async move {
+            let (output_tx, mut output_rx) = mpsc::channel(256);
+            let mut forward_tx = tx.clone();
+            let forward_task = tokio::spawn(async move {
+                while let Some(output) = output_rx.recv().await {
+                    // Keep draining when the client goes away, so bazel isn't blocked on us
+                    let _ = forward_tx.send(Ok(output_response(output))).await;
+                }
+            });
+
+            let options = ExecuteOptions {
+                output: OutputSink::Channel(output_tx),
+                build_event_file: super::build_event_file_for(&request.args)
+                    .map(|path| working_directory.join(path)),
+                working_directory: Some(working_directory),
+                unused_deps,
+            };
+            let exit_code = daemon
+                .run(request.args, options, dry_run, unused_deps)
+                .await;
+            let _ = forward_task.await;
+            let _ = tx
+                .send(Ok(RunCommandResponse {
+                    payload: Some(run_command_response::Payload::ExitCode(exit_code)),
+                }))
+                .await;
+        });
+        Ok(Response::new(rx))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DaemonClient {
+    client: DaemonServiceClient<Channel>,
+}
+
+impl DaemonClient {
+    pub async fn connect(socket_path: &Path) -> Result<DaemonClient, Box<dyn Error>> {
+        let socket_path = socket_path.to_path_buf();
+        // The uri is required by tonic, but unused as we always connect to the socket
+        let channel = Endpoint::try_from("http://[::]:50051")?
+            .connect_with_connector(service_fn(move |_: Uri| {
+                UnixStream::connect(socket_path.clone())
+            }))
+            .await?;
+        Ok(DaemonClient {
+            client: DaemonServiceClient::new(channel),
+        })
+    }
+
+    // Runs the command in the daemon, relaying its output to ours, and returns its exit code
+    pub async fn run(
+        &mut self,
+        args: Vec<String>,
+        working_directory: &Path,
+        dry_run: bool,
+        unused_deps: UnusedDepsMode,
+    ) -> Result<i32, Box<dyn Error>> {
+        let request = RunCommandRequest {
+            args,
+            working_directory: working_directory.to_string_lossy().to_string(),
+            dry_run,
+            unused_deps: unused_deps.to_string(),
+        };
+        let mut responses = self.client.run_command(request).await?.into_inner();
+
+        let mut stdout = tokio::io::stdout();
+        let mut stderr = tokio::io::stderr();
+        let mut exit_code = None;
+        while let Some(response) = responses.message().await? {
+            match response.payload {
+                Some(run_command_response::Payload::Stdout(bytes)) => {
+                    stdout.write_all(&bytes).await?
+                }
+                Some(run_command_response::Payload::Stderr(bytes)) => {
+                    stderr.write_all(&bytes).await?
+                }
+                Some(run_command_response::Payload::ExitCode(code)) => exit_code = Some(code),
+                None => (),
+            }
+        }
+        exit_code.ok_or_else(|| "The daemon stopped before the command completed".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_run_command_through_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace_root = dir.path().to_path_buf();
+        let socket_path = dir.path().join("daemon.sock");
+
+        let (_bes, sender_arc, _) =
+            crate::build_events::build_event_server::build_bazel_build_events_service();
+        let daemon = Daemon::new(
+            sender_arc,
+            0,
+            workspace_root.clone(),
+            None,
+            crate::buildozer_driver::from_workspace_root(workspace_root.clone()),
+            None,
+            false,
+        )
+        .unwrap();
+        let serve_path = socket_path.clone();
+        tokio::spawn(async move { daemon.serve(&serve_path).await.unwrap() });
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        let exit_code = client
+            .run(
+                vec![
+                    String::from("sh"),
+                    String::from("-c"),
+                    String::from("echo hello; exit 3"),
+                ],
+                &workspace_root,
+                false,
+                UnusedDepsMode::Ignore,
+            )
+            .await
+            .unwrap();
+        assert_eq!(exit_code, 3);
+
+        // Requests from outside the daemon's workspace are refused
+        assert!(client
+            .run(
+                vec![String::from("true")],
+                Path::new("/"),
+                false,
+                UnusedDepsMode::Ignore
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_through_daemon() {
+        use ::prost::Message;
+        use bazelfe_protos::build_event_stream;
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace_root = dir.path().to_path_buf();
+        let socket_path = dir.path().join("daemon.sock");
+
+        let build_file = workspace_root.join("foo/BUILD");
+        let build_file_content =
+            "java_library(\n    name = \"foo\",\n    deps = [\n        \"//a:a\",\n    ],\n)\n";
+        std::fs::create_dir_all(workspace_root.join("foo")).unwrap();
+        std::fs::write(&build_file, build_file_content).unwrap();
+
+        // Each attempt fails, with javac's strict deps telling us what to add
+        let stderr_path = workspace_root.join("stderr.txt");
+        std::fs::write(&stderr_path, "buildozer 'add deps //bar:bar' //foo:foo\n").unwrap();
+        let action_failed = build_event_stream::BuildEvent {
+            id: Some(build_event_stream::BuildEventId {
+                id: Some(build_event_stream::build_event_id::Id::ActionCompleted(
+                    build_event_stream::build_event_id::ActionCompletedId {
+                        label: String::from("//foo:foo"),
+                        ..Default::default()
+                    },
+                )),
+            }),
+            payload: Some(build_event_stream::build_event::Payload::Action(
+                build_event_stream::ActionExecuted {
+                    success: false,
+                    r#type: String::from("Javac"),
+                    stderr: Some(build_event_stream::File {
+                        file: Some(build_event_stream::file::File::Uri(format!(
+                            "file://{}",
+                            stderr_path.to_string_lossy()
+                        ))),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        };
+        let recorded_events = workspace_root.join("recorded.bin");
+        let mut buf = vec![];
+        action_failed.encode_length_delimited(&mut buf).unwrap();
+        std::fs::write(&recorded_events, buf).unwrap();
+
+        let build_event_file = workspace_root.join("build_events.bin");
+        let command = vec![
+            String::from("sh"),
+            String::from("-c"),
+            format!(
+                "cp {} {}; exit 1",
+                recorded_events.to_string_lossy(),
+                build_event_file.to_string_lossy()
+            ),
+            String::from("--bes_backend=grpc://127.0.0.1:1"),
+            format!(
+                "--build_event_binary_file={}",
+                build_event_file.to_string_lossy()
+            ),
+        ];
+
+        let (_bes, sender_arc, _) =
+            crate::build_events::build_event_server::build_bazel_build_events_service();
+        let daemon = Daemon::new(
+            sender_arc,
+            0,
+            workspace_root.clone(),
+            None,
+            crate::buildozer_driver::from_workspace_root(workspace_root.clone()),
+            None,
+            false,
+        )
+        .unwrap();
+        let serving_daemon = daemon.clone();
+        let serve_path = socket_path.clone();
+        tokio::spawn(async move { serving_daemon.serve(&serve_path).await.unwrap() });
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        let exit_code = client
+            .run(
+                command.clone(),
+                &workspace_root,
+                true,
+                UnusedDepsMode::Ignore,
+            )
+            .await
+            .unwrap();
+        assert_eq!(exit_code, 1);
+        assert_eq!(
+            std::fs::read_to_string(&build_file).unwrap(),
+            build_file_content
+        );
+        assert!(daemon.state.batching_buildozer.pending().is_empty());
+
+        // Without the dry run the same command gets its dep added
+        client
+            .run(command, &workspace_root, false, UnusedDepsMode::Ignore)
+            .await
+            .unwrap();
+        assert!(std::fs::read_to_string(&build_file)
+            .unwrap()
+            .contains("\"//bar:bar\""));
+    }
+}
@@ -0,0 +1,203 @@
+// A machine readable record of a session, for tools like IDE plugins to follow rather than
+// scraping our logs. Each line is a JSON object, tagged with the session and attempt it
+// happened in:
+// {"session_id":1605000000,"attempt":1,"timestamp_ms":1605000012345,"event":"edit_applied",...}
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use super::action_event_stream::AppliedCorrection;
+use crate::buildozer_driver::edit_plan::BuildozerEdit;
+use crate::error_extraction::{ClassImportRequest, ClassSuffixMatch};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    SessionStarted {
+        command: Vec<String>,
+    },
+    AttemptStarted,
+    ActionFailed {
+        label: String,
+        target_kind: Option<String>,
+        class_import_requests: Vec<ClassImportRequest>,
+        suffix_requests: Vec<ClassSuffixMatch>,
+    },
+    BazelAborted {
+        label: Option<String>,
+        description: String,
+    },
+    TargetComplete {
+        label: String,
+        target_kind: Option<String>,
+        success: bool,
+    },
+    EditApplied {
+        action: &'static str,
+        target: String,
+        label: String,
+        src_fn: String,
+    },
+    AttemptFinished {
+        exit_code: i32,
+    },
+    SessionFinished {
+        exit_code: i32,
+        attempts: u16,
+    },
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    session_id: u64,
+    attempt: u16,
+    timestamp_ms: u64,
+    #[serde(flatten)]
+    event: &'a SessionEvent,
+}
+
+#[derive(Clone)]
+pub struct EventLog {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    // The session and attempt events are currently logged against
+    position: Arc<Mutex<(u64, u16)>>,
+}
+
+impl fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EventLog")
+    }
+}
+
+impl EventLog {
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> EventLog {
+        EventLog {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            position: Arc::new(Mutex::new((0, 0))),
+        }
+    }
+
+    // Opens a file to log to, or an inherited file descriptor given as fd:N
+    pub fn open(destination: &str) -> io::Result<EventLog> {
+        let file = match destination.strip_prefix("fd:") {
+            Some(fd) => OpenOptions::new()
+                .write(true)
+                .open(format!("/dev/fd/{}", fd))?,
+            None => File::create(destination)?,
+        };
+        Ok(EventLog::from_writer(file))
+    }
+
+    pub fn start_session(&self, session_id: u64, command: &[String]) {
+        *self.position.lock().unwrap() = (session_id, 0);
+        self.log(SessionEvent::SessionStarted {
+            command: command.to_vec(),
+        });
+    }
+
+    pub fn start_attempt(&self, attempt: u16) {
+        self.position.lock().unwrap().1 = attempt;
+        self.log(SessionEvent::AttemptStarted);
+    }
+
+    pub fn log_edits(&self, corrections: &[AppliedCorrection]) {
+        for correction in corrections.iter() {
+            let (action, target, label) = match &correction.edit {
+                BuildozerEdit::AddDependency { target, label } => ("add", target, label),
+                BuildozerEdit::RemoveDependency { target, label } => ("remove", target, label),
+            };
+            self.log(SessionEvent::EditApplied {
+                action,
+                target: target.clone(),
+                label: label.clone(),
+                src_fn: correction.src_fn.clone(),
+            });
+        }
+    }
+
+    pub fn log(&self, event: SessionEvent) {
+        let (session_id, attempt) = *self.position.lock().unwrap();
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let line = LogLine {
+            session_id,
+            attempt,
+            timestamp_ms,
+            event: &event,
+        };
+        let result = serde_json::to_string(&line)
+            .map_err(io::Error::from)
+            .and_then(|json| {
+                let mut writer = self.writer.lock().unwrap();
+                writeln!(writer, "{}", json)?;
+                writer.flush()
+            });
+        if let Err(e) = result {
+            warn!("Failed to write to the event log: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_log_session() {
+        let buffer = SharedBuffer::default();
+        let event_log = EventLog::from_writer(buffer.clone());
+
+        event_log.start_session(1605000000, &[String::from("build"), String::from("//...")]);
+        event_log.start_attempt(1);
+        event_log.log_edits(&[AppliedCorrection {
+            edit: BuildozerEdit::AddDependency {
+                target: String::from("//src/main/java/com/example:example"),
+                label: String::from("//src/main/java/com/example/foo:foo"),
+            },
+            src_fn: String::from("java::cannot_find_symbol"),
+        }]);
+        event_log.log(SessionEvent::AttemptFinished { exit_code: 1 });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|ln| serde_json::from_str(ln).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+
+        assert_eq!(lines[0]["event"], "session_started");
+        assert_eq!(lines[0]["session_id"], 1605000000);
+        assert_eq!(lines[0]["attempt"], 0);
+        assert_eq!(lines[0]["command"], serde_json::json!(["build", "//..."]));
+
+        assert_eq!(lines[1]["event"], "attempt_started");
+        assert_eq!(lines[1]["attempt"], 1);
+
+        assert_eq!(lines[2]["event"], "edit_applied");
+        assert_eq!(lines[2]["action"], "add");
+        assert_eq!(lines[2]["label"], "//src/main/java/com/example/foo:foo");
+        assert_eq!(lines[2]["src_fn"], "java::cannot_find_symbol");
+
+        assert_eq!(lines[3]["event"], "attempt_finished");
+        assert_eq!(lines[3]["exit_code"], 1);
+        assert_eq!(lines[3]["session_id"], 1605000000);
+    }
+}
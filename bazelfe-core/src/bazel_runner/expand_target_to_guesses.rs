@@ -1,4 +1,8 @@
-pub(crate) fn get_guesses_for_class_name(class_name: &str) -> Vec<(u16, String)> {
+// Fills in each template's {package_path} and {last_segment} from the class's package
+pub(crate) fn get_guesses_for_class_name<S: AsRef<str>>(
+    class_name: &str,
+    templates: &[S],
+) -> Vec<(u16, String)> {
     let mut sections: Vec<&str> = class_name.split(".").collect();
 
     // heuristic looking for a class name, to ignore separate from the package...
@@ -22,22 +26,32 @@ pub(crate) fn get_guesses_for_class_name(class_name: &str) -> Vec<(u16, String)>
         return vec![];
     }
 
-    let suffix = format!("{}:{}", sections.join("/"), sections.last().unwrap());
+    let package_path = sections.join("/");
+    let last_segment = sections.last().unwrap();
 
-    vec![
-        (0, format!("//src/main/scala/{}", suffix).to_string()),
-        (0, format!("//src/main/java/{}", suffix).to_string()),
-    ]
+    templates
+        .iter()
+        .map(|template| {
+            (
+                0,
+                template
+                    .as_ref()
+                    .replace("{package_path}", &package_path)
+                    .replace("{last_segment}", last_segment),
+            )
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bazel_runner::config::DEFAULT_GUESS_TEMPLATES;
 
     #[test]
     fn test_guess_for_class_name() {
         assert_eq!(
-            get_guesses_for_class_name("com.example.foo.bar.baz"),
+            get_guesses_for_class_name("com.example.foo.bar.baz", DEFAULT_GUESS_TEMPLATES),
             vec![
                 (
                     0,
@@ -51,10 +65,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_guess_for_class_name_from_templates() {
+        assert_eq!(
+            get_guesses_for_class_name(
+                "com.example.foo.Bar",
+                &[
+                    "//java/{package_path}:{last_segment}",
+                    "//src/test/scala/{package_path}",
+                ]
+            ),
+            vec![
+                (0, String::from("//java/com/example/foo:foo")),
+                (0, String::from("//src/test/scala/com/example/foo"))
+            ]
+        );
+    }
+
     #[test]
     fn test_guess_for_class_name_too_short() {
         assert_eq!(
-            get_guesses_for_class_name("com.example"),
+            get_guesses_for_class_name("com.example", DEFAULT_GUESS_TEMPLATES),
             Vec::<(u16, String)>::new()
         );
     }
@@ -62,7 +93,10 @@ mod tests {
     #[test]
     fn test_guess_for_class_name_strip_class_name() {
         assert_eq!(
-            get_guesses_for_class_name("com.example.foo.bar.baz.MyObject.InnerObject"),
+            get_guesses_for_class_name(
+                "com.example.foo.bar.baz.MyObject.InnerObject",
+                DEFAULT_GUESS_TEMPLATES
+            ),
             vec![
                 (
                     0,
@@ -79,7 +113,10 @@ mod tests {
     #[test]
     fn test_guess_for_class_name_too_short_post_strip() {
         assert_eq!(
-            get_guesses_for_class_name("com.example.MyObject.MyObject.InnerObject"),
+            get_guesses_for_class_name(
+                "com.example.MyObject.MyObject.InnerObject",
+                DEFAULT_GUESS_TEMPLATES
+            ),
             Vec::<(u16, String)>::new()
         );
     }
@@ -87,7 +124,7 @@ mod tests {
     #[test]
     fn test_guess_for_class_start_with_class_name() {
         assert_eq!(
-            get_guesses_for_class_name("MyObject.MyObject.InnerObject"),
+            get_guesses_for_class_name("MyObject.MyObject.InnerObject", DEFAULT_GUESS_TEMPLATES),
             Vec::<(u16, String)>::new()
         );
     }
@@ -0,0 +1,191 @@
+// What happened while looking for the deps missing from a failed action, for --explain. It
+// follows process_missing_dependency_errors: the requests the extractors made, the class
+// names each was expanded to, and the candidates found for them.
+
+use std::fmt;
+
+use crate::buildozer_driver::edit_plan::BuildozerEdit;
+use crate::error_extraction::{ClassImportRequest, ClassSuffixMatch};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CandidateSource {
+    Index,
+    Guess,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SkipReason {
+    AlreadyInDeps,
+    // Added in an earlier attempt, or the target itself
+    PreviouslySeen,
+    // There's no BUILD file where the label points
+    InvalidTarget,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CandidateOutcome {
+    Added,
+    // Another class in the same request was already satisfied by this candidate
+    AlreadyAdded,
+    Skipped(SkipReason),
+    // An earlier candidate was used
+    NotTried,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CandidateExplanation {
+    pub label: String,
+    pub score: u16,
+    pub source: CandidateSource,
+    pub outcome: CandidateOutcome,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookupExplanation {
+    // The class name or suffix looked up
+    pub lookup: String,
+    pub src_fn: String,
+    pub candidates: Vec<CandidateExplanation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ActionExplanation {
+    pub label: String,
+    pub target_kind: Option<String>,
+    pub buildozer_hints: Vec<BuildozerEdit>,
+    pub class_import_requests: Vec<ClassImportRequest>,
+    pub suffix_requests: Vec<ClassSuffixMatch>,
+    pub expanded_class_names: Vec<(String, Vec<String>)>,
+    pub lookups: Vec<LookupExplanation>,
+}
+
+impl fmt::Display for CandidateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CandidateSource::Index => write!(f, "index"),
+            CandidateSource::Guess => write!(f, "guess"),
+        }
+    }
+}
+
+impl fmt::Display for CandidateOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CandidateOutcome::Added => write!(f, "added"),
+            CandidateOutcome::AlreadyAdded => write!(f, "already added this attempt"),
+            CandidateOutcome::Skipped(SkipReason::AlreadyInDeps) => {
+                write!(f, "skipped, already in deps")
+            }
+            CandidateOutcome::Skipped(SkipReason::PreviouslySeen) => {
+                write!(f, "skipped, previously seen")
+            }
+            CandidateOutcome::Skipped(SkipReason::InvalidTarget) => {
+                write!(f, "skipped, no BUILD file for the target")
+            }
+            CandidateOutcome::NotTried => write!(f, "not tried"),
+        }
+    }
+}
+
+impl fmt::Display for ActionExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target_kind {
+            Some(kind) => writeln!(f, "Explaining {} ({}):", self.label, kind)?,
+            None => writeln!(f, "Explaining {}:", self.label)?,
+        }
+        for hint in self.buildozer_hints.iter() {
+            writeln!(f, "  Compiler suggested: {}", hint.to_buildozer_command())?;
+        }
+        if self.class_import_requests.is_empty() && self.suffix_requests.is_empty() {
+            writeln!(f, "  No errors were recognized")?;
+        }
+        for req in self.class_import_requests.iter() {
+            writeln!(
+                f,
+                "  Class import request: {} (src_fn: {}, priority: {}, exact_only: {})",
+                req.class_name, req.src_fn, req.priority, req.exact_only
+            )?;
+        }
+        for req in self.suffix_requests.iter() {
+            writeln!(
+                f,
+                "  Suffix request: {} (src_fn: {})",
+                req.suffix, req.src_fn
+            )?;
+        }
+        for (class_name, expanded) in self.expanded_class_names.iter() {
+            writeln!(f, "  Expanded {} to {}", class_name, expanded.join(", "))?;
+        }
+        for lookup in self.lookups.iter() {
+            writeln!(f, "  Candidates for {} ({}):", lookup.lookup, lookup.src_fn)?;
+            if lookup.candidates.is_empty() {
+                writeln!(f, "    none")?;
+            }
+            for candidate in lookup.candidates.iter() {
+                writeln!(
+                    f,
+                    "    {} score {} from {}: {}",
+                    candidate.label, candidate.score, candidate.source, candidate.outcome
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_explanation() {
+        let explanation = ActionExplanation {
+            label: String::from("//src/main/scala/com/example:example"),
+            target_kind: Some(String::from("scala_library")),
+            buildozer_hints: vec![],
+            class_import_requests: vec![ClassImportRequest {
+                class_name: String::from("com.example.foo.Foo"),
+                exact_only: false,
+                src_fn: String::from("scala::type_not_found"),
+                priority: 1,
+            }],
+            suffix_requests: vec![],
+            expanded_class_names: vec![(
+                String::from("com.example.foo.Foo"),
+                vec![
+                    String::from("com.example.foo.Foo"),
+                    String::from("com.example.foo"),
+                ],
+            )],
+            lookups: vec![LookupExplanation {
+                lookup: String::from("com.example.foo.Foo"),
+                src_fn: String::from("scala::type_not_found"),
+                candidates: vec![
+                    CandidateExplanation {
+                        label: String::from("//src/main/scala/com/example/bar:bar"),
+                        score: 12,
+                        source: CandidateSource::Index,
+                        outcome: CandidateOutcome::Skipped(SkipReason::AlreadyInDeps),
+                    },
+                    CandidateExplanation {
+                        label: String::from("//src/main/scala/com/example/foo:foo"),
+                        score: 0,
+                        source: CandidateSource::Guess,
+                        outcome: CandidateOutcome::Added,
+                    },
+                ],
+            }],
+        };
+
+        assert_eq!(
+            explanation.to_string(),
+            "Explaining //src/main/scala/com/example:example (scala_library):
+  Class import request: com.example.foo.Foo (src_fn: scala::type_not_found, priority: 1, exact_only: false)
+  Expanded com.example.foo.Foo to com.example.foo.Foo, com.example.foo
+  Candidates for com.example.foo.Foo (scala::type_not_found):
+    //src/main/scala/com/example/bar:bar score 12 from index: skipped, already in deps
+    //src/main/scala/com/example/foo:foo score 0 from guess: added
+"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::buildozer_driver::{edit_plan::BuildozerEdit, Buildozer};
+
+use super::action_event_stream::AppliedCorrection;
+
+// One BUILD file edit recorded by bazel-runner.
+// Stored as a tab separated line:
+// action_id  session_id  attempt  add|remove  target  label  src_fn
+#[derive(Clone, PartialEq, Debug)]
+pub struct JournalEntry {
+    pub action_id: u64,
+    pub session_id: u64,
+    pub attempt: u16,
+    pub edit: BuildozerEdit,
+    pub src_fn: String,
+}
+
+impl JournalEntry {
+    fn to_line(&self) -> String {
+        let (action, target, label) = match &self.edit {
+            BuildozerEdit::AddDependency { target, label } => ("add", target, label),
+            BuildozerEdit::RemoveDependency { target, label } => ("remove", target, label),
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.action_id, self.session_id, self.attempt, action, target, label, self.src_fn
+        )
+    }
+
+    fn parse_line(ln: &str) -> Option<JournalEntry> {
+        let fields: Vec<&str> = ln.split('\t').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+        let target = fields[4].to_string();
+        let label = fields[5].to_string();
+        let edit = match fields[3] {
+            "add" => BuildozerEdit::AddDependency { target, label },
+            "remove" => BuildozerEdit::RemoveDependency { target, label },
+            _ => return None,
+        };
+        Some(JournalEntry {
+            action_id: fields[0].parse().ok()?,
+            session_id: fields[1].parse().ok()?,
+            attempt: fields[2].parse().ok()?,
+            edit,
+            src_fn: fields[6].to_string(),
+        })
+    }
+}
+
+// Append only log of the edits made to BUILD files, so they can be reverted later.
+#[derive(Clone, Debug)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn read_entries(&self) -> std::io::Result<Vec<JournalEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::default());
+        }
+        let content = std::fs::read_to_string(&self.path)?;
+        let mut entries = Vec::default();
+        for ln in content.lines() {
+            match JournalEntry::parse_line(ln) {
+                Some(entry) => entries.push(entry),
+                None => {
+                    if !ln.trim().is_empty() {
+                        warn!("Ignoring malformed journal line: {:?}", ln);
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn record(
+        &self,
+        session_id: u64,
+        attempt: u16,
+        corrections: &[AppliedCorrection],
+    ) -> std::io::Result<()> {
+        if corrections.is_empty() {
+            return Ok(());
+        }
+        let next_action_id = self
+            .read_entries()?
+            .iter()
+            .map(|e| e.action_id + 1)
+            .max()
+            .unwrap_or(0);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for (idx, correction) in corrections.iter().enumerate() {
+            let entry = JournalEntry {
+                action_id: next_action_id + idx as u64,
+                session_id,
+                attempt,
+                edit: correction.edit.clone(),
+                src_fn: correction.src_fn.clone(),
+            };
+            writeln!(file, "{}", entry.to_line())?;
+        }
+        Ok(())
+    }
+
+    // The entries to revert, newest first. Either the single chosen action,
+    // or every action from the most recent session.
+    pub fn entries_to_undo(&self, action_id: Option<u64>) -> std::io::Result<Vec<JournalEntry>> {
+        let entries = self.read_entries()?;
+        let mut selected: Vec<JournalEntry> = match action_id {
+            Some(action_id) => entries
+                .into_iter()
+                .filter(|e| e.action_id == action_id)
+                .collect(),
+            None => match entries.last().map(|e| e.session_id) {
+                Some(session_id) => entries
+                    .into_iter()
+                    .filter(|e| e.session_id == session_id)
+                    .collect(),
+                None => Vec::default(),
+            },
+        };
+        selected.reverse();
+        Ok(selected)
+    }
+
+    // Drops a session's entries for edits which have since been reverted
+    pub fn remove_session_edits(
+        &self,
+        session_id: u64,
+        edits: &[BuildozerEdit],
+    ) -> std::io::Result<()> {
+        let action_ids: HashSet<u64> = self
+            .read_entries()?
+            .into_iter()
+            .filter(|e| e.session_id == session_id && edits.contains(&e.edit))
+            .map(|e| e.action_id)
+            .collect();
+        self.remove_entries(&action_ids)
+    }
+
+    pub fn remove_entries(&self, action_ids: &HashSet<u64>) -> std::io::Result<()> {
+        let mut content = String::new();
+        for entry in self.read_entries()?.into_iter() {
+            if !action_ids.contains(&entry.action_id) {
+                content.push_str(&entry.to_line());
+                content.push('\n');
+            }
+        }
+        std::fs::write(&self.path, content)
+    }
+}
+
+// Reverts the selected journal entries through the supplied buildozer, dropping
+// the ones that were successfully reverted from the journal.
+// Returns the number of edits reverted.
+pub async fn undo<T: Buildozer + Send + Sync>(
+    buildozer: &T,
+    journal: &Journal,
+    action_id: Option<u64>,
+) -> std::io::Result<u32> {
+    let mut reverted: HashSet<u64> = HashSet::new();
+    for entry in journal.entries_to_undo(action_id)?.into_iter() {
+        let reversed = entry.edit.reversed();
+        info!(
+            "Undoing action {} ({}): {}",
+            entry.action_id,
+            entry.src_fn,
+            reversed.to_buildozer_command()
+        );
+        match reversed.apply(buildozer).await {
+            Ok(_) => {
+                reverted.insert(entry.action_id);
+            }
+            Err(e) => warn!("Failed to undo action {}: {:?}", entry.action_id, e),
+        }
+    }
+    journal.remove_entries(&reverted)?;
+    Ok(reverted.len() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buildozer_driver::edit_plan::{EditPlan, PlanningBuildozer};
+    use crate::buildozer_driver::Result;
+    use async_trait::async_trait;
+
+    #[derive(Clone, Debug)]
+    struct NoDepsBuildozer;
+
+    #[async_trait]
+    impl Buildozer for NoDepsBuildozer {
+        async fn print_deps(&self, _label: &String) -> Result<Vec<String>> {
+            Ok(Vec::default())
+        }
+
+        async fn add_dependency(&self, _target: &String, _label: &String) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_dependency(&self, _target: &String, _label: &String) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn add_correction(label: &str, src_fn: &str) -> AppliedCorrection {
+        AppliedCorrection {
+            edit: BuildozerEdit::AddDependency {
+                target: String::from("//src/main/java/com/example:example"),
+                label: String::from(label),
+            },
+            src_fn: String::from(src_fn),
+        }
+    }
+
+    #[test]
+    fn test_journal_entry_round_trip() {
+        let entry = JournalEntry {
+            action_id: 3,
+            session_id: 1600000000,
+            attempt: 2,
+            edit: BuildozerEdit::RemoveDependency {
+                target: String::from("//src/main/java/com/example:example"),
+                label: String::from("//src/main/java/com/example/foo:foo"),
+            },
+            src_fn: String::from("bazel::target_not_visible"),
+        };
+        assert_eq!(
+            JournalEntry::parse_line(&entry.to_line()),
+            Some(entry.clone())
+        );
+        assert_eq!(JournalEntry::parse_line("not a journal line"), None);
+    }
+
+    #[tokio::test]
+    async fn test_undo_last_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path().join("journal"));
+
+        journal
+            .record(1, 0, &[add_correction("//a:a", "java::cannot_find_symbol")])
+            .unwrap();
+        journal
+            .record(
+                2,
+                0,
+                &[
+                    add_correction("//b:b", "java::cannot_find_symbol"),
+                    add_correction("//c:c", "scala::object_not_in_package"),
+                ],
+            )
+            .unwrap();
+
+        let to_undo = journal.entries_to_undo(None).unwrap();
+        assert_eq!(
+            to_undo.iter().map(|e| e.action_id).collect::<Vec<u64>>(),
+            vec![2, 1]
+        );
+
+        let plan = EditPlan::new();
+        let buildozer = PlanningBuildozer::new(NoDepsBuildozer, plan.clone());
+        assert_eq!(undo(&buildozer, &journal, None).await.unwrap(), 2);
+        assert_eq!(
+            plan.edits(),
+            vec![
+                BuildozerEdit::RemoveDependency {
+                    target: String::from("//src/main/java/com/example:example"),
+                    label: String::from("//c:c"),
+                },
+                BuildozerEdit::RemoveDependency {
+                    target: String::from("//src/main/java/com/example:example"),
+                    label: String::from("//b:b"),
+                },
+            ]
+        );
+
+        // Only the first session remains, and can be undone by action id
+        let remaining = journal.read_entries().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].action_id, 0);
+        assert_eq!(undo(&buildozer, &journal, Some(0)).await.unwrap(), 1);
+        assert!(journal.read_entries().unwrap().is_empty());
+    }
+}
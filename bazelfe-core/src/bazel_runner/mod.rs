@@ -1,12 +1,36 @@
 use std::ffi::OsString;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::atomic::Ordering;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
 
 use tokio::process::Command;
 
+use process_unused_dependencies::UnusedDepsMode;
+
 static SUB_PROCESS_PID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+static STOP_REQUESTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// Asks the running session to stop once its current attempt is done, interrupting bazel so
+// that comes soon. Edits from the attempt are still flushed before the session returns.
+pub fn request_stop() {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+    let current_sub_process_pid: u32 = SUB_PROCESS_PID.load(Ordering::SeqCst);
+    if current_sub_process_pid != 0 {
+        info!("Interrupting bazel, pid: {:?}", current_sub_process_pid);
+        let _ = std::process::Command::new("kill")
+            .arg("-INT")
+            .arg(current_sub_process_pid.to_string())
+            .status();
+    }
+}
+
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
 
 pub fn register_ctrlc_handler() {
     ctrlc::set_handler(move || {
@@ -25,9 +49,48 @@ pub fn register_ctrlc_handler() {
     .expect("Error setting Ctrl-C handler");
 }
 
+fn is_flag(arg: &str, flag: &str) -> bool {
+    arg == flag || arg.starts_with(&format!("{}=", flag))
+}
+
+// The command without the given flag, passed either as --flag=value or --flag value
+pub fn without_flag(command: &[String], flag: &str) -> Vec<String> {
+    let mut result = Vec::default();
+    let mut args = command.iter();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            args.next();
+        } else if !is_flag(arg, flag) {
+            result.push(arg.clone());
+        }
+    }
+    result
+}
+
+// When the command already publishes its build events to a bes backend, bazel can't publish
+// them to us as well. Instead we have it write them to a file we follow, using the one the
+// user asked for if any.
+pub fn build_event_file_for<S: AsRef<str>>(command: &[S]) -> Option<PathBuf> {
+    if !command.iter().any(|e| is_flag(e.as_ref(), "--bes_backend")) {
+        return None;
+    }
+    let mut args = command.iter().map(|e| e.as_ref());
+    while let Some(arg) = args.next() {
+        if arg == "--build_event_binary_file" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--build_event_binary_file=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    Some(std::env::temp_dir().join(format!("bazel-fe-build-events-{}.bin", std::process::id())))
+}
+
 fn update_command<S: Into<String> + Clone>(
     command: &Vec<S>,
     srv_port: u16,
+    build_event_file: Option<&PathBuf>,
+    unused_deps: UnusedDepsMode,
 ) -> Option<Vec<OsString>> {
     let lst_str: Vec<String> = command.iter().skip(1).map(|e| e.clone().into()).collect();
 
@@ -56,16 +119,43 @@ fn update_command<S: Into<String> + Clone>(
     let (pre_cmd, cmd_including_post) = lst_str.split_at(idx);
     let (cmd, post_command) = cmd_including_post.split_at(1);
 
-    let bes_section = vec![
+    let mut bes_section = vec![
         cmd[0].clone(),
         String::from("--build_event_publish_all_actions"),
-        String::from("--experimental_build_event_upload_strategy=local"),
         String::from("--build_event_text_file_path_conversion"),
         String::from("--color"),
         String::from("yes"),
-        String::from("--bes_backend"),
-        String::from(format!("grpc://127.0.0.1:{}", srv_port)),
     ];
+    if unused_deps != UnusedDepsMode::Ignore {
+        // Already built, this only has its outputs reported, for the .jdeps in them
+        bes_section.push(format!(
+            "--output_groups=+{}",
+            crate::build_events::hydrated_stream::HIDDEN_OUTPUT_GROUP
+        ));
+    }
+    match build_event_file {
+        Some(path) => {
+            // Leave how events are uploaded to the user's backend alone, but keep the local
+            // paths to action outputs in our file so we can read them
+            bes_section.push(String::from("--nobuild_event_binary_file_path_conversion"));
+            if !lst_str
+                .iter()
+                .any(|e| is_flag(e, "--build_event_binary_file"))
+            {
+                bes_section.push(format!(
+                    "--build_event_binary_file={}",
+                    path.to_string_lossy()
+                ));
+            }
+        }
+        None => {
+            bes_section.push(String::from(
+                "--experimental_build_event_upload_strategy=local",
+            ));
+            bes_section.push(String::from("--bes_backend"));
+            bes_section.push(format!("grpc://127.0.0.1:{}", srv_port));
+        }
+    }
 
     Some(
         vec![pre_cmd.iter(), bes_section.iter(), post_command.iter()]
@@ -81,6 +171,43 @@ pub struct ExecuteResult {
     pub exit_code: i32,
     pub errors_corrected: u32,
 }
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ProcessOutput {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+// Where the output of the bazel process goes
+#[derive(Clone, Debug)]
+pub enum OutputSink {
+    Terminal,
+    Discard,
+    Channel(mpsc::Sender<ProcessOutput>),
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecuteOptions {
+    // Run bazel from here rather than our own working directory
+    pub working_directory: Option<PathBuf>,
+    pub output: OutputSink,
+    // Have bazel write its build events here, rather than publish them to our service
+    pub build_event_file: Option<PathBuf>,
+    // Whether the session looks for unused deps, which needs bazel to report their .jdeps
+    pub unused_deps: UnusedDepsMode,
+}
+
+impl Default for ExecuteOptions {
+    fn default() -> Self {
+        Self {
+            working_directory: None,
+            output: OutputSink::Terminal,
+            build_event_file: None,
+            unused_deps: UnusedDepsMode::Ignore,
+        }
+    }
+}
+
 pub async fn execute_bazel<S: Into<String> + Clone>(
     command: Vec<S>,
     bes_port: u16,
@@ -92,6 +219,50 @@ pub async fn execute_bazel_output_control<S: Into<String> + Clone>(
     command: Vec<S>,
     bes_port: u16,
     show_output: bool,
+) -> ExecuteResult {
+    let options = ExecuteOptions {
+        working_directory: None,
+        output: if show_output {
+            OutputSink::Terminal
+        } else {
+            OutputSink::Discard
+        },
+        build_event_file: None,
+        unused_deps: UnusedDepsMode::Ignore,
+    };
+    execute_bazel_with_options(command, bes_port, &options).await
+}
+
+async fn forward_output<R, W>(
+    mut reader: R,
+    mut terminal: W,
+    mut output: OutputSink,
+    to_chunk: fn(Vec<u8>) -> ProcessOutput,
+) where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = [0; 1024];
+    loop {
+        let bytes_read = reader.read(&mut buffer[..]).await.unwrap();
+        if bytes_read == 0 {
+            break;
+        }
+        match output {
+            OutputSink::Terminal => terminal.write_all(&buffer[0..bytes_read]).await.unwrap(),
+            OutputSink::Discard => (),
+            OutputSink::Channel(ref mut tx) => {
+                // The receiver going away shouldn't stop bazel, so just drop the output
+                let _ = tx.send(to_chunk(buffer[0..bytes_read].to_vec())).await;
+            }
+        }
+    }
+}
+
+pub async fn execute_bazel_with_options<S: Into<String> + Clone>(
+    command: Vec<S>,
+    bes_port: u16,
+    options: &ExecuteOptions,
 ) -> ExecuteResult {
     let application: OsString = command
         .first()
@@ -102,7 +273,12 @@ pub async fn execute_bazel_output_control<S: Into<String> + Clone>(
         .expect("Should have had at least one arg the bazel process itself.")
         .into();
 
-    let updated_command = match update_command(&command, bes_port) {
+    let updated_command = match update_command(
+        &command,
+        bes_port,
+        options.build_event_file.as_ref(),
+        options.unused_deps,
+    ) {
         Some(e) => e,
         None => command
             .iter()
@@ -121,39 +297,31 @@ pub async fn execute_bazel_output_control<S: Into<String> + Clone>(
     cmd.args(&updated_command)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
+    if let Some(working_directory) = &options.working_directory {
+        cmd.current_dir(working_directory);
+    }
 
     let mut child = cmd.spawn().expect("failed to start bazel process");
     SUB_PROCESS_PID.store(child.id(), Ordering::SeqCst);
 
-    let mut child_stdout = child.stdout.take().expect("Child didn't have a stdout");
-
-    tokio::spawn(async move {
-        let mut bytes_read = 1;
-        let mut buffer = [0; 1024];
-        let mut stdout = tokio::io::stdout();
+    let child_stdout = child.stdout.take().expect("Child didn't have a stdout");
+    let stdout_task = tokio::spawn(forward_output(
+        child_stdout,
+        tokio::io::stdout(),
+        options.output.clone(),
+        ProcessOutput::Stdout,
+    ));
 
-        while bytes_read > 0 {
-            bytes_read = child_stdout.read(&mut buffer[..]).await.unwrap();
-            if show_output {
-                stdout.write_all(&buffer[0..bytes_read]).await.unwrap();
-            }
-        }
-    });
-
-    let mut child_stderr = child.stderr.take().expect("Child didn't have a stderr");
-
-    tokio::spawn(async move {
-        let mut bytes_read = 1;
-        let mut buffer = [0; 1024];
-        let mut stderr = tokio::io::stderr();
-        while bytes_read > 0 {
-            bytes_read = child_stderr.read(&mut buffer[..]).await.unwrap();
-            if show_output {
-                stderr.write_all(&buffer[0..bytes_read]).await.unwrap();
-            }
-        }
-    });
+    let child_stderr = child.stderr.take().expect("Child didn't have a stderr");
+    let stderr_task = tokio::spawn(forward_output(
+        child_stderr,
+        tokio::io::stderr(),
+        options.output.clone(),
+        ProcessOutput::Stderr,
+    ));
     let result = child.await.expect("The command wasn't running");
+    let _ = stdout_task.await;
+    let _ = stderr_task.await;
 
     SUB_PROCESS_PID.store(0, Ordering::SeqCst);
 
@@ -163,7 +331,34 @@ pub async fn execute_bazel_output_control<S: Into<String> + Clone>(
     }
 }
 pub mod action_event_stream;
+pub mod config;
+pub mod daemon;
+pub mod event_log;
 pub mod expand_target_to_guesses;
+pub mod explain;
+pub mod journal;
 pub mod process_build_abort_errors;
 pub mod process_missing_dependency_errors;
+pub mod process_unused_dependencies;
+pub mod replay;
 mod sanitization_tools;
+pub mod session;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_command_output_groups() {
+        let command = vec!["bazel", "build", "//..."];
+        let has_output_group = |unused_deps| {
+            update_command(&command, 5000, None, unused_deps)
+                .unwrap()
+                .iter()
+                .any(|e| e.to_string_lossy().starts_with("--output_groups="))
+        };
+        assert!(!has_output_group(UnusedDepsMode::Ignore));
+        assert!(has_output_group(UnusedDepsMode::Propose));
+        assert!(has_output_group(UnusedDepsMode::Remove));
+    }
+}
@@ -2,10 +2,15 @@ use crate::{build_events::build_event_server::bazel_event::ProgressEvt};
 use bazelfe_protos::*;
 use lazy_static::lazy_static;
 
-use crate::{build_events::hydrated_stream, buildozer_driver::Buildozer};
+use crate::{
+    build_events::hydrated_stream,
+    buildozer_driver::{edit_plan::BuildozerEdit, Buildozer},
+};
 use dashmap::{DashMap, DashSet};
 use regex::Regex;
 use std::sync::Arc;
+
+use super::action_event_stream::AppliedCorrection;
 #[derive(Clone, PartialEq, Debug)]
 
 enum BazelCorrectionCommand {
@@ -15,6 +20,7 @@ enum BazelCorrectionCommand {
 struct BuildozerRemoveDepCmd {
     pub target_to_operate_on: String,
     pub dependency_to_remove: String,
+    pub src_fn: &'static str,
 }
 
 fn extract_target_does_not_exist(
@@ -44,6 +50,7 @@ fn extract_target_does_not_exist(
                         BazelCorrectionCommand::BuildozerRemoveDep(BuildozerRemoveDepCmd {
                             target_to_operate_on: src_target.to_string(),
                             dependency_to_remove: offending_dependency.to_string(),
+                            src_fn: "bazel::target_does_not_exist",
                         });
                     command_stream.push(correction);
                 }
@@ -76,6 +83,7 @@ fn extract_target_not_declared_in_package(
                     BazelCorrectionCommand::BuildozerRemoveDep(BuildozerRemoveDepCmd {
                         target_to_operate_on: src_target.to_string(),
                         dependency_to_remove: offending_dependency.to_string(),
+                        src_fn: "bazel::target_not_declared_in_package",
                     });
                 command_stream.push(correction);
             }
@@ -110,6 +118,7 @@ fn extract_target_not_visible(
                         BazelCorrectionCommand::BuildozerRemoveDep(BuildozerRemoveDepCmd {
                             target_to_operate_on: src_target.to_string(),
                             dependency_to_remove: offending_dependency.to_string(),
+                            src_fn: "bazel::target_not_visible",
                         });
                     command_stream.push(correction);
                 }
@@ -169,6 +178,7 @@ fn extract_added_cycle_in_dependency_graph(
                                 BazelCorrectionCommand::BuildozerRemoveDep(BuildozerRemoveDepCmd {
                                     target_to_operate_on: target_to_operate_on,
                                     dependency_to_remove: dependency_to_remove,
+                                    src_fn: "bazel::added_cycle_in_dependency_graph",
                                 });
                             command_stream.push(correction);
                         }
@@ -195,11 +205,8 @@ fn extract_added_cycle_in_dependency_graph(
 async fn apply_candidates<T: Buildozer + Clone + Send + Sync + 'static>(
     candidate_correction_commands: Vec<BazelCorrectionCommand>,
     buildozer: T,
-) -> u32 {
-    if candidate_correction_commands.len() == 0 {
-        return 0;
-    }
-    let mut actions_completed: u32 = 0;
+) -> Vec<AppliedCorrection> {
+    let mut actions_completed: Vec<AppliedCorrection> = Vec::default();
     for correction_command in candidate_correction_commands.into_iter() {
         match correction_command {
             BazelCorrectionCommand::BuildozerRemoveDep(buildozer_remove_dep) => {
@@ -217,7 +224,13 @@ async fn apply_candidates<T: Buildozer + Clone + Send + Sync + 'static>(
                     .await;
                 match buildozer_res {
                     Ok(_) => {
-                        actions_completed += 1;
+                        actions_completed.push(AppliedCorrection {
+                            edit: BuildozerEdit::RemoveDependency {
+                                target: target_to_operate_on,
+                                label: dependency_to_remove,
+                            },
+                            src_fn: buildozer_remove_dep.src_fn.to_string(),
+                        });
                     }
                     Err(_) => info!("Buildozer command failed"),
                 }
@@ -231,7 +244,7 @@ pub async fn process_progress<T: Buildozer + Clone + Send + Sync + 'static>(
     buildozer: T,
     bazel_progress_error_info: &ProgressEvt,
     previous_global_seen: Arc<DashMap<String, DashSet<String>>>,
-) -> u32 {
+) -> Vec<AppliedCorrection> {
     let mut candidate_correction_commands: Vec<BazelCorrectionCommand> = vec![];
 
     extract_added_cycle_in_dependency_graph(
@@ -251,7 +264,7 @@ pub async fn process_progress<T: Buildozer + Clone + Send + Sync + 'static>(
 pub async fn process_build_abort_errors<T: Buildozer + Clone + Send + Sync + 'static>(
     buildozer: T,
     bazel_abort_error_info: &hydrated_stream::BazelAbortErrorInfo,
-) -> u32 {
+) -> Vec<AppliedCorrection> {
     let mut candidate_correction_commands: Vec<BazelCorrectionCommand> = vec![];
 
     extract_target_does_not_exist(&bazel_abort_error_info, &mut candidate_correction_commands);
@@ -280,6 +293,7 @@ mod tests {
                 BuildozerRemoveDepCmd {
                     target_to_operate_on: String::from("//src/main/java/com/example:Example"),
                     dependency_to_remove: String::from("//src/main/java/com/example:asdfasdf"),
+                    src_fn: "bazel::target_does_not_exist",
                 }
             )]
         );
@@ -301,6 +315,7 @@ mod tests {
                 BuildozerRemoveDepCmd {
                     target_to_operate_on: String::from("//src/main/java/com/example/c:c"),
                     dependency_to_remove: String::from("//src/main/java/com/example/foo:foo"),
+                    src_fn: "bazel::target_not_declared_in_package",
                 }
             )]
         );
@@ -323,6 +338,7 @@ mod tests {
                 BuildozerRemoveDepCmd {
                     target_to_operate_on: String::from("//src/main/java/com/com/example:Example"),
                     dependency_to_remove: String::from("@third_party_jvm//3rdparty/jvm/com/google/api/grpc:proto_google_common_protos"),
+                    src_fn: "bazel::target_not_visible",
                 }
             )]
         );
@@ -381,6 +397,7 @@ mod tests {
                     dependency_to_remove: String::from(
                         "//src/main/java/com/example/foo/actions:actions"
                     ),
+                    src_fn: "bazel::added_cycle_in_dependency_graph",
                 }
             ),]
         );
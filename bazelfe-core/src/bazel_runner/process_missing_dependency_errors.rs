@@ -1,17 +1,19 @@
 use bazelfe_protos::*;
-use std::{
-    collections::{HashMap, HashSet},
-    path::Path,
-    path::PathBuf,
-};
-
-use lazy_static::lazy_static;
+use std::{collections::HashSet, path::Path, path::PathBuf};
 
 use crate::{
-    build_events::hydrated_stream::ActionFailedErrorInfo, buildozer_driver::Buildozer,
+    build_events::hydrated_stream::ActionFailedErrorInfo,
+    buildozer_driver::{edit_plan::BuildozerEdit, Buildozer},
     error_extraction, index_table,
 };
 
+use super::action_event_stream::AppliedCorrection;
+use super::config::Config;
+use super::explain::{
+    ActionExplanation, CandidateExplanation, CandidateOutcome, CandidateSource, LookupExplanation,
+    SkipReason,
+};
+
 use dashmap::DashSet;
 use log;
 
@@ -19,51 +21,36 @@ fn get_candidates_for_class_name(
     error_info: &ActionFailedErrorInfo,
     class_name: &str,
     index_table: &index_table::IndexTable,
+    config: &Config,
 ) -> Vec<(u16, String)> {
-    lazy_static! {
-      // These are things that are already implicit dependencencies so we should ensure they are not included
-        static ref FORBIDDEN_TARGETS_BY_TYPE: HashMap<String, HashSet<String>> = {
-            let mut m = HashMap::new();
-            let mut cur_s = HashSet::new();
-            cur_s.insert(String::from(
-                "@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library",
-            ));
-            m.insert(String::from("scala_library"), cur_s);
-
-            let mut cur_s = HashSet::new();
-            cur_s.insert(String::from("@third_party_jvm//3rdparty/jvm/org/scalatest"));
-            cur_s.insert(String::from(
-                "@third_party_jvm//3rdparty/jvm/org/scalatest:scalatest",
-            ));
-            cur_s.insert(String::from(
-                "@third_party_jvm//3rdparty/jvm/org/scala_lang:scala_library",
-            ));
-            m.insert(String::from("scala_test"), cur_s);
-            m
-        };
+    let mut results = index_table.get(class_name).unwrap_or(vec![]);
+
+    if let Some(target_kind) = &error_info.target_kind {
+        results = results
+            .into_iter()
+            .filter(|(_, target)| !config.is_forbidden_dep(target_kind, target))
+            .collect();
     }
 
-    let mut results = index_table
-        .get(class_name)
-        .map(|e| e.clone())
-        .unwrap_or(vec![]);
-
-    match &error_info.target_kind {
-        Some(target_kind) => match FORBIDDEN_TARGETS_BY_TYPE.get(target_kind) {
-            None => (),
-            Some(forbidden_targets) => {
-                results = results
-                    .into_iter()
-                    .filter(|(_, target)| !forbidden_targets.contains(target))
-                    .collect();
-            }
-        },
-        None => (),
+    // Guessing a target from the package only makes sense for JVM class names
+    let guess_from_package = error_info
+        .target_kind
+        .as_ref()
+        .and_then(|kind| config.extractor_for(kind))
+        .map(|e| e.is_jvm())
+        .unwrap_or(true);
+    let guesses = if guess_from_package {
+        super::expand_target_to_guesses::get_guesses_for_class_name(
+            class_name,
+            &config.guess_templates(),
+        )
+    } else {
+        Vec::default()
     };
 
     results = results
         .into_iter()
-        .chain(super::expand_target_to_guesses::get_guesses_for_class_name(class_name).into_iter())
+        .chain(guesses.into_iter())
         .map(|(a, b)| (a, super::sanitization_tools::sanitize_label(b)))
         .collect();
 
@@ -71,52 +58,56 @@ fn get_candidates_for_class_name(
     results
 }
 
-pub fn is_potentially_valid_target(label: &str) -> bool {
+pub fn is_potentially_valid_target(workspace_root: &Path, label: &str) -> bool {
     let prepared_path = label.strip_prefix("//").and_then(|e| e.split(":").next());
     match prepared_path {
         Some(p) => {
-            let path = Path::new(p);
-            path.join("BUILD").exists()
+            let path = workspace_root.join(p);
+            path.join("BUILD.bazel").exists() || path.join("BUILD").exists()
         }
         None => true,
     }
 }
 
-fn output_error_paths(err_data: &ActionFailedErrorInfo) -> Vec<std::path::PathBuf> {
-    err_data
-        .output_files
-        .iter()
-        .flat_map(|e| match e {
+// The output of the failed action, read from disk unless it was inlined into the event
+async fn output_error_contents(err_data: &ActionFailedErrorInfo) -> Vec<String> {
+    let mut results = Vec::default();
+    for e in err_data.output_files.iter() {
+        match e {
             build_event_stream::file::File::Uri(e) => {
                 if e.starts_with("file://") {
                     let u: PathBuf = e.strip_prefix("file://").unwrap().into();
-                    Some(u)
+                    match tokio::fs::read_to_string(&u).await {
+                        Ok(content) => results.push(content),
+                        Err(err) => warn!("Unable to read action output {:?}: {:?}", u, err),
+                    }
                 } else {
                     warn!("Path isn't a file, so skipping...{:?}", e);
-
-                    None
                 }
             }
-            build_event_stream::file::File::Contents(_) => None,
-        })
-        .collect()
+            build_event_stream::file::File::Contents(contents) => {
+                results.push(String::from_utf8_lossy(contents).to_string());
+            }
+        }
+    }
+    results
 }
 
-async fn path_to_import_requests(
+fn output_to_import_requests(
     error_info: &ActionFailedErrorInfo,
-    path_to_use: &PathBuf,
+    loaded_output: &str,
+    config: &Config,
     candidate_import_requests: &mut Vec<error_extraction::ClassImportRequest>,
     suffix_requests: &mut Vec<error_extraction::ClassSuffixMatch>,
 ) {
-    let loaded_path = tokio::fs::read_to_string(path_to_use).await.unwrap();
-
-    candidate_import_requests.extend(error_extraction::extract_errors(
-        &error_info.target_kind,
-        &loaded_path,
-    ));
+    let extractor = error_info
+        .target_kind
+        .as_ref()
+        .and_then(|kind| config.extractor_for(kind));
+    candidate_import_requests.extend(error_extraction::extract_errors(extractor, loaded_output));
     suffix_requests.extend(error_extraction::extract_suffix_errors(
-        &error_info.target_kind,
-        &loaded_path,
+        extractor,
+        loaded_output,
     ));
 }
 
@@ -125,18 +116,28 @@ pub async fn process_missing_dependency_errors<T: Buildozer + Clone + Send + Syn
     buildozer: T,
     action_failed_error_info: &ActionFailedErrorInfo,
     index_table: &index_table::IndexTable,
-) -> u32 {
+    config: &Config,
+) -> (Vec<AppliedCorrection>, ActionExplanation) {
     let mut local_previous_seen: HashSet<String> = HashSet::new();
+    let mut explanation = ActionExplanation {
+        label: action_failed_error_info.label.clone(),
+        target_kind: action_failed_error_info.target_kind.clone(),
+        ..Default::default()
+    };
+
+    // Candidates are checked for a package relative to the workspace, wherever we're run from
+    let workspace_root = super::config::workspace_root().unwrap_or_default();
+
+    let declared_deps: HashSet<String> = buildozer
+        .print_deps(&action_failed_error_info.label)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(super::sanitization_tools::sanitize_label)
+        .collect();
 
     let ignore_dep_references: HashSet<String> = {
-        let mut to_ignore = HashSet::new();
-        let d = buildozer
-            .print_deps(&action_failed_error_info.label)
-            .await
-            .unwrap();
-        d.into_iter().for_each(|dep| {
-            to_ignore.insert(super::sanitization_tools::sanitize_label(dep));
-        });
+        let mut to_ignore = declared_deps.clone();
 
         global_previous_seen.iter().for_each(|dep| {
             to_ignore.insert(super::sanitization_tools::sanitize_label(dep.to_string()));
@@ -154,39 +155,107 @@ pub async fn process_missing_dependency_errors<T: Buildozer + Clone + Send + Syn
     };
     log::debug!("ignore_dep_references: {:?}", ignore_dep_references);
 
-    let mut actions_completed: u32 = 0;
+    let mut actions_completed: Vec<AppliedCorrection> = Vec::default();
 
     let mut prefix_candidate_import_requests: Vec<error_extraction::ClassImportRequest> = vec![];
     let mut suffix_requests: Vec<error_extraction::ClassSuffixMatch> = vec![];
-    for path in output_error_paths(&action_failed_error_info).into_iter() {
-        path_to_import_requests(
+    let mut buildozer_hints: Vec<BuildozerEdit> = vec![];
+    for loaded_output in output_error_contents(&action_failed_error_info)
+        .await
+        .into_iter()
+    {
+        buildozer_hints.extend(error_extraction::extract_buildozer_hints(&loaded_output));
+        output_to_import_requests(
             &action_failed_error_info,
-            &path.into(),
+            &loaded_output,
+            config,
             &mut prefix_candidate_import_requests,
             &mut suffix_requests,
         )
-        .await
+    }
+    explanation.buildozer_hints = buildozer_hints.clone();
+    explanation.class_import_requests = prefix_candidate_import_requests.clone();
+    explanation.suffix_requests = suffix_requests.clone();
+
+    // The compiler told us exactly what to do, so there's no need to guess from the index.
+    // Anything else wrong will show up again in the next attempt.
+    for edit in buildozer_hints.into_iter() {
+        let target = super::sanitization_tools::sanitize_label(edit.target().clone());
+        let label = super::sanitization_tools::sanitize_label(edit.label().clone());
+        let edit = match edit {
+            BuildozerEdit::AddDependency { .. } => {
+                if target == action_failed_error_info.label {
+                    if ignore_dep_references.contains(&label) {
+                        continue;
+                    }
+                    local_previous_seen.insert(label.clone());
+                } else {
+                    // Hints can be for another target, so check what that one already has
+                    let target_deps = match buildozer.print_deps(&target).await {
+                        Ok(deps) => deps,
+                        Err(e) => {
+                            warn!("Unable to read the deps of {}: {:?}", target, e);
+                            continue;
+                        }
+                    };
+                    if target_deps
+                        .into_iter()
+                        .map(super::sanitization_tools::sanitize_label)
+                        .any(|dep| dep == label)
+                    {
+                        continue;
+                    }
+                }
+                BuildozerEdit::AddDependency { target, label }
+            }
+            BuildozerEdit::RemoveDependency { .. } => {
+                BuildozerEdit::RemoveDependency { target, label }
+            }
+        };
+        info!("Buildozer action: {}", edit.to_buildozer_command());
+        match edit.apply(&buildozer).await {
+            Ok(_) => actions_completed.push(AppliedCorrection {
+                edit,
+                src_fn: String::from("compiler::buildozer_hint"),
+            }),
+            Err(e) => warn!("Failed to apply the compiler's suggestion: {:?}", e),
+        }
+    }
+    if !actions_completed.is_empty() {
+        for e in local_previous_seen.into_iter() {
+            global_previous_seen.insert(e);
+        }
+        return (actions_completed, explanation);
     }
 
     debug!("Prefix Candidates: {:#?}", prefix_candidate_import_requests);
     #[derive(Debug, PartialEq)]
     enum Request {
-        Prefix(String),
+        Prefix { class_name: String, src_fn: String },
         Suffix(error_extraction::ClassSuffixMatch),
     }
 
-    let all_requests: Vec<Vec<Request>> = Box::new(
-        super::sanitization_tools::expand_candidate_import_requests(
-            prefix_candidate_import_requests,
-        )
-        .into_iter()
-        .map(|(_, inner)| {
+    let expanded_requests = super::sanitization_tools::expand_candidate_import_requests(
+        prefix_candidate_import_requests,
+    );
+    explanation.expanded_class_names = expanded_requests
+        .iter()
+        .map(|(class_import_request, inner)| {
+            (class_import_request.class_name.clone(), inner.clone())
+        })
+        .collect();
+
+    let all_requests: Vec<Vec<Request>> = Box::new(expanded_requests.into_iter().map(
+        |(class_import_request, inner)| {
             inner
                 .into_iter()
-                .map(|e| Request::Prefix(e))
+                .map(|e| Request::Prefix {
+                    class_name: e,
+                    src_fn: class_import_request.src_fn.clone(),
+                })
                 .collect::<Vec<Request>>()
-        }),
-    )
+        },
+    ))
     .chain(
         suffix_requests
             .into_iter()
@@ -195,30 +264,58 @@ pub async fn process_missing_dependency_errors<T: Buildozer + Clone + Send + Syn
     .collect();
 
     for req in all_requests.into_iter() {
-        'class_entry_loop: for req in req.into_iter() {
-            let candidates: Vec<(u16, String)> = match &req {
-                Request::Prefix(class_name) => get_candidates_for_class_name(
-                    action_failed_error_info,
-                    &class_name,
-                    &index_table,
+        for req in req.into_iter() {
+            let (candidates, index_labels): (Vec<(u16, String)>, HashSet<String>) = match &req {
+                Request::Prefix { class_name, .. } => (
+                    get_candidates_for_class_name(
+                        action_failed_error_info,
+                        &class_name,
+                        &index_table,
+                        config,
+                    ),
+                    index_table
+                        .get(class_name)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(_, label)| super::sanitization_tools::sanitize_label(label))
+                        .collect(),
                 ),
                 Request::Suffix(suffix) => {
                     let mut r = index_table.get_from_suffix(&suffix.suffix);
                     r.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-                    r
+                    let index_labels = r.iter().map(|(_, label)| label.clone()).collect();
+                    (r, index_labels)
                 }
             };
             debug!("Candidates for class name: {:#?} : {:#?}", req, candidates);
-            for (_, target_name) in candidates {
-                if !ignore_dep_references.contains(&target_name)
-                    && is_potentially_valid_target(&target_name)
-                {
+            let src_fn = match &req {
+                Request::Prefix { src_fn, .. } => src_fn.clone(),
+                Request::Suffix(suffix) => suffix.src_fn.clone(),
+            };
+            let mut lookup = LookupExplanation {
+                lookup: match &req {
+                    Request::Prefix { class_name, .. } => class_name.clone(),
+                    Request::Suffix(suffix) => suffix.suffix.clone(),
+                },
+                src_fn: src_fn.clone(),
+                candidates: Vec::default(),
+            };
+            let mut resolved = false;
+            for (score, target_name) in candidates {
+                let outcome = if resolved {
+                    CandidateOutcome::NotTried
+                } else if declared_deps.contains(&target_name) {
+                    CandidateOutcome::Skipped(SkipReason::AlreadyInDeps)
+                } else if ignore_dep_references.contains(&target_name) {
+                    CandidateOutcome::Skipped(SkipReason::PreviouslySeen)
+                } else if !is_potentially_valid_target(&workspace_root, &target_name) {
+                    CandidateOutcome::Skipped(SkipReason::InvalidTarget)
+                } else if local_previous_seen.contains(&target_name) {
                     // If our top candidate hits to be a local previous seen stop
                     // processing this class
-                    if local_previous_seen.contains(&target_name) {
-                        break 'class_entry_loop;
-                    }
-
+                    resolved = true;
+                    CandidateOutcome::AlreadyAdded
+                } else {
                     // otherwise... add the dependency with buildozer here
                     // then add it ot the local seen dependencies
                     info!(
@@ -229,13 +326,35 @@ pub async fn process_missing_dependency_errors<T: Buildozer + Clone + Send + Syn
                         .add_dependency(&action_failed_error_info.label, &target_name)
                         .await
                         .unwrap();
-                    actions_completed += 1;
+                    actions_completed.push(AppliedCorrection {
+                        edit: BuildozerEdit::AddDependency {
+                            target: action_failed_error_info.label.clone(),
+                            label: target_name.clone(),
+                        },
+                        src_fn: src_fn.clone(),
+                    });
 
                     local_previous_seen.insert(target_name.clone());
+                    resolved = true;
+                    CandidateOutcome::Added
+                };
+                let source = if index_labels.contains(&target_name) {
+                    CandidateSource::Index
+                } else {
+                    CandidateSource::Guess
+                };
+                lookup.candidates.push(CandidateExplanation {
+                    label: target_name,
+                    score,
+                    source,
+                    outcome,
+                });
+            }
+            explanation.lookups.push(lookup);
 
-                    // Now that we have a version with a match we can jump right out to the outside
-                    break 'class_entry_loop;
-                }
+            // Now that we have a version with a match we can jump right out to the outside
+            if resolved {
+                break;
             }
         }
     }
@@ -246,11 +365,12 @@ pub async fn process_missing_dependency_errors<T: Buildozer + Clone + Send + Syn
         global_previous_seen.insert(e);
     }
 
-    actions_completed
+    (actions_completed, explanation)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::path::PathBuf;
 
     use super::*;
@@ -263,15 +383,22 @@ mod tests {
             vec![(13, String::from("//src/main/foop/blah:oop"))],
         );
         let index_table = index_table::IndexTable::from_hashmap(tbl_map);
+        let config = Config::default();
 
         let error_info = ActionFailedErrorInfo {
             label: String::from("//src/main/foo/asd/we:wer"),
+            mnemonic: Some(String::from("Scalac")),
             output_files: vec![],
             target_kind: Some(String::from("scala_library")),
         };
 
         assert_eq!(
-            get_candidates_for_class_name(&error_info, "com.example.bar.Baz", &index_table),
+            get_candidates_for_class_name(
+                &error_info,
+                "com.example.bar.Baz",
+                &index_table,
+                &config
+            ),
             vec![
                 (0, String::from("//src/main/scala/com/example/bar:bar")),
                 (0, String::from("//src/main/java/com/example/bar:bar")),
@@ -279,7 +406,12 @@ mod tests {
         );
 
         assert_eq!(
-            get_candidates_for_class_name(&error_info, "com.example.foo.bar.Baz", &index_table),
+            get_candidates_for_class_name(
+                &error_info,
+                "com.example.foo.bar.Baz",
+                &index_table,
+                &config
+            ),
             vec![
                 (13, String::from("//src/main/foop/blah:oop")),
                 (0, String::from("//src/main/scala/com/example/foo/bar:bar")),
@@ -288,7 +420,12 @@ mod tests {
         );
 
         assert_eq!(
-            get_candidates_for_class_name(&error_info, "com.example.a.b.c.Baz", &index_table),
+            get_candidates_for_class_name(
+                &error_info,
+                "com.example.a.b.c.Baz",
+                &index_table,
+                &config
+            ),
             vec![
                 (0, String::from("//src/main/scala/com/example/a/b/c:c")),
                 (0, String::from("//src/main/java/com/example/a/b/c:c"))
@@ -296,15 +433,146 @@ mod tests {
         );
     }
 
+    #[test]
+    fn get_candidates_without_configured_forbidden_deps() {
+        let mut tbl_map = HashMap::new();
+        tbl_map.insert(
+            String::from("com.example.foo.Foo"),
+            vec![
+                (13, String::from("//src/main/company/runtime:runtime")),
+                (2, String::from("//src/main/foop/blah:oop")),
+            ],
+        );
+        let index_table = index_table::IndexTable::from_hashmap(tbl_map);
+        let config = Config::parse(
+            "[rule_kinds.company_scala_library]
+forbidden_deps = [\"//src/main/company/runtime:runtime\"]
+",
+        )
+        .unwrap();
+
+        let error_info = ActionFailedErrorInfo {
+            label: String::from("//src/main/foo/asd/we:wer"),
+            mnemonic: Some(String::from("Scalac")),
+            output_files: vec![],
+            target_kind: Some(String::from("company_scala_library")),
+        };
+
+        assert_eq!(
+            get_candidates_for_class_name(
+                &error_info,
+                "com.example.foo.Foo",
+                &index_table,
+                &config
+            ),
+            vec![
+                (2, String::from("//src/main/foop/blah:oop")),
+                (0, String::from("//src/main/scala/com/example/foo:foo")),
+                (0, String::from("//src/main/java/com/example/foo:foo"))
+            ]
+        );
+    }
+
+    #[derive(Clone, Debug)]
+    struct DepsByTargetBuildozer {
+        deps: HashMap<String, Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Buildozer for DepsByTargetBuildozer {
+        async fn print_deps(&self, label: &String) -> crate::buildozer_driver::Result<Vec<String>> {
+            Ok(self.deps.get(label).cloned().unwrap_or_default())
+        }
+
+        async fn add_dependency(
+            &self,
+            _target: &String,
+            _label: &String,
+        ) -> crate::buildozer_driver::Result<()> {
+            Ok(())
+        }
+
+        async fn remove_dependency(
+            &self,
+            _target: &String,
+            _label: &String,
+        ) -> crate::buildozer_driver::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buildozer_hints() {
+        let mut deps = HashMap::new();
+        deps.insert(
+            String::from("//src/main/java/com/example/other:other"),
+            vec![String::from("//src/main/java/com/example/bar")],
+        );
+        let buildozer = DepsByTargetBuildozer { deps };
+
+        let error_info = ActionFailedErrorInfo {
+            label: String::from("//src/main/java/com/example:example"),
+            mnemonic: Some(String::from("Javac")),
+            output_files: vec![build_event_stream::file::File::Contents(
+                b"buildozer 'add deps //src/main/java/com/example/bar:bar' //src/main/java/com/example/other:other
+buildozer 'add deps //src/main/java/com/example/baz' //src/main/java/com/example/other:other
+buildozer 'remove deps //src/main/java/com/example/old' //src/main/java/com/example:example_auto_gen_0
+"
+                .to_vec(),
+            )],
+            target_kind: Some(String::from("java_library")),
+        };
+
+        let (corrections, _) = process_missing_dependency_errors(
+            &DashSet::new(),
+            buildozer,
+            &error_info,
+            &index_table::IndexTable::new(),
+            &Config::default(),
+        )
+        .await;
+        assert_eq!(
+            corrections.into_iter().map(|e| e.edit).collect::<Vec<_>>(),
+            vec![
+                BuildozerEdit::AddDependency {
+                    target: String::from("//src/main/java/com/example/other:other"),
+                    label: String::from("//src/main/java/com/example/baz:baz"),
+                },
+                BuildozerEdit::RemoveDependency {
+                    target: String::from("//src/main/java/com/example:example"),
+                    label: String::from("//src/main/java/com/example/old:old"),
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_is_potentially_valid_target() {
-        assert_eq!(is_potentially_valid_target("@foo/bar/baz"), true);
+        let workspace_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        assert!(is_potentially_valid_target(&workspace_root, "@foo/bar/baz"));
+
+        assert!(!is_potentially_valid_target(
+            &workspace_root,
+            "//foo/bar/foo"
+        ));
 
-        assert_eq!(is_potentially_valid_target("//foo/bar/foo"), false);
+        assert!(is_potentially_valid_target(
+            &workspace_root,
+            "//resources/tests/bazel/sample_build:sample_build"
+        ));
 
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("resources/tests/bazel/sample_build");
-        let built_path = format!("//{}", d.to_str().unwrap());
-        assert_eq!(is_potentially_valid_target(&built_path), true);
+        // Gazelle writes BUILD.bazel files
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("src/example/bar");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(package_dir.join("BUILD.bazel"), "").unwrap();
+        assert!(is_potentially_valid_target(
+            dir.path(),
+            "//src/example/bar:bar"
+        ));
+        assert!(!is_potentially_valid_target(
+            dir.path(),
+            "//src/example/baz:baz"
+        ));
     }
 }
@@ -0,0 +1,311 @@
+// Looks for deps a target declares but never uses, going by the .jdeps files the java and
+// scala compilers write alongside its jar, which record the jars on the classpath and whether
+// the compilation needed them.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use ::prost::Message;
+use bazelfe_protos::{blaze_deps, build_event_stream};
+
+use super::sanitization_tools::sanitize_label;
+use crate::{
+    build_events::hydrated_stream::TargetCompleteInfo,
+    buildozer_driver::{edit_plan::BuildozerEdit, Buildozer},
+    zip_parse::jar_target_label,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnusedDepsMode {
+    Ignore,
+    // Collect the removals, for them to be reported at the end of the session
+    Propose,
+    Remove,
+}
+
+impl FromStr for UnusedDepsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(UnusedDepsMode::Ignore),
+            "propose" => Ok(UnusedDepsMode::Propose),
+            "remove" => Ok(UnusedDepsMode::Remove),
+            other => Err(format!(
+                "Expected one of ignore, propose or remove, got {:?}",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UnusedDepsMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            UnusedDepsMode::Ignore => "ignore",
+            UnusedDepsMode::Propose => "propose",
+            UnusedDepsMode::Remove => "remove",
+        };
+        write!(f, "{}", mode)
+    }
+}
+
+fn jdeps_paths(tce: &TargetCompleteInfo) -> Vec<PathBuf> {
+    tce.output_files
+        .iter()
+        .chain(tce.hidden_output_files.iter())
+        .filter_map(|f| match f {
+            build_event_stream::file::File::Uri(uri) => uri.strip_prefix("file://"),
+            _ => None,
+        })
+        .filter(|path| path.ends_with(".jdeps"))
+        .map(PathBuf::from)
+        .collect()
+}
+
+// The paths in jdeps are relative to the execroot the .jdeps file was written under
+fn execroot_of(jdeps_path: &Path) -> Option<PathBuf> {
+    let path = jdeps_path.to_str()?;
+    path.find("/bazel-out/")
+        .map(|idx| PathBuf::from(&path[..idx]))
+}
+
+// Labels as bazel stamps them may name the main repository, which the BUILD files don't
+fn normalize_label(label: &str) -> String {
+    let label = match label.strip_prefix("@@") {
+        Some(rest) => format!("@{}", rest),
+        None => label.to_string(),
+    };
+    let label = match label.strip_prefix("@//") {
+        Some(rest) => format!("//{}", rest),
+        None => label,
+    };
+    sanitize_label(label)
+}
+
+// Resolves deps relative to the target's package, such as :foo, to full labels
+fn absolute_label(target: &str, dep: &str) -> String {
+    match dep.strip_prefix(':') {
+        Some(name) => {
+            let package = target.split(':').next().unwrap_or(target);
+            format!("{}:{}", package, name)
+        }
+        None => dep.to_string(),
+    }
+}
+
+// The declared deps whose own jars were on the classpath but unused, going by the labels of
+// the targets the jars were built for. Deps without a jar of their own, such as aliases or
+// targets which only export others, can't be told apart from used ones so they're kept.
+pub fn unused_dependencies(
+    target: &str,
+    declared_deps: &[String],
+    dependencies: &[blaze_deps::Dependencies],
+    jar_labels: &HashMap<String, String>,
+) -> Vec<String> {
+    if dependencies.is_empty() || dependencies.iter().any(|d| !d.success()) {
+        return Vec::default();
+    }
+    let mut used: HashSet<String> = HashSet::default();
+    let mut unused: HashSet<String> = HashSet::default();
+    for d in dependencies.iter().flat_map(|e| e.dependency.iter()) {
+        if let Some(label) = jar_labels.get(&d.path) {
+            let label = normalize_label(label);
+            if d.kind() == blaze_deps::dependency::Kind::Unused {
+                unused.insert(label);
+            } else {
+                used.insert(label);
+            }
+        }
+    }
+    declared_deps
+        .iter()
+        .filter(|dep| {
+            let label = normalize_label(&absolute_label(target, dep));
+            unused.contains(&label) && !used.contains(&label)
+        })
+        .cloned()
+        .collect()
+}
+
+pub async fn find_unused_dependencies<T: Buildozer + Clone + Send + Sync + 'static>(
+    buildozer: &T,
+    tce: &TargetCompleteInfo,
+) -> Vec<BuildozerEdit> {
+    if !tce.success {
+        return Vec::default();
+    }
+    let mut dependencies = Vec::default();
+    let mut jar_labels = HashMap::default();
+    for path in jdeps_paths(tce) {
+        let d = match tokio::fs::read(&path).await {
+            Ok(data) => match blaze_deps::Dependencies::decode(&*data) {
+                Ok(d) => d,
+                Err(e) => {
+                    warn!("Unable to decode jdeps {:?}: {:?}", path, e);
+                    continue;
+                }
+            },
+            Err(e) => {
+                warn!("Unable to read jdeps {:?}: {:?}", path, e);
+                continue;
+            }
+        };
+        if let Some(execroot) = execroot_of(&path) {
+            for dependency in d.dependency.iter() {
+                if !jar_labels.contains_key(&dependency.path) {
+                    if let Some(label) = jar_target_label(&execroot.join(&dependency.path)) {
+                        jar_labels.insert(dependency.path.clone(), label);
+                    }
+                }
+            }
+        }
+        dependencies.push(d);
+    }
+    if dependencies.is_empty() {
+        return Vec::default();
+    }
+
+    let declared_deps = match buildozer.print_deps(&tce.label).await {
+        Ok(deps) => deps,
+        Err(e) => {
+            warn!("Unable to read the deps of {}: {:?}", tce.label, e);
+            return Vec::default();
+        }
+    };
+    unused_dependencies(&tce.label, &declared_deps, &dependencies, &jar_labels)
+        .into_iter()
+        .map(|label| BuildozerEdit::RemoveDependency {
+            target: tce.label.clone(),
+            label,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(path: &str, kind: blaze_deps::dependency::Kind) -> blaze_deps::Dependency {
+        let mut d = blaze_deps::Dependency {
+            path: String::from(path),
+            ..Default::default()
+        };
+        d.set_kind(kind);
+        d
+    }
+
+    #[test]
+    fn test_execroot_of() {
+        assert_eq!(
+            execroot_of(Path::new(
+                "/home/user/.cache/bazel/execroot/__main__/bazel-out/k8-fastbuild/bin/foo/libfoo.jdeps"
+            )),
+            Some(PathBuf::from("/home/user/.cache/bazel/execroot/__main__"))
+        );
+        assert_eq!(execroot_of(Path::new("/tmp/libfoo.jdeps")), None);
+    }
+
+    #[test]
+    fn test_unused_dependencies() {
+        use blaze_deps::dependency::Kind;
+        let foo_jar = "bazel-out/k8-fastbuild/bin/src/main/java/com/example/foo/libfoo-hjar.jar";
+        let guava_jar =
+            "bazel-out/k8-fastbuild/bin/external/maven/v1/https/repo1/guava-29.0-ijar.jar";
+        let bar_jar = "bazel-out/k8-fastbuild/bin/src/main/java/com/example/bar/libbar-hjar.jar";
+        let exported_jar =
+            "bazel-out/k8-fastbuild/bin/src/main/java/com/example/baz/libbaz-hjar.jar";
+        let dependencies = blaze_deps::Dependencies {
+            dependency: vec![
+                dependency(foo_jar, Kind::Explicit),
+                dependency(guava_jar, Kind::Implicit),
+                dependency(bar_jar, Kind::Unused),
+                dependency(exported_jar, Kind::Unused),
+            ],
+            success: Some(true),
+            ..Default::default()
+        };
+        let mut jar_labels = HashMap::new();
+        jar_labels.insert(
+            String::from(foo_jar),
+            String::from("@//src/main/java/com/example/foo:foo"),
+        );
+        jar_labels.insert(
+            String::from(guava_jar),
+            String::from("@maven//:com_google_guava_guava"),
+        );
+        jar_labels.insert(
+            String::from(bar_jar),
+            String::from("//src/main/java/com/example/bar:bar"),
+        );
+        jar_labels.insert(
+            String::from(exported_jar),
+            String::from("//src/main/java/com/example/baz:baz"),
+        );
+        let declared = vec![
+            String::from("//src/main/java/com/example/foo"),
+            String::from("//src/main/java/com/example/bar:bar"),
+            // Exports //src/main/java/com/example/baz, with no jar of its own
+            String::from(":aggregate"),
+            // A bazel-deps alias of @maven//:com_google_guava_guava
+            String::from("@third_party_jvm//3rdparty/jvm/com/google/guava"),
+        ];
+
+        assert_eq!(
+            unused_dependencies(
+                "//src/main/java/com/example/app:app",
+                &declared,
+                std::slice::from_ref(&dependencies),
+                &jar_labels
+            ),
+            vec![String::from("//src/main/java/com/example/bar:bar")]
+        );
+
+        // Jdeps from failed compilations can be missing what was used
+        let failed = blaze_deps::Dependencies {
+            success: Some(false),
+            ..dependencies
+        };
+        assert!(unused_dependencies(
+            "//src/main/java/com/example/app:app",
+            &declared,
+            &[failed],
+            &jar_labels
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_unused_bazel_deps_alias() {
+        use blaze_deps::dependency::Kind;
+        // bazel-deps aliases the jars it provides, so they're stamped with the targets the
+        // aliases point at rather than the deps as declared
+        let guava_jar =
+            "bazel-out/k8-fastbuild/bin/external/maven/v1/https/repo1/guava-29.0-ijar.jar";
+        let mut jar_labels = HashMap::new();
+        jar_labels.insert(
+            String::from(guava_jar),
+            String::from("@maven//:com_google_guava_guava"),
+        );
+        let declared = vec![String::from(
+            "@third_party_jvm//3rdparty/jvm/com/google/guava:guava",
+        )];
+
+        for kind in vec![Kind::Explicit, Kind::Unused].into_iter() {
+            let dependencies = blaze_deps::Dependencies {
+                dependency: vec![dependency(guava_jar, kind)],
+                success: Some(true),
+                ..Default::default()
+            };
+            assert!(unused_dependencies(
+                "//src/main/scala/com/example:example",
+                &declared,
+                &[dependencies],
+                &jar_labels
+            )
+            .is_empty());
+        }
+    }
+}
@@ -0,0 +1,173 @@
+// Feeds a recorded build back through the correction pipeline, to see which edits we
+// would make for it without running bazel.
+
+use bazelfe_protos::google::devtools::build::v1::PublishBuildToolEventStreamRequest;
+use tokio::sync::broadcast;
+
+use super::action_event_stream::{ActionEventStream, AppliedCorrection};
+use crate::build_events::build_event_server::{bazel_event, BuildEventAction};
+use crate::build_events::hydrated_stream::HydratedInfo;
+use crate::buildozer_driver::Buildozer;
+
+pub async fn replay<T>(
+    requests: Vec<PublishBuildToolEventStreamRequest>,
+    aes: &ActionEventStream<T>,
+) -> Vec<AppliedCorrection>
+where
+    T: Buildozer + Send + Clone + Sync + 'static,
+{
+    // Sized to hold the whole recording, so nothing is dropped if we get ahead of the pipeline
+    let (tx, rx) = broadcast::channel(requests.len() + 1);
+    let error_stream = HydratedInfo::build_transformer(rx);
+    let mut corrections_stream = aes.build_action_pipeline(error_stream);
+
+    for mut request in requests.into_iter() {
+        if let Some(evt) = bazel_event::BazelBuildEvent::transform_from(&mut request) {
+            let _ = tx.send(BuildEventAction::BuildEvent(evt));
+        }
+    }
+    let _ = tx.send(BuildEventAction::BuildCompleted);
+    drop(tx);
+
+    let mut corrections = Vec::default();
+    while let Some(action) = corrections_stream.recv().await {
+        if let Some(c) = action {
+            corrections.extend(c);
+        }
+    }
+    corrections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buildozer_driver::edit_plan::{BuildozerEdit, EditPlan, PlanningBuildozer};
+    use async_trait::async_trait;
+
+    #[derive(Clone, Debug)]
+    struct NoDepsBuildozer;
+
+    #[async_trait]
+    impl Buildozer for NoDepsBuildozer {
+        async fn print_deps(
+            &self,
+            _label: &String,
+        ) -> crate::buildozer_driver::Result<Vec<String>> {
+            Ok(Vec::default())
+        }
+
+        async fn add_dependency(
+            &self,
+            _target: &String,
+            _label: &String,
+        ) -> crate::buildozer_driver::Result<()> {
+            panic!("Replays should only plan edits")
+        }
+
+        async fn remove_dependency(
+            &self,
+            _target: &String,
+            _label: &String,
+        ) -> crate::buildozer_driver::Result<()> {
+            panic!("Replays should only plan edits")
+        }
+    }
+
+    fn bazel_event_request(
+        evt: bazelfe_protos::build_event_stream::BuildEvent,
+    ) -> PublishBuildToolEventStreamRequest {
+        use ::prost::Message;
+        use bazelfe_protos::google::devtools::build::v1;
+        let mut value = vec![];
+        evt.encode(&mut value).unwrap();
+        PublishBuildToolEventStreamRequest {
+            ordered_build_event: Some(v1::OrderedBuildEvent {
+                event: Some(v1::BuildEvent {
+                    event: Some(v1::build_event::Event::BazelEvent(prost_types::Any {
+                        type_url: String::from("type.googleapis.com/build_event_stream.BuildEvent"),
+                        value,
+                    })),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_failed_build() {
+        use bazelfe_protos::build_event_stream::*;
+        let label = String::from("//src/main/scala/com/example:example");
+        let requests = vec![
+            bazel_event_request(BuildEvent {
+                id: Some(BuildEventId {
+                    id: Some(build_event_id::Id::TargetConfigured(
+                        build_event_id::TargetConfiguredId {
+                            label: label.clone(),
+                            ..Default::default()
+                        },
+                    )),
+                }),
+                payload: Some(build_event::Payload::Configured(TargetConfigured {
+                    target_kind: String::from("scala_library rule"),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }),
+            bazel_event_request(BuildEvent {
+                id: Some(BuildEventId {
+                    id: Some(build_event_id::Id::ActionCompleted(
+                        build_event_id::ActionCompletedId {
+                            label: label.clone(),
+                            ..Default::default()
+                        },
+                    )),
+                }),
+                payload: Some(build_event::Payload::Action(ActionExecuted {
+                    success: false,
+                    stderr: Some(File {
+                        file: Some(file::File::Contents(
+                            b"src/main/scala/com/example/Example.scala:2: error: object foo is not a member of package com.example
+import com.example.foo.bar.Baz
+                   ^
+one error found"
+                                .to_vec(),
+                        )),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }),
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        let index_path = dir.path().join("index");
+        std::fs::write(
+            &index_path,
+            "com.example.foo\t1:@third_party_jvm//3rdparty/jvm/com/example:foo\n",
+        )
+        .unwrap();
+
+        let edit_plan = EditPlan::new();
+        let aes = ActionEventStream::new(
+            Some(index_path),
+            PlanningBuildozer::new(NoDepsBuildozer, edit_plan.clone()),
+        );
+        let corrections = replay(requests, &aes).await;
+
+        let expected_edit = BuildozerEdit::AddDependency {
+            target: label.clone(),
+            label: String::from("@third_party_jvm//3rdparty/jvm/com/example:foo"),
+        };
+        assert_eq!(
+            corrections
+                .iter()
+                .map(|c| c.edit.clone())
+                .collect::<Vec<_>>(),
+            vec![expected_edit.clone()]
+        );
+        assert_eq!(edit_plan.edits(), vec![expected_edit]);
+    }
+}
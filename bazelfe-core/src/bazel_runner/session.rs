@@ -0,0 +1,416 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bazelfe_protos::google::devtools::build::v1::publish_build_event_server::PublishBuildEventServer;
+use rand::Rng;
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex};
+use tonic::transport::Server;
+
+use super::action_event_stream::{ActionEventStream, AppliedCorrection};
+use super::event_log::SessionEvent;
+use super::journal::Journal;
+use super::{ExecuteOptions, ExecuteResult};
+use crate::build_events::build_event_file;
+use crate::build_events::build_event_server::{self, bazel_event, BuildEventAction};
+use crate::build_events::hydrated_stream::HydratedInfo;
+use crate::build_events::recording::{self, BuildEventRecorder};
+use crate::build_events::upstream::Upstream;
+use crate::buildozer_driver::{batching::BatchingBuildozer, edit_plan::BuildozerEdit, Buildozer};
+
+pub type BuildEventSender =
+    Arc<Mutex<Option<broadcast::Sender<BuildEventAction<bazel_event::BazelBuildEvent>>>>>;
+
+pub const MAX_ATTEMPTS: u16 = 15;
+
+// What a live view of a session, such as the bazel-fe-bin dashboard, is sent as it goes on
+#[derive(Clone, Debug)]
+pub enum SessionUpdate {
+    AttemptStarted(u16),
+    Hydrated(HydratedInfo),
+    CorrectionsApplied(Vec<AppliedCorrection>),
+    AttemptFinished { exit_code: i32 },
+    SessionFinished { exit_code: i32, attempts: u16 },
+}
+
+pub type SessionUpdateSender = mpsc::UnboundedSender<SessionUpdate>;
+
+// Sessions are identified by their start time, in seconds since the epoch
+pub fn new_session_id() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Starts the build event service bazel will publish to, returning the sender its events are
+// relayed through and the port it listens on.
+pub fn start_build_event_service(
+    bind_address: Option<String>,
+    record_build_events: Option<PathBuf>,
+    upstream: Option<Upstream>,
+) -> std::io::Result<(BuildEventSender, u16)> {
+    let mut rng = rand::thread_rng();
+    let default_port = {
+        let rand_v: u16 = rng.gen();
+        40000 + (rand_v % 3000)
+    };
+
+    let addr: std::net::SocketAddr = bind_address
+        .or(std::env::var("BIND_ADDRESS").ok())
+        .unwrap_or_else(|| format!("127.0.0.1:{}", default_port))
+        .parse()
+        .expect("can't parse BIND_ADDRESS variable");
+
+    info!("Services listening on {}", addr);
+
+    let (mut bes, sender_arc, _) = match record_build_events {
+        Some(path) => {
+            let recorder = BuildEventRecorder::create(&path)?;
+            recording::build_recording_build_events_service(recorder)
+        }
+        None => build_event_server::build_bazel_build_events_service(),
+    };
+    bes.upstream = upstream;
+
+    let bes_port: u16 = addr.port();
+
+    let _service_fut = tokio::spawn(async move {
+        Server::builder()
+            .add_service(PublishBuildEventServer::new(bes))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+    Ok((sender_arc, bes_port))
+}
+
+pub async fn spawn_bazel_attempt<T>(
+    sender_arc: &BuildEventSender,
+    aes: &ActionEventStream<T>,
+    bes_port: u16,
+    passthrough_args: &[String],
+    options: &ExecuteOptions,
+) -> (Vec<AppliedCorrection>, ExecuteResult)
+where
+    T: Buildozer + Send + Clone + Sync + 'static,
+{
+    let (tx, rx) = broadcast::channel(8192);
+    let file_tailer = options.build_event_file.as_ref().map(|path| {
+        // Anything left from an earlier build would be read as part of this one
+        let _ = std::fs::remove_file(path);
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let tail_task = tokio::spawn(build_event_file::tail_build_event_file(
+            path.clone(),
+            tx.clone(),
+            finished_rx,
+        ));
+        (finished_tx, tail_task)
+    });
+    {
+        let mut locked = sender_arc.lock().await;
+        *locked = Some(tx);
+    }
+    let error_stream = HydratedInfo::build_transformer(rx);
+
+    let mut target_extracted_stream = aes.build_action_pipeline(error_stream);
+
+    let recv_task = tokio::spawn(async move {
+        let mut actions_completed: Vec<AppliedCorrection> = Vec::default();
+        while let Some(action) = target_extracted_stream.recv().await {
+            match action {
+                None => (),
+                Some(corrections) => {
+                    actions_completed.extend(corrections);
+                }
+            }
+        }
+        actions_completed
+    });
+    let res = super::execute_bazel_with_options(passthrough_args.to_vec(), bes_port, options).await;
+
+    info!("Bazel completed with state: {:?}", res);
+    if let Some((finished_tx, tail_task)) = file_tailer {
+        let _ = finished_tx.send(());
+        let _ = tail_task.await;
+    }
+    {
+        let mut locked = sender_arc.lock().await;
+        locked.take();
+    }
+
+    let actions_completed = recv_task.await.unwrap();
+    info!("Receive task done");
+    (actions_completed, res)
+}
+
+pub fn record_in_journal(
+    journal: &Option<Journal>,
+    session_id: u64,
+    attempt: u16,
+    corrections: &[AppliedCorrection],
+) {
+    if let Some(journal) = journal {
+        if let Err(e) = journal.record(session_id, attempt, corrections) {
+            warn!("Failed to record edits in the journal: {:?}", e);
+        }
+    }
+}
+
+pub async fn persist_index<T>(aes: &ActionEventStream<T>)
+where
+    T: Buildozer + Send + Clone + Sync + 'static,
+{
+    match aes.persist_index().await {
+        Ok(true) => info!("Updated the index with targets built this session"),
+        Ok(false) => (),
+        Err(e) => warn!("Failed to write out the updated index: {:?}", e),
+    }
+}
+
+pub fn report_proposed_removals<T>(aes: &ActionEventStream<T>)
+where
+    T: Buildozer + Send + Clone + Sync + 'static,
+{
+    print!("{}", proposed_removals_report(&aes.proposed_removals()));
+}
+
+// What's reported for the removals proposed in a session, nothing when there are none
+pub fn proposed_removals_report(removals: &[BuildozerEdit]) -> String {
+    if removals.is_empty() {
+        return String::new();
+    }
+    let mut report = String::from("Deps unused by the targets built, which could be removed:\n");
+    for edit in removals.iter() {
+        report.push_str(&edit.to_buildozer_command());
+        report.push('\n');
+    }
+    report
+}
+
+// Puts back deps whose removal broke the build, dropping the removals from the journal.
+// They're remembered so the same deps aren't removed again.
+async fn revert_removals<T>(
+    aes: &ActionEventStream<BatchingBuildozer<T>>,
+    batching_buildozer: &BatchingBuildozer<T>,
+    journal: &Option<Journal>,
+    session_id: u64,
+    removals: &[AppliedCorrection],
+) where
+    T: Buildozer + Send + Clone + Sync + 'static,
+{
+    for correction in removals.iter() {
+        let reversed = correction.edit.reversed();
+        warn!(
+            "The build failed after removing unused deps, reverting: {}",
+            reversed.to_buildozer_command()
+        );
+        aes.reject_removal(correction.edit.clone());
+        if let Err(e) = reversed.apply(batching_buildozer).await {
+            warn!("Failed to queue reverting the removal: {:?}", e);
+        }
+    }
+    let applied = batching_buildozer.flush().await;
+    let reverted: Vec<BuildozerEdit> = removals
+        .iter()
+        .map(|c| c.edit.clone())
+        .filter(|e| applied.contains(&e.reversed()))
+        .collect();
+    if let Some(journal) = journal {
+        if let Err(e) = journal.remove_session_edits(session_id, &reverted) {
+            warn!("Failed to drop reverted edits from the journal: {:?}", e);
+        }
+    }
+}
+
+// Runs bazel until it succeeds or an attempt makes no corrections, applying the edits
+// queued during each attempt once it has finished. An attempt which removed unused deps is
+// always followed by another, with the removals reverted if that one fails.
+// Returns bazel's final exit code.
+pub async fn run_batched_attempts<T>(
+    sender_arc: &BuildEventSender,
+    aes: &ActionEventStream<BatchingBuildozer<T>>,
+    batching_buildozer: &BatchingBuildozer<T>,
+    bes_port: u16,
+    passthrough_args: &[String],
+    journal: &Option<Journal>,
+    options: &ExecuteOptions,
+) -> i32
+where
+    T: Buildozer + Send + Clone + Sync + 'static,
+{
+    let session_id = new_session_id();
+    let mut attempts: u16 = 0;
+    let mut final_exit_code = 0;
+    // Removals made by the last attempt, which the next has to build without
+    let mut unverified_removals: Vec<AppliedCorrection> = Vec::default();
+    if let Some(event_log) = aes.event_log() {
+        event_log.start_session(session_id, passthrough_args);
+    }
+    while attempts < MAX_ATTEMPTS {
+        if let Some(event_log) = aes.event_log() {
+            event_log.start_attempt(attempts);
+        }
+        aes.send_session_update(SessionUpdate::AttemptStarted(attempts));
+        let (proposed_corrections, bazel_result) =
+            spawn_bazel_attempt(sender_arc, aes, bes_port, passthrough_args, options).await;
+        final_exit_code = bazel_result.exit_code;
+        let applied = batching_buildozer.flush().await;
+        let actions_corrected: Vec<AppliedCorrection> = proposed_corrections
+            .into_iter()
+            .filter(|c| applied.contains(&c.edit))
+            .collect();
+        record_in_journal(journal, session_id, attempts, &actions_corrected);
+        if let Some(event_log) = aes.event_log() {
+            event_log.log_edits(&actions_corrected);
+            event_log.log(SessionEvent::AttemptFinished {
+                exit_code: bazel_result.exit_code,
+            });
+        }
+        if !actions_corrected.is_empty() {
+            aes.send_session_update(SessionUpdate::CorrectionsApplied(actions_corrected.clone()));
+        }
+        aes.send_session_update(SessionUpdate::AttemptFinished {
+            exit_code: bazel_result.exit_code,
+        });
+        let reverted = !unverified_removals.is_empty() && bazel_result.exit_code != 0;
+        if reverted {
+            revert_removals(
+                aes,
+                batching_buildozer,
+                journal,
+                session_id,
+                &unverified_removals,
+            )
+            .await;
+        }
+        unverified_removals = actions_corrected
+            .iter()
+            .filter(|c| matches!(c.edit, BuildozerEdit::RemoveDependency { .. }))
+            .cloned()
+            .collect();
+        if super::stop_requested() {
+            break;
+        }
+        if !reverted
+            && unverified_removals.is_empty()
+            && (bazel_result.exit_code == 0 || actions_corrected.is_empty())
+        {
+            break;
+        }
+        attempts += 1;
+    }
+    if !unverified_removals.is_empty() {
+        // Nothing built without them, so they can't be trusted
+        revert_removals(
+            aes,
+            batching_buildozer,
+            journal,
+            session_id,
+            &unverified_removals,
+        )
+        .await;
+    }
+
+    info!("Attempts/build cycles: {:?}", attempts);
+    if let Some(event_log) = aes.event_log() {
+        event_log.log(SessionEvent::SessionFinished {
+            exit_code: final_exit_code,
+            attempts,
+        });
+    }
+    aes.send_session_update(SessionUpdate::SessionFinished {
+        exit_code: final_exit_code,
+        attempts,
+    });
+    final_exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buildozer_driver::Result;
+    use async_trait::async_trait;
+
+    // Records the edits applied through it
+    #[derive(Clone, Debug, Default)]
+    struct RecordingBuildozer {
+        applied: Arc<std::sync::Mutex<Vec<BuildozerEdit>>>,
+    }
+
+    #[async_trait]
+    impl Buildozer for RecordingBuildozer {
+        async fn print_deps(&self, _label: &String) -> Result<Vec<String>> {
+            Ok(Vec::default())
+        }
+
+        async fn add_dependency(&self, target: &String, label: &String) -> Result<()> {
+            self.applied
+                .lock()
+                .unwrap()
+                .push(BuildozerEdit::AddDependency {
+                    target: target.clone(),
+                    label: label.clone(),
+                });
+            Ok(())
+        }
+
+        async fn remove_dependency(&self, target: &String, label: &String) -> Result<()> {
+            self.applied
+                .lock()
+                .unwrap()
+                .push(BuildozerEdit::RemoveDependency {
+                    target: target.clone(),
+                    label: label.clone(),
+                });
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revert_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path().join("journal"));
+        let recorder = RecordingBuildozer::default();
+        let batching_buildozer = BatchingBuildozer::new(recorder.clone());
+        let aes = ActionEventStream::new(None, batching_buildozer.clone());
+
+        let target = String::from("//src/main/java/com/example:example");
+        let removal = AppliedCorrection {
+            edit: BuildozerEdit::RemoveDependency {
+                target: target.clone(),
+                label: String::from("//a:a"),
+            },
+            src_fn: String::from("jdeps::unused_dependency"),
+        };
+        let addition = AppliedCorrection {
+            edit: BuildozerEdit::AddDependency {
+                target: target.clone(),
+                label: String::from("//b:b"),
+            },
+            src_fn: String::from("java::cannot_find_symbol"),
+        };
+        journal
+            .record(7, 0, &[removal.clone(), addition.clone()])
+            .unwrap();
+
+        revert_removals(
+            &aes,
+            &batching_buildozer,
+            &Some(journal.clone()),
+            7,
+            &[removal],
+        )
+        .await;
+
+        assert_eq!(
+            *recorder.applied.lock().unwrap(),
+            vec![BuildozerEdit::AddDependency {
+                target,
+                label: String::from("//a:a"),
+            }]
+        );
+        let remaining = journal.read_entries().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].edit, addition.edit);
+    }
+}
@@ -1,12 +1,19 @@
 use argh::FromArgs;
 use bazelfe_core::app::App;
-use bazelfe_core::event::{Config, Event, Events};
+use bazelfe_core::bazel_runner::{
+    self, action_event_stream::ActionEventStream, config, config::Config, session, ExecuteOptions,
+    OutputSink,
+};
+use bazelfe_core::buildozer_driver::{self, batching::BatchingBuildozer};
+use bazelfe_core::event::{Config as EventsConfig, Event, Events};
 use bazelfe_core::ui;
-use std::{error::Error, io, time::Duration};
+use std::path::PathBuf;
+use std::{error::Error, io, thread, time::Duration};
 use termion::{event::Key, input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
 use tui::{backend::TermionBackend, Terminal};
 
-/// Termion demo
+/// Runs a bazel command, adding the deps it's missing between attempts, with a live dashboard
+/// of the session. Put the command after a --, e.g. bazel-fe-bin -- bazel build //...
 #[derive(Debug, FromArgs)]
 struct Cli {
     /// time in ms between two ticks.
@@ -15,14 +22,77 @@ struct Cli {
     /// whether unicode symbols are used to improve the overall look of the app
     #[argh(option, default = "true")]
     enhanced_graphics: bool,
+    /// the index of classes to the targets providing them
+    #[argh(option)]
+    index_input_location: Option<PathBuf>,
+    /// the bazel command to run
+    #[argh(positional)]
+    command: Vec<String>,
+}
+
+// Runs bazel until it succeeds or an attempt makes no corrections, as bazel-runner would,
+// sending what happens to the dashboard.
+fn run_session(
+    command: Vec<String>,
+    index_input_location: Option<PathBuf>,
+    config: Config,
+    workspace_root: PathBuf,
+    session_updates: session::SessionUpdateSender,
+) -> i32 {
+    let mut runtime = tokio::runtime::Runtime::new().expect("Unable to start the tokio runtime");
+    runtime.block_on(async move {
+        let (sender_arc, bes_port) = session::start_build_event_service(None, None, None)
+            .expect("Unable to start the build event service");
+        let batching_buildozer =
+            BatchingBuildozer::new(buildozer_driver::from_workspace_root(workspace_root));
+        let aes = ActionEventStream::new(index_input_location, batching_buildozer.clone())
+            .with_config(config)
+            .with_session_updates(Some(session_updates));
+        // The dashboard is drawn where bazel would write its output
+        let options = ExecuteOptions {
+            output: OutputSink::Discard,
+            build_event_file: bazel_runner::build_event_file_for(&command),
+            ..Default::default()
+        };
+        session::run_batched_attempts(
+            &sender_arc,
+            &aes,
+            &batching_buildozer,
+            bes_port,
+            &command,
+            &None,
+            &options,
+        )
+        .await
+    })
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli: Cli = argh::from_env();
+    if cli.command.is_empty() {
+        return Err(
+            "A bazel command to run is required, e.g. bazel-fe-bin -- bazel build //...".into(),
+        );
+    }
+
+    let workspace_root = config::workspace_root()?;
+    let config = Config::load(&workspace_root)?;
+    let (session_updates, mut updates) = tokio::sync::mpsc::unbounded_channel();
+    let command = cli.command.clone();
+    let index_input_location = cli.index_input_location.clone();
+    let session = thread::spawn(move || {
+        run_session(
+            command,
+            index_input_location,
+            config,
+            workspace_root,
+            session_updates,
+        )
+    });
 
-    let events = Events::with_config(Config {
+    let events = Events::with_config(EventsConfig {
         tick_rate: Duration::from_millis(cli.tick_rate),
-        ..Config::default()
+        ..EventsConfig::default()
     });
 
     let stdout = io::stdout().into_raw_mode()?;
@@ -31,7 +101,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new("Termion demo", cli.enhanced_graphics);
+    let mut app = App::new("bazel-fe", cli.enhanced_graphics);
     loop {
         terminal.draw(|f| ui::draw(f, &mut app))?;
 
@@ -52,10 +122,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                 Key::Right => {
                     app.on_right();
                 }
+                Key::Esc => {
+                    app.on_escape();
+                }
                 _ => {}
             },
             Event::Tick => {
-                app.on_tick();
+                while let Ok(update) = updates.try_recv() {
+                    app.on_session_update(update);
+                }
             }
         }
         if app.should_quit {
@@ -63,5 +138,13 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    Ok(())
+    // Leave the alternate screen before exiting
+    drop(terminal);
+    if app.exit_code.is_none() {
+        // Quitting early, bazel is stopped and the attempt's BUILD edits flushed before exiting
+        eprintln!("Stopping bazel and waiting for pending BUILD file edits...");
+        bazel_runner::request_stop();
+    }
+    let session_exit_code = session.join().unwrap_or(1);
+    std::process::exit(app.exit_code.unwrap_or(session_exit_code));
 }
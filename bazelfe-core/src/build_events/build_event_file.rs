@@ -0,0 +1,186 @@
+// Follows the file bazel writes with --build_event_binary_file, producing the same actions
+// the build event service does. This lets us work alongside a bes backend of the user's
+// own, where we can't have bazel publish to us.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ::prost::Message;
+use bazelfe_protos::build_event_stream;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{broadcast, oneshot};
+
+use super::build_event_server::{bazel_event, BuildEventAction};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Takes the next complete event from the front of the buffer. Bazel writes each event
+// prefixed by its varint encoded length, so None means we need to wait for more data.
+fn take_event(
+    buffer: &mut Vec<u8>,
+) -> Option<Result<build_event_stream::BuildEvent, prost::DecodeError>> {
+    let len = prost::decode_length_delimiter(&buffer[..]).ok()?;
+    let header_len = prost::length_delimiter_len(len);
+    if buffer.len() < header_len + len {
+        return None;
+    }
+    let evt = build_event_stream::BuildEvent::decode(&buffer[header_len..header_len + len]);
+    buffer.drain(..header_len + len);
+    Some(evt)
+}
+
+// Reads events as they're appended to the file until bazel writes its last one, or
+// `bazel_finished` fires and we've drained what's left. The file needn't exist yet.
+pub async fn tail_build_event_file(
+    path: PathBuf,
+    tx: broadcast::Sender<BuildEventAction<bazel_event::BazelBuildEvent>>,
+    mut bazel_finished: oneshot::Receiver<()>,
+) {
+    let mut file: Option<tokio::fs::File> = None;
+    let mut buffer: Vec<u8> = Vec::default();
+    let mut chunk = [0; 8192];
+    let mut finished = false;
+    loop {
+        // Checked before reading, so once bazel has exited we do a final full read
+        if !finished {
+            finished = !matches!(
+                bazel_finished.try_recv(),
+                Err(oneshot::error::TryRecvError::Empty)
+            );
+        }
+
+        if file.is_none() {
+            file = tokio::fs::File::open(&path).await.ok();
+        }
+        if let Some(f) = file.as_mut() {
+            loop {
+                match f.read(&mut chunk[..]).await {
+                    Ok(0) => break,
+                    Ok(bytes_read) => buffer.extend_from_slice(&chunk[0..bytes_read]),
+                    Err(e) => {
+                        warn!("Failed reading build events from {:?}: {:?}", path, e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        while let Some(evt) = take_event(&mut buffer) {
+            match evt {
+                Ok(evt) => {
+                    let last_message = evt.last_message;
+                    let _ = tx.send(BuildEventAction::BuildEvent(
+                        bazel_event::BazelBuildEvent::from_build_event(evt),
+                    ));
+                    if last_message {
+                        let _ = tx.send(BuildEventAction::BuildCompleted);
+                        return;
+                    }
+                }
+                Err(e) => warn!("Unable to decode build event from {:?}: {:?}", path, e),
+            }
+        }
+
+        if finished {
+            break;
+        }
+        tokio::time::delay_for(POLL_INTERVAL).await;
+    }
+    let _ = tx.send(BuildEventAction::BuildCompleted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn progress_event(stdout: &str, last_message: bool) -> build_event_stream::BuildEvent {
+        build_event_stream::BuildEvent {
+            payload: Some(build_event_stream::build_event::Payload::Progress(
+                build_event_stream::Progress {
+                    stdout: String::from(stdout),
+                    ..Default::default()
+                },
+            )),
+            last_message,
+            ..Default::default()
+        }
+    }
+
+    fn encoded(evt: &build_event_stream::BuildEvent) -> Vec<u8> {
+        let mut buf = vec![];
+        evt.encode_length_delimited(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_take_event_waits_for_whole_event() {
+        let data = encoded(&progress_event("hello", false));
+        let mut buffer = data[0..data.len() - 1].to_vec();
+        assert!(take_event(&mut buffer).is_none());
+
+        buffer.push(data[data.len() - 1]);
+        assert_eq!(
+            take_event(&mut buffer).unwrap().unwrap(),
+            progress_event("hello", false)
+        );
+        assert!(buffer.is_empty());
+        assert!(take_event(&mut buffer).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tail_file_as_it_is_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build_events.bin");
+
+        let (tx, mut rx) = broadcast::channel(16);
+        let (_finished_tx, finished_rx) = oneshot::channel();
+        let tail_task = tokio::spawn(tail_build_event_file(path.clone(), tx, finished_rx));
+
+        // Bazel creates the file after we start, and may be part way through an event
+        // when we read it
+        tokio::time::delay_for(POLL_INTERVAL * 2).await;
+        let mut file = std::fs::File::create(&path).unwrap();
+        let first = encoded(&progress_event("first", false));
+        file.write_all(&first[0..3]).unwrap();
+        file.flush().unwrap();
+        tokio::time::delay_for(POLL_INTERVAL * 2).await;
+        file.write_all(&first[3..]).unwrap();
+        file.write_all(&encoded(&progress_event("last", true)))
+            .unwrap();
+        file.flush().unwrap();
+
+        tail_task.await.unwrap();
+
+        let mut events = 0;
+        loop {
+            match rx.recv().await.unwrap() {
+                BuildEventAction::BuildEvent(_) => events += 1,
+                BuildEventAction::BuildCompleted => break,
+                BuildEventAction::LifecycleEvent(_) => panic!("Unexpected lifecycle event"),
+            }
+        }
+        assert_eq!(events, 2);
+    }
+
+    #[tokio::test]
+    async fn test_tail_stops_when_bazel_finishes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build_events.bin");
+        std::fs::write(&path, encoded(&progress_event("only", false))).unwrap();
+
+        let (tx, mut rx) = broadcast::channel(16);
+        let (finished_tx, finished_rx) = oneshot::channel();
+        finished_tx.send(()).unwrap();
+        tail_build_event_file(path, tx, finished_rx).await;
+
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            BuildEventAction::BuildEvent(_)
+        ));
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            BuildEventAction::BuildCompleted
+        ));
+    }
+}
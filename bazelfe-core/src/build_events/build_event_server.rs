@@ -10,7 +10,9 @@ use google::devtools::build::v1::{
 };
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+use super::upstream::Upstream;
 
 pub mod bazel_event {
     use super::*;
@@ -36,211 +38,7 @@ pub mod bazel_event {
                 Some(inner) => match inner {
                     google::devtools::build::v1::build_event::Event::BazelEvent(e) => {
                         let v = build_event_stream::BuildEvent::decode(&*e.value).unwrap();
-
-                        let target_configured_evt: Option<TargetConfiguredEvt> = {
-                            let target_kind_opt = v.payload.as_ref().and_then(|e| match e {
-                                build_event_stream::build_event::Payload::Configured(cfg) => {
-                                    Some(cfg.target_kind.replace(" rule", ""))
-                                }
-                                _ => None,
-                            });
-                            let target_label_opt = v
-                                .id
-                                .as_ref()
-                                .and_then(|e| e.id.as_ref())
-                                .and_then(|e| match e {
-                                    build_event_stream::build_event_id::Id::TargetConfigured(
-                                        target_configured_id,
-                                    ) => Some(target_configured_id.label.clone()),
-                                    _ => None,
-                                });
-
-                            target_kind_opt.and_then(|e| {
-                                target_label_opt.map(|u| TargetConfiguredEvt {
-                                    rule_kind: e,
-                                    label: u,
-                                })
-                            })
-                        };
-
-                        let aborted: Option<Evt> = {
-                            let abort_info = v.payload.as_ref().and_then(|e| match e {
-                                build_event_stream::build_event::Payload::Aborted(cfg) => Some((
-                                    build_event_stream::aborted::AbortReason::from_i32(cfg.reason),
-                                    cfg.description.clone(),
-                                )),
-                                _ => None,
-                            });
-                            let target_label_opt =
-                                v.id.as_ref()
-                                    .and_then(|e| e.id.as_ref())
-                                    .and_then(|e| match e {
-                                        build_event_stream::build_event_id::Id::ConfiguredLabel(
-                                            configured_label_id,
-                                        ) => Some(configured_label_id.label.clone()),
-                                        _ => None,
-                                    });
-
-                            abort_info.map(|(reason, description)| {
-                                Evt::Aborted(AbortedEvt {
-                                    label: target_label_opt,
-                                    reason: reason,
-                                    description: description,
-                                })
-                            })
-                        };
-
-                        let progress_info: Option<Evt> = v.payload.as_ref().and_then(|e| match e {
-                            build_event_stream::build_event::Payload::Progress(cfg) => {
-                                if cfg.stdout.is_empty() && cfg.stderr.is_empty() {
-                                    None
-                                } else {
-                                    Some(Evt::Progress(ProgressEvt {
-                                        stdout: cfg.stdout.clone(),
-                                        stderr: cfg.stderr.clone(),
-                                    }))
-                                }
-                            }
-                            _ => None,
-                        });
-
-                        let action_info: Option<Evt> = {
-                            let target_label_opt =
-                                v.id.as_ref()
-                                    .and_then(|e| e.id.as_ref())
-                                    .and_then(|e| match e {
-                                        build_event_stream::build_event_id::Id::ActionCompleted(
-                                            action_completed_id,
-                                        ) => Some(action_completed_id.label.clone()),
-                                        _ => None,
-                                    });
-
-                            target_label_opt.and_then(|label| {
-                                v.payload.as_ref().and_then(|e| match e {
-                                    build_event_stream::build_event::Payload::Action(
-                                        action_executed,
-                                    ) => {
-                                        let stdout = action_executed
-                                            .stdout
-                                            .as_ref()
-                                            .and_then(|e| e.file.clone());
-                                        let stderr = action_executed
-                                            .stderr
-                                            .as_ref()
-                                            .and_then(|e| e.file.clone());
-
-                                        Some(Evt::ActionCompleted(ActionCompletedEvt {
-                                            success: action_executed.success,
-                                            label: label,
-                                            stdout: stdout,
-                                            stderr: stderr,
-                                        }))
-                                    }
-                                    _ => None,
-                                })
-                            })
-                        };
-
-                        let named_set_of_files: Option<Evt> = {
-                            let fileset_id =
-                                v.id.as_ref()
-                                    .and_then(|e| e.id.as_ref())
-                                    .and_then(|e| match e {
-                                        build_event_stream::build_event_id::Id::NamedSet(
-                                            fileset_id,
-                                        ) => Some(fileset_id.id.clone()),
-                                        _ => None,
-                                    });
-
-                            fileset_id.and_then(|id| {
-                                v.payload.as_ref().and_then(|e| match e {
-                                    build_event_stream::build_event::Payload::NamedSetOfFiles(
-                                        named_set_of_files,
-                                    ) => Some(Evt::NamedSetOfFiles {
-                                        id: id,
-                                        named_set_of_files: named_set_of_files.clone(),
-                                    }),
-                                    _ => None,
-                                })
-                            })
-                        };
-
-                        let target_complete: Option<Evt> = {
-                            let target_label_opt =
-                                v.id.as_ref()
-                                    .and_then(|e| e.id.as_ref())
-                                    .and_then(|e| match e {
-                                        build_event_stream::build_event_id::Id::TargetCompleted(
-                                            target_completed_id,
-                                        ) => Some(target_completed_id.label.clone()),
-                                        _ => None,
-                                    });
-
-                            target_label_opt.and_then(|label| {
-                                v.payload.as_ref().and_then(|e| match e {
-                                    build_event_stream::build_event::Payload::Completed(
-                                        target_completed,
-                                    ) => Some(Evt::TargetCompleted(TargetCompletedEvt {
-                                        success: target_completed.success,
-                                        label: label,
-                                        output_groups: target_completed.output_group.clone(),
-                                    })),
-                                    _ => None,
-                                })
-                            })
-                        };
-
-                        let test_outputs: Option<Evt> = {
-                            let failed_file_data: Option<Vec<build_event_stream::file::File>> =
-                                v.payload.as_ref().and_then(|e| match e {
-                                    build_event_stream::build_event::Payload::TestSummary(cfg) => {
-                                        Some(
-                                            cfg.failed
-                                                .iter()
-                                                .flat_map(|e| e.file.clone().into_iter())
-                                                .collect(),
-                                        )
-                                    }
-                                    _ => None,
-                                });
-
-                            let target_label_opt =
-                                v.id.as_ref()
-                                    .and_then(|e| e.id.as_ref())
-                                    .and_then(|e| match e {
-                                        build_event_stream::build_event_id::Id::TestSummary(
-                                            test_summary_id,
-                                        ) => Some(test_summary_id.label.clone()),
-                                        _ => None,
-                                    });
-
-                            failed_file_data.and_then(|failed_files| {
-                                target_label_opt.map(|u| {
-                                    Evt::TestFailure(TestFailureEvt {
-                                        label: u,
-                                        failed_files: failed_files,
-                                    })
-                                })
-                            })
-                        };
-
-                        if let Some(e) = target_configured_evt {
-                            Evt::TargetConfigured(e)
-                        } else if let Some(e) = action_info {
-                            e
-                        } else if let Some(e) = target_complete {
-                            e
-                        } else if let Some(e) = test_outputs {
-                            e
-                        } else if let Some(e) = named_set_of_files {
-                            e
-                        } else if let Some(e) = aborted {
-                            e
-                        } else if let Some(e) = progress_info {
-                            e
-                        } else {
-                            Evt::BazelEvent(v)
-                        }
+                        BazelBuildEvent::evt_from_build_event(v)
                     }
                     other => Evt::UnknownEvent(format!("{:?}", other)),
                 },
@@ -250,11 +48,218 @@ pub mod bazel_event {
             info!("Decoded evt: {:?}", decoded_evt);
             Some(BazelBuildEvent { event: decoded_evt })
         }
+
+        // For events read directly from bazel, rather than published to us over grpc
+        pub fn from_build_event(v: build_event_stream::BuildEvent) -> BazelBuildEvent {
+            let decoded_evt = BazelBuildEvent::evt_from_build_event(v);
+            info!("Decoded evt: {:?}", decoded_evt);
+            BazelBuildEvent { event: decoded_evt }
+        }
+
+        fn evt_from_build_event(v: build_event_stream::BuildEvent) -> Evt {
+            let target_configured_evt: Option<TargetConfiguredEvt> = {
+                let target_kind_opt = v.payload.as_ref().and_then(|e| match e {
+                    build_event_stream::build_event::Payload::Configured(cfg) => {
+                        Some(cfg.target_kind.replace(" rule", ""))
+                    }
+                    _ => None,
+                });
+                let target_label_opt =
+                    v.id.as_ref()
+                        .and_then(|e| e.id.as_ref())
+                        .and_then(|e| match e {
+                            build_event_stream::build_event_id::Id::TargetConfigured(
+                                target_configured_id,
+                            ) => Some(target_configured_id.label.clone()),
+                            _ => None,
+                        });
+
+                target_kind_opt.and_then(|e| {
+                    target_label_opt.map(|u| TargetConfiguredEvt {
+                        rule_kind: e,
+                        label: u,
+                    })
+                })
+            };
+
+            let aborted: Option<Evt> = {
+                let abort_info = v.payload.as_ref().and_then(|e| match e {
+                    build_event_stream::build_event::Payload::Aborted(cfg) => Some((
+                        build_event_stream::aborted::AbortReason::from_i32(cfg.reason),
+                        cfg.description.clone(),
+                    )),
+                    _ => None,
+                });
+                let target_label_opt =
+                    v.id.as_ref()
+                        .and_then(|e| e.id.as_ref())
+                        .and_then(|e| match e {
+                            build_event_stream::build_event_id::Id::ConfiguredLabel(
+                                configured_label_id,
+                            ) => Some(configured_label_id.label.clone()),
+                            _ => None,
+                        });
+
+                abort_info.map(|(reason, description)| {
+                    Evt::Aborted(AbortedEvt {
+                        label: target_label_opt,
+                        reason: reason,
+                        description: description,
+                    })
+                })
+            };
+
+            let progress_info: Option<Evt> = v.payload.as_ref().and_then(|e| match e {
+                build_event_stream::build_event::Payload::Progress(cfg) => {
+                    if cfg.stdout.is_empty() && cfg.stderr.is_empty() {
+                        None
+                    } else {
+                        Some(Evt::Progress(ProgressEvt {
+                            stdout: cfg.stdout.clone(),
+                            stderr: cfg.stderr.clone(),
+                        }))
+                    }
+                }
+                _ => None,
+            });
+
+            let action_info: Option<Evt> = {
+                let target_label_opt =
+                    v.id.as_ref()
+                        .and_then(|e| e.id.as_ref())
+                        .and_then(|e| match e {
+                            build_event_stream::build_event_id::Id::ActionCompleted(
+                                action_completed_id,
+                            ) => Some(action_completed_id.label.clone()),
+                            _ => None,
+                        });
+
+                target_label_opt.and_then(|label| {
+                    v.payload.as_ref().and_then(|e| match e {
+                        build_event_stream::build_event::Payload::Action(action_executed) => {
+                            let stdout =
+                                action_executed.stdout.as_ref().and_then(|e| e.file.clone());
+                            let stderr =
+                                action_executed.stderr.as_ref().and_then(|e| e.file.clone());
+
+                            Some(Evt::ActionCompleted(ActionCompletedEvt {
+                                success: action_executed.success,
+                                label: label,
+                                mnemonic: action_executed.r#type.clone(),
+                                stdout: stdout,
+                                stderr: stderr,
+                            }))
+                        }
+                        _ => None,
+                    })
+                })
+            };
+
+            let named_set_of_files: Option<Evt> = {
+                let fileset_id =
+                    v.id.as_ref()
+                        .and_then(|e| e.id.as_ref())
+                        .and_then(|e| match e {
+                            build_event_stream::build_event_id::Id::NamedSet(fileset_id) => {
+                                Some(fileset_id.id.clone())
+                            }
+                            _ => None,
+                        });
+
+                fileset_id.and_then(|id| {
+                    v.payload.as_ref().and_then(|e| match e {
+                        build_event_stream::build_event::Payload::NamedSetOfFiles(
+                            named_set_of_files,
+                        ) => Some(Evt::NamedSetOfFiles {
+                            id: id,
+                            named_set_of_files: named_set_of_files.clone(),
+                        }),
+                        _ => None,
+                    })
+                })
+            };
+
+            let target_complete: Option<Evt> = {
+                let target_label_opt =
+                    v.id.as_ref()
+                        .and_then(|e| e.id.as_ref())
+                        .and_then(|e| match e {
+                            build_event_stream::build_event_id::Id::TargetCompleted(
+                                target_completed_id,
+                            ) => Some(target_completed_id.label.clone()),
+                            _ => None,
+                        });
+
+                target_label_opt.and_then(|label| {
+                    v.payload.as_ref().and_then(|e| match e {
+                        build_event_stream::build_event::Payload::Completed(target_completed) => {
+                            Some(Evt::TargetCompleted(TargetCompletedEvt {
+                                success: target_completed.success,
+                                label: label,
+                                output_groups: target_completed.output_group.clone(),
+                            }))
+                        }
+                        _ => None,
+                    })
+                })
+            };
+
+            let test_outputs: Option<Evt> = {
+                let failed_file_data: Option<Vec<build_event_stream::file::File>> =
+                    v.payload.as_ref().and_then(|e| match e {
+                        build_event_stream::build_event::Payload::TestSummary(cfg) => Some(
+                            cfg.failed
+                                .iter()
+                                .flat_map(|e| e.file.clone().into_iter())
+                                .collect(),
+                        ),
+                        _ => None,
+                    });
+
+                let target_label_opt =
+                    v.id.as_ref()
+                        .and_then(|e| e.id.as_ref())
+                        .and_then(|e| match e {
+                            build_event_stream::build_event_id::Id::TestSummary(
+                                test_summary_id,
+                            ) => Some(test_summary_id.label.clone()),
+                            _ => None,
+                        });
+
+                failed_file_data.and_then(|failed_files| {
+                    target_label_opt.map(|u| {
+                        Evt::TestFailure(TestFailureEvt {
+                            label: u,
+                            failed_files: failed_files,
+                        })
+                    })
+                })
+            };
+
+            if let Some(e) = target_configured_evt {
+                Evt::TargetConfigured(e)
+            } else if let Some(e) = action_info {
+                e
+            } else if let Some(e) = target_complete {
+                e
+            } else if let Some(e) = test_outputs {
+                e
+            } else if let Some(e) = named_set_of_files {
+                e
+            } else if let Some(e) = aborted {
+                e
+            } else if let Some(e) = progress_info {
+                e
+            } else {
+                Evt::BazelEvent(v)
+            }
+        }
     }
     #[derive(Clone, PartialEq, Debug)]
     pub struct ActionCompletedEvt {
         pub success: bool,
         pub label: String,
+        pub mnemonic: String,
         pub stdout: Option<build_event_stream::file::File>,
         pub stderr: Option<build_event_stream::file::File>,
     }
@@ -320,6 +325,8 @@ where
     pub write_channel: Arc<Mutex<Option<broadcast::Sender<BuildEventAction<T>>>>>,
     pub transform_fn:
         Arc<dyn Fn(&mut PublishBuildToolEventStreamRequest) -> Option<T> + Send + Sync>,
+    // When set we act as a proxy, passing everything we're sent on to here
+    pub upstream: Option<Upstream>,
 }
 
 fn transform_queue_error_to_status() -> Status {
@@ -336,6 +343,7 @@ pub fn build_bazel_build_events_service() -> (
     let server_instance = BuildEventService {
         write_channel: Arc::clone(&write_channel_arc),
         transform_fn: Arc::new(bazel_event::BazelBuildEvent::transform_from),
+        upstream: None,
     };
     (server_instance, write_channel_arc, rx)
 }
@@ -358,8 +366,18 @@ where
         &self,
         request: Request<tonic::Streaming<PublishBuildToolEventStreamRequest>>,
     ) -> Result<Response<Self::PublishBuildToolEventStreamStream>, Status> {
+        let metadata = request.metadata().clone();
         let mut stream = request.into_inner();
 
+        let (mut forward_tx, forward_task) = match self.upstream.clone() {
+            Some(upstream) => {
+                let (tx, rx) = mpsc::channel(256);
+                let task = tokio::spawn(async move { upstream.forward_stream(metadata, rx).await });
+                (Some(tx), Some(task))
+            }
+            None => (None, None),
+        };
+
         let sender_ref = {
             let e = Arc::clone(&self.write_channel);
             let m = e.lock().await;
@@ -383,6 +401,17 @@ where
             }
                     None => ()
                 };
+
+                // Waiting on the forwarder here is what pushes back on bazel when the
+                // upstream is slow
+                let forwarded = match forward_tx.as_mut() {
+                    Some(tx) => tx.send(inbound_evt.clone()).await.is_ok(),
+                    None => true,
+                };
+                if !forwarded {
+                    forward_tx = None;
+                }
+
                 let transformed_data = (transform_fn)(&mut inbound_evt);
 
                 if let Some(r) = transformed_data {
@@ -401,8 +430,23 @@ where
             }
 
 
-            if let Some(tx) = second_writer {
-                tx.send(BuildEventAction::BuildCompleted).map_err(|_| transform_queue_error_to_status())?;
+            drop(forward_tx);
+            let completed = match second_writer {
+                Some(tx) => tx.send(BuildEventAction::BuildCompleted).is_ok(),
+                None => true,
+            };
+
+            // Bazel waits for us to close the stream before exiting, so don't until the
+            // upstream has everything
+            if let Some(task) = forward_task {
+                match task.await {
+                    Ok(Ok(())) => (),
+                    Ok(Err(e)) => error!("Failed to forward build events upstream: {}", e),
+                    Err(e) => error!("Build event forwarding stopped: {}", e),
+                }
+            }
+            if !completed {
+                Err(transform_queue_error_to_status())?;
             }
             info!("Finished stream...");
         };
@@ -422,8 +466,18 @@ where
             (*m).clone()
         };
 
+        let metadata = request.metadata().clone();
+        let inner = request.into_inner();
+        if let Some(upstream) = self.upstream.as_ref() {
+            if let Err(e) = upstream
+                .forward_lifecycle_event(&metadata, inner.clone())
+                .await
+            {
+                error!("Failed to forward lifecycle event upstream: {}", e);
+            }
+        }
+
         if let Some(tx) = cloned_v {
-            let inner = request.into_inner();
             info!("life cycle event: {:?}", inner);
 
             tx.send(BuildEventAction::LifecycleEvent(inner))
@@ -47,6 +47,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let greeter = BuildEventService {
         write_channel: Arc::new(Mutex::new(Some(tx))),
         transform_fn: std::sync::Arc::new(transform_fn),
+        upstream: None,
     };
 
     tokio::spawn(async move {
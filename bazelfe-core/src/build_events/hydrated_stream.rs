@@ -10,6 +10,7 @@ use std::collections::HashMap;
 
 use super::build_event_server::bazel_event;
 use super::build_event_server::BuildEventAction;
+use crate::error_extraction;
 use bazelfe_protos::*;
 
 use tokio::sync::broadcast;
@@ -21,6 +22,7 @@ use tokio::sync::mpsc;
 #[derive(Clone, PartialEq, Debug)]
 pub struct ActionFailedErrorInfo {
     pub label: String,
+    pub mnemonic: Option<String>,
     pub output_files: Vec<build_event_stream::file::File>,
     pub target_kind: Option<String>,
 }
@@ -35,6 +37,7 @@ pub struct BazelAbortErrorInfo {
 #[derive(Clone, PartialEq, Debug)]
 pub struct ActionSuccessInfo {
     pub label: String,
+    pub mnemonic: Option<String>,
     pub stdout: Option<build_event_stream::file::File>,
     pub stderr: Option<build_event_stream::file::File>,
     pub target_kind: Option<String>,
@@ -46,8 +49,13 @@ pub struct TargetCompleteInfo {
     pub success: bool,
     pub target_kind: Option<String>,
     pub output_files: Vec<build_event_stream::file::File>,
+    // The outputs of HIDDEN_OUTPUT_GROUP, which is where the java rules put their .jdeps
+    pub hidden_output_files: Vec<build_event_stream::file::File>,
 }
 
+// Built for every top level target, though bazel only reports it when it's requested
+pub const HIDDEN_OUTPUT_GROUP: &str = "_hidden_top_level_INTERNAL_";
+
 // Broad strokes of the failure occured inside an action (most common)
 // or at a bazel abort, things like mis-configured build files
 #[derive(Clone, PartialEq, Debug)]
@@ -81,22 +89,22 @@ fn recursive_lookup(
     true
 }
 
-fn tce_event(
-    tce: bazel_event::TargetCompletedEvt,
-    rule_kind_lookup: &HashMap<String, String>,
+// Whether every file set of the output group has been seen yet, if the target has one
+fn output_group_lookup(
+    tce: &bazel_event::TargetCompletedEvt,
+    group_name: &str,
     named_set_of_files_lookup: &HashMap<String, build_event_stream::NamedSetOfFiles>,
-    to_revisit: &mut Vec<bazel_event::TargetCompletedEvt>,
-) -> Option<TargetCompleteInfo> {
-    let mut output_files = Vec::default();
-    let found_everything = if let Some(output_grp) = &tce
+    results: &mut Vec<build_event_stream::file::File>,
+) -> bool {
+    if let Some(output_grp) = &tce
         .output_groups
         .iter()
-        .filter(|grp| grp.name == "default")
+        .filter(|grp| grp.name == group_name)
         .next()
     {
         recursive_lookup(
             &named_set_of_files_lookup,
-            &mut output_files,
+            results,
             output_grp
                 .file_sets
                 .iter()
@@ -105,11 +113,30 @@ fn tce_event(
         )
     } else {
         true
-    };
+    }
+}
+
+fn tce_event(
+    tce: bazel_event::TargetCompletedEvt,
+    rule_kind_lookup: &HashMap<String, String>,
+    named_set_of_files_lookup: &HashMap<String, build_event_stream::NamedSetOfFiles>,
+    to_revisit: &mut Vec<bazel_event::TargetCompletedEvt>,
+) -> Option<TargetCompleteInfo> {
+    let mut output_files = Vec::default();
+    let mut hidden_output_files = Vec::default();
+    let found_everything =
+        output_group_lookup(&tce, "default", named_set_of_files_lookup, &mut output_files)
+            && output_group_lookup(
+                &tce,
+                HIDDEN_OUTPUT_GROUP,
+                named_set_of_files_lookup,
+                &mut hidden_output_files,
+            );
 
     if found_everything {
         let target_complete_info = TargetCompleteInfo {
             output_files: output_files,
+            hidden_output_files,
             target_kind: rule_kind_lookup.get(&tce.label).map(|e| e.clone()),
             label: tce.label,
             success: tce.success,
@@ -182,6 +209,8 @@ impl HydratedInfo {
                         }
 
                         bazel_event::Evt::ActionCompleted(ace) => {
+                            // Older bazels don't say what kind of action it was
+                            let mnemonic = Some(ace.mnemonic).filter(|e| !e.is_empty());
                             if !ace.success {
                                 let err_info = ActionFailedErrorInfo {
                                     output_files: ace
@@ -193,6 +222,7 @@ impl HydratedInfo {
                                         .get(&ace.label)
                                         .map(|e| e.clone()),
                                     label: ace.label,
+                                    mnemonic,
                                 };
                                 tx.send(Some(HydratedInfo::ActionFailed(err_info)))
                                     .await
@@ -206,6 +236,7 @@ impl HydratedInfo {
                                         .get(&ace.label)
                                         .map(|e| e.clone()),
                                     label: ace.label,
+                                    mnemonic,
                                 };
                                 tx.send(Some(HydratedInfo::ActionSuccess(act_info)))
                                     .await
@@ -218,12 +249,30 @@ impl HydratedInfo {
                                 output_files: tfe.failed_files,
                                 target_kind: rule_kind_lookup.get(&tfe.label).map(|e| e.clone()),
                                 label: tfe.label,
+                                mnemonic: None,
                             };
                             tx.send(Some(HydratedInfo::ActionFailed(err_info)))
                                 .await
                                 .unwrap();
                         }
                         bazel_event::Evt::Progress(progress) => {
+                            // Bazel finds headers missing from a C++ rule's deps itself, so
+                            // they aren't in the output of the compile action
+                            for (label, output) in
+                                error_extraction::cpp::split_undeclared_inclusions(&progress.stderr)
+                            {
+                                let err_info = ActionFailedErrorInfo {
+                                    output_files: vec![build_event_stream::file::File::Contents(
+                                        output.into_bytes(),
+                                    )],
+                                    target_kind: rule_kind_lookup.get(&label).cloned(),
+                                    label,
+                                    mnemonic: Some(String::from("CppCompile")),
+                                };
+                                tx.send(Some(HydratedInfo::ActionFailed(err_info)))
+                                    .await
+                                    .unwrap();
+                            }
                             tx.send(Some(HydratedInfo::Progress(progress)))
                                 .await
                                 .unwrap();
@@ -262,6 +311,7 @@ mod tests {
                 stdout: None,
                 stderr: None,
                 label: String::from("foo_bar_baz"),
+                mnemonic: String::new(),
                 success: false,
             }),
         }))
@@ -274,6 +324,7 @@ mod tests {
             Some(HydratedInfo::ActionFailed(ActionFailedErrorInfo {
                 target_kind: None,
                 label: String::from("foo_bar_baz"),
+                mnemonic: None,
                 output_files: vec![]
             }))
         );
@@ -293,6 +344,7 @@ mod tests {
                     "path-to-stderr",
                 ))),
                 label: String::from("foo_bar_baz"),
+                mnemonic: String::from("Scalac"),
                 success: false,
             }),
         }))
@@ -305,6 +357,7 @@ mod tests {
             Some(HydratedInfo::ActionFailed(ActionFailedErrorInfo {
                 target_kind: None,
                 label: String::from("foo_bar_baz"),
+                mnemonic: Some(String::from("Scalac")),
                 output_files: vec![
                     build_event_stream::file::File::Uri(String::from("path-to-stdout",)),
                     build_event_stream::file::File::Uri(String::from("path-to-stderr",))
@@ -335,6 +388,7 @@ mod tests {
                     "path-to-stderr",
                 ))),
                 label: String::from("foo_bar_baz"),
+                mnemonic: String::from("Scalac"),
                 success: false,
             }),
         }))
@@ -347,6 +401,7 @@ mod tests {
             Some(HydratedInfo::ActionFailed(ActionFailedErrorInfo {
                 target_kind: Some(String::from("my_madeup_rule")),
                 label: String::from("foo_bar_baz"),
+                mnemonic: Some(String::from("Scalac")),
                 output_files: vec![
                     build_event_stream::file::File::Uri(String::from("path-to-stdout",)),
                     build_event_stream::file::File::Uri(String::from("path-to-stderr",))
@@ -379,6 +434,7 @@ mod tests {
                     "path-to-stderr",
                 ))),
                 label: String::from("foo_bar_baz"),
+                mnemonic: String::from("Scalac"),
                 success: false,
             }),
         }))
@@ -396,6 +452,7 @@ mod tests {
             Some(HydratedInfo::ActionFailed(ActionFailedErrorInfo {
                 target_kind: None,
                 label: String::from("foo_bar_baz"),
+                mnemonic: Some(String::from("Scalac")),
                 output_files: vec![
                     build_event_stream::file::File::Uri(String::from("path-to-stdout",)),
                     build_event_stream::file::File::Uri(String::from("path-to-stderr",))
@@ -403,4 +460,107 @@ mod tests {
             }))
         );
     }
+
+    #[tokio::test]
+    async fn test_undeclared_inclusions() {
+        let (tx, rx) = broadcast::channel(128);
+        let mut child_rx = HydratedInfo::build_transformer(rx);
+
+        tx.send(BuildEventAction::BuildEvent(bazel_event::BazelBuildEvent {
+            event: bazel_event::Evt::TargetConfigured(bazel_event::TargetConfiguredEvt {
+                label: String::from("//foo:foo"),
+                rule_kind: String::from("cc_library"),
+            }),
+        }))
+        .unwrap();
+
+        let undeclared_inclusion = "ERROR: /home/user/repo/foo/BUILD:1:11: undeclared inclusion(s) in rule '//foo:foo':
+this rule is missing dependency declarations for the following files included by 'foo/foo.cc':
+  'bar/bar.h'";
+        tx.send(BuildEventAction::BuildEvent(bazel_event::BazelBuildEvent {
+            event: bazel_event::Evt::Progress(bazel_event::ProgressEvt {
+                stdout: String::from(""),
+                stderr: format!("{}\nTarget //foo:foo failed to build", undeclared_inclusion),
+            }),
+        }))
+        .unwrap();
+
+        let received_res = child_rx.next().await.unwrap();
+
+        assert_eq!(
+            received_res,
+            Some(HydratedInfo::ActionFailed(ActionFailedErrorInfo {
+                target_kind: Some(String::from("cc_library")),
+                label: String::from("//foo:foo"),
+                mnemonic: Some(String::from("CppCompile")),
+                output_files: vec![build_event_stream::file::File::Contents(
+                    undeclared_inclusion.as_bytes().to_vec()
+                )]
+            }))
+        );
+
+        let received_res = child_rx.next().await.unwrap();
+        assert!(matches!(received_res, Some(HydratedInfo::Progress(_))));
+    }
+
+    #[tokio::test]
+    async fn test_target_complete_output_groups() {
+        let (tx, rx) = broadcast::channel(128);
+        let mut child_rx = HydratedInfo::build_transformer(rx);
+
+        let output_group = |name: &str, id: &str| build_event_stream::OutputGroup {
+            name: String::from(name),
+            file_sets: vec![build_event_stream::build_event_id::NamedSetOfFilesId {
+                id: String::from(id),
+            }],
+        };
+        let named_set = |id: &str, uri: &str| bazel_event::Evt::NamedSetOfFiles {
+            id: String::from(id),
+            named_set_of_files: build_event_stream::NamedSetOfFiles {
+                files: vec![build_event_stream::File {
+                    file: Some(build_event_stream::file::File::Uri(String::from(uri))),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        };
+
+        tx.send(BuildEventAction::BuildEvent(bazel_event::BazelBuildEvent {
+            event: named_set("0", "file:///bin/foo/libfoo.jar"),
+        }))
+        .unwrap();
+        tx.send(BuildEventAction::BuildEvent(bazel_event::BazelBuildEvent {
+            event: bazel_event::Evt::TargetCompleted(bazel_event::TargetCompletedEvt {
+                label: String::from("//foo:foo"),
+                success: true,
+                output_groups: vec![
+                    output_group("default", "0"),
+                    output_group(HIDDEN_OUTPUT_GROUP, "1"),
+                ],
+            }),
+        }))
+        .unwrap();
+        // Held back until every file set it refers to has been seen
+        tx.send(BuildEventAction::BuildEvent(bazel_event::BazelBuildEvent {
+            event: named_set("1", "file:///bin/foo/libfoo.jdeps"),
+        }))
+        .unwrap();
+
+        let received_res = child_rx.next().await.unwrap();
+
+        assert_eq!(
+            received_res,
+            Some(HydratedInfo::TargetComplete(TargetCompleteInfo {
+                label: String::from("//foo:foo"),
+                success: true,
+                target_kind: None,
+                output_files: vec![build_event_stream::file::File::Uri(String::from(
+                    "file:///bin/foo/libfoo.jar"
+                ))],
+                hidden_output_files: vec![build_event_stream::file::File::Uri(String::from(
+                    "file:///bin/foo/libfoo.jdeps"
+                ))],
+            }))
+        );
+    }
 }
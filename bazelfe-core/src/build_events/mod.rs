@@ -1,2 +1,5 @@
+pub mod build_event_file;
 pub mod build_event_server;
 pub mod hydrated_stream;
+pub mod recording;
+pub mod upstream;
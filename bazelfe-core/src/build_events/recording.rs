@@ -0,0 +1,226 @@
+// Recordings of the requests bazel publishes to us, stored length delimited one after
+// another, so a build can be replayed later to see which corrections we'd make for it.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use ::prost::Message;
+use bazelfe_protos::build_event_stream;
+use bazelfe_protos::google::devtools::build::v1::{
+    build_event, PublishBuildToolEventStreamRequest,
+};
+use tokio::sync::broadcast;
+
+use super::build_event_server::{bazel_event, BuildEventAction, BuildEventService};
+
+#[derive(Clone, Debug)]
+pub struct BuildEventRecorder {
+    file: Arc<Mutex<std::fs::File>>,
+}
+
+impl BuildEventRecorder {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            file: Arc::new(Mutex::new(std::fs::File::create(path)?)),
+        })
+    }
+
+    pub fn record(&self, request: &PublishBuildToolEventStreamRequest) -> std::io::Result<()> {
+        let inlined = inline_failed_action_output(request);
+        let mut buf = vec![];
+        // The vec grows as needed, so encoding into it cannot fail
+        inlined
+            .as_ref()
+            .unwrap_or(request)
+            .encode_length_delimited(&mut buf)
+            .unwrap();
+        self.file.lock().unwrap().write_all(&buf)
+    }
+}
+
+// Failed actions refer to their output by a path which won't exist wherever the recording
+// is replayed, so their output is copied into the event instead.
+fn inline_failed_action_output(
+    request: &PublishBuildToolEventStreamRequest,
+) -> Option<PublishBuildToolEventStreamRequest> {
+    let bazel_event = request
+        .ordered_build_event
+        .as_ref()
+        .and_then(|e| e.event.as_ref())
+        .and_then(|e| e.event.as_ref())?;
+    let mut evt = match bazel_event {
+        build_event::Event::BazelEvent(any) => {
+            build_event_stream::BuildEvent::decode(&*any.value).ok()?
+        }
+        _ => return None,
+    };
+    match evt.payload.as_mut() {
+        Some(build_event_stream::build_event::Payload::Action(action)) if !action.success => {
+            for output in vec![&mut action.stdout, &mut action.stderr]
+                .into_iter()
+                .flatten()
+            {
+                let contents = match &output.file {
+                    Some(build_event_stream::file::File::Uri(uri)) => uri
+                        .strip_prefix("file://")
+                        .and_then(|path| std::fs::read(path).ok()),
+                    _ => None,
+                };
+                if let Some(contents) = contents {
+                    output.file = Some(build_event_stream::file::File::Contents(contents));
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    let mut inlined = request.clone();
+    if let Some(build_event::Event::BazelEvent(any)) = inlined
+        .ordered_build_event
+        .as_mut()
+        .and_then(|e| e.event.as_mut())
+        .and_then(|e| e.event.as_mut())
+    {
+        any.value.clear();
+        evt.encode(&mut any.value).unwrap();
+    }
+    Some(inlined)
+}
+
+pub fn read_recording(
+    path: &Path,
+) -> Result<Vec<PublishBuildToolEventStreamRequest>, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let mut buf: &[u8] = &data;
+    let mut requests = vec![];
+    while !buf.is_empty() {
+        requests.push(PublishBuildToolEventStreamRequest::decode_length_delimited(
+            &mut buf,
+        )?);
+    }
+    Ok(requests)
+}
+
+type BazelEventSender = Arc<
+    tokio::sync::Mutex<Option<broadcast::Sender<BuildEventAction<bazel_event::BazelBuildEvent>>>>,
+>;
+
+// The same as `build_bazel_build_events_service`, but recording every request it's sent
+pub fn build_recording_build_events_service(
+    recorder: BuildEventRecorder,
+) -> (
+    BuildEventService<bazel_event::BazelBuildEvent>,
+    BazelEventSender,
+    broadcast::Receiver<BuildEventAction<bazel_event::BazelBuildEvent>>,
+) {
+    let (tx, rx) = broadcast::channel(256);
+    let write_channel_arc = Arc::new(tokio::sync::Mutex::new(Some(tx)));
+    let transform_fn = move |request: &mut PublishBuildToolEventStreamRequest| {
+        if let Err(e) = recorder.record(request) {
+            warn!("Failed to record build event: {:?}", e);
+        }
+        bazel_event::BazelBuildEvent::transform_from(request)
+    };
+    let server_instance = BuildEventService {
+        write_channel: Arc::clone(&write_channel_arc),
+        transform_fn: Arc::new(transform_fn),
+        upstream: None,
+    };
+    (server_instance, write_channel_arc, rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bazelfe_protos::google::devtools::build::v1::OrderedBuildEvent;
+
+    #[test]
+    fn test_record_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.proto");
+
+        let requests: Vec<PublishBuildToolEventStreamRequest> = (0..3)
+            .map(|sequence_number| PublishBuildToolEventStreamRequest {
+                ordered_build_event: Some(OrderedBuildEvent {
+                    sequence_number,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect();
+
+        let recorder = BuildEventRecorder::create(&path).unwrap();
+        for request in requests.iter() {
+            recorder.record(request).unwrap();
+        }
+        drop(recorder);
+
+        assert_eq!(read_recording(&path).unwrap(), requests);
+    }
+
+    #[test]
+    fn test_failed_action_output_is_inlined() {
+        let dir = tempfile::tempdir().unwrap();
+        let stderr_path = dir.path().join("stderr");
+        std::fs::write(
+            &stderr_path,
+            "error: object foo is not a member of package bar",
+        )
+        .unwrap();
+
+        let action = build_event_stream::BuildEvent {
+            payload: Some(build_event_stream::build_event::Payload::Action(
+                build_event_stream::ActionExecuted {
+                    success: false,
+                    stderr: Some(build_event_stream::File {
+                        file: Some(build_event_stream::file::File::Uri(format!(
+                            "file://{}",
+                            stderr_path.to_str().unwrap()
+                        ))),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        };
+        let mut value = vec![];
+        action.encode(&mut value).unwrap();
+        let request = PublishBuildToolEventStreamRequest {
+            ordered_build_event: Some(OrderedBuildEvent {
+                event: Some(bazelfe_protos::google::devtools::build::v1::BuildEvent {
+                    event: Some(build_event::Event::BazelEvent(prost_types::Any {
+                        type_url: String::from("type.googleapis.com/build_event_stream.BuildEvent"),
+                        value,
+                    })),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let inlined = inline_failed_action_output(&request).unwrap();
+        let evt = match inlined
+            .ordered_build_event
+            .and_then(|e| e.event)
+            .and_then(|e| e.event)
+        {
+            Some(build_event::Event::BazelEvent(any)) => {
+                build_event_stream::BuildEvent::decode(&*any.value).unwrap()
+            }
+            _ => panic!("Expected a bazel event"),
+        };
+        match evt.payload {
+            Some(build_event_stream::build_event::Payload::Action(action)) => assert_eq!(
+                action.stderr.and_then(|f| f.file),
+                Some(build_event_stream::file::File::Contents(
+                    b"error: object foo is not a member of package bar".to_vec()
+                ))
+            ),
+            _ => panic!("Expected an action event"),
+        }
+    }
+}
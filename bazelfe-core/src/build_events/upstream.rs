@@ -0,0 +1,360 @@
+// Forwards what bazel publishes to us on to another build event service, so we can sit
+// between bazel and a backend the user already has, analysing the events as they pass.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::time::Duration;
+
+use bazelfe_protos::google::devtools::build::v1::publish_build_event_client::PublishBuildEventClient;
+use bazelfe_protos::google::devtools::build::v1::{
+    PublishBuildToolEventStreamRequest, PublishLifecycleEventRequest,
+};
+use tokio::sync::mpsc;
+use tonic::metadata::MetadataMap;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+#[derive(Clone, Debug)]
+pub struct Upstream {
+    client: PublishBuildEventClient<Channel>,
+    // Bazel's stream is only read while fewer than this many requests are waiting on the
+    // upstream to acknowledge them, so a slow upstream slows bazel rather than growing our
+    // buffers.
+    max_in_flight: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+// Bazel names backends grpc://host:port, where we need the http uri
+fn upstream_uri(backend: &str) -> Result<String, Box<dyn Error>> {
+    if let Some(rest) = backend.strip_prefix("grpc://") {
+        Ok(format!("http://{}", rest))
+    } else if backend.starts_with("http://") {
+        Ok(backend.to_string())
+    } else {
+        Err(format!(
+            "Only plaintext grpc:// upstreams are supported, got {:?}",
+            backend
+        )
+        .into())
+    }
+}
+
+fn sequence_number(request: &PublishBuildToolEventStreamRequest) -> i64 {
+    request
+        .ordered_build_event
+        .as_ref()
+        .map(|e| e.sequence_number)
+        .unwrap_or(0)
+}
+
+fn with_metadata<T>(metadata: &MetadataMap, message: T) -> Request<T> {
+    // Headers bazel sent us, such as credentials for the upstream. Those describing the
+    // connection itself are dropped by tonic when the request is sent.
+    let mut request = Request::new(message);
+    *request.metadata_mut() = metadata.clone();
+    request
+}
+
+impl Upstream {
+    // Connects on first use, so an unreachable upstream is retried like any other failure
+    pub fn new(backend: &str) -> Result<Upstream, Box<dyn Error>> {
+        let channel = Endpoint::from_shared(upstream_uri(backend)?)?.connect_lazy()?;
+        Ok(Upstream::from_channel(channel))
+    }
+
+    fn from_channel(channel: Channel) -> Upstream {
+        Upstream {
+            client: PublishBuildEventClient::new(channel),
+            max_in_flight: 256,
+            max_retries: 5,
+            retry_delay: Duration::from_millis(100),
+        }
+    }
+
+    async fn backoff(&self, attempt: u32) {
+        tokio::time::delay_for(self.retry_delay * 2u32.pow(attempt.min(6))).await;
+    }
+
+    pub async fn forward_lifecycle_event(
+        &self,
+        metadata: &MetadataMap,
+        request: PublishLifecycleEventRequest,
+    ) -> Result<(), Status> {
+        let mut attempt = 0;
+        loop {
+            let mut client = self.client.clone();
+            match client
+                .publish_lifecycle_event(with_metadata(metadata, request.clone()))
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.max_retries => {
+                    warn!("Forwarding lifecycle event failed, retrying: {:?}", e);
+                    self.backoff(attempt).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Forwards requests from `inbound` until it closes and the upstream has acknowledged
+    // them all. When the stream to the upstream fails, a new one is opened and everything
+    // not yet acknowledged is sent again.
+    pub async fn forward_stream(
+        &self,
+        metadata: MetadataMap,
+        mut inbound: mpsc::Receiver<PublishBuildToolEventStreamRequest>,
+    ) -> Result<(), Status> {
+        let mut pending: VecDeque<PublishBuildToolEventStreamRequest> = VecDeque::default();
+        let mut inbound_open = true;
+        let mut attempt = 0;
+        loop {
+            let (tx, rx) = mpsc::unbounded_channel();
+            for request in pending.iter() {
+                let _ = tx.send(request.clone());
+            }
+            // Dropping the sender ends the stream to the upstream
+            let mut outbound = if inbound_open { Some(tx) } else { None };
+
+            let mut client = self.client.clone();
+            let result = match client
+                .publish_build_tool_event_stream(with_metadata(&metadata, rx))
+                .await
+            {
+                Ok(response) => {
+                    let mut acks = response.into_inner();
+                    loop {
+                        let accepting = inbound_open && pending.len() < self.max_in_flight;
+                        tokio::select! {
+                            request = inbound.recv(), if accepting => {
+                                match request {
+                                    Some(request) => {
+                                        if let Some(tx) = outbound.as_ref() {
+                                            let _ = tx.send(request.clone());
+                                        }
+                                        pending.push_back(request);
+                                    }
+                                    None => {
+                                        inbound_open = false;
+                                        outbound = None;
+                                    }
+                                }
+                            }
+                            ack = acks.message() => {
+                                match ack {
+                                    Ok(Some(ack)) => {
+                                        attempt = 0;
+                                        while pending
+                                            .front()
+                                            .map(|r| sequence_number(r) <= ack.sequence_number)
+                                            .unwrap_or(false)
+                                        {
+                                            pending.pop_front();
+                                        }
+                                    }
+                                    Ok(None) if !inbound_open && pending.is_empty() => break Ok(()),
+                                    Ok(None) => {
+                                        break Err(Status::unavailable(
+                                            "The upstream closed the stream early",
+                                        ))
+                                    }
+                                    Err(e) => break Err(e),
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.max_retries => {
+                    warn!(
+                        "Forwarding build events failed, resending {} unacknowledged: {:?}",
+                        pending.len(),
+                        e
+                    );
+                    self.backoff(attempt).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bazelfe_protos::google::devtools::build::v1::publish_build_event_server::{
+        PublishBuildEvent, PublishBuildEventServer,
+    };
+    use bazelfe_protos::google::devtools::build::v1::{
+        OrderedBuildEvent, PublishBuildToolEventStreamResponse,
+    };
+    use futures::{Stream, StreamExt, TryStreamExt};
+    use std::convert::TryFrom;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tonic::transport::{Server, Uri};
+    use tonic::Response;
+    use tower::service_fn;
+
+    // Stands in for a real backend, recording what it's sent. It can be made to fail the
+    // first stream part way through, to check we resend.
+    #[derive(Clone, Default)]
+    struct StandInUpstream {
+        received: Arc<Mutex<Vec<i64>>>,
+        lifecycle_events: Arc<Mutex<Vec<PublishLifecycleEventRequest>>>,
+        auth_headers: Arc<Mutex<Vec<String>>>,
+        fail_next_stream: Arc<AtomicBool>,
+    }
+
+    #[tonic::async_trait]
+    impl PublishBuildEvent for StandInUpstream {
+        type PublishBuildToolEventStreamStream = Pin<
+            Box<
+                dyn Stream<Item = Result<PublishBuildToolEventStreamResponse, Status>>
+                    + Send
+                    + Sync
+                    + 'static,
+            >,
+        >;
+
+        async fn publish_build_tool_event_stream(
+            &self,
+            request: Request<tonic::Streaming<PublishBuildToolEventStreamRequest>>,
+        ) -> Result<Response<Self::PublishBuildToolEventStreamStream>, Status> {
+            if let Some(auth) = request.metadata().get("authorization") {
+                self.auth_headers
+                    .lock()
+                    .unwrap()
+                    .push(auth.to_str().unwrap().to_string());
+            }
+            let mut stream = request.into_inner();
+            let received = Arc::clone(&self.received);
+            let fail = self.fail_next_stream.swap(false, Ordering::SeqCst);
+            let output = async_stream::try_stream! {
+                while let Some(request) = stream.next().await {
+                    let request = request?;
+                    let sequence_number = sequence_number(&request);
+                    if fail && sequence_number == 3 {
+                        Err(Status::unavailable("Stand in failure"))?;
+                    }
+                    received.lock().unwrap().push(sequence_number);
+                    yield PublishBuildToolEventStreamResponse {
+                        stream_id: None,
+                        sequence_number,
+                    };
+                }
+            };
+            Ok(Response::new(
+                Box::pin(output) as Self::PublishBuildToolEventStreamStream
+            ))
+        }
+
+        async fn publish_lifecycle_event(
+            &self,
+            request: Request<PublishLifecycleEventRequest>,
+        ) -> Result<Response<()>, Status> {
+            self.lifecycle_events
+                .lock()
+                .unwrap()
+                .push(request.into_inner());
+            Ok(Response::new(()))
+        }
+    }
+
+    async fn start_stand_in(stand_in: StandInUpstream, dir: &tempfile::TempDir) -> Upstream {
+        let socket_path = dir.path().join("upstream.sock");
+        let mut uds = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(PublishBuildEventServer::new(stand_in))
+                .serve_with_incoming(uds.incoming().map_ok(crate::tokioext::unix::UnixStream))
+                .await
+                .unwrap();
+        });
+        let channel = Endpoint::try_from("http://[::]:50051")
+            .unwrap()
+            .connect_with_connector(service_fn(move |_: Uri| {
+                tokio::net::UnixStream::connect(socket_path.clone())
+            }))
+            .await
+            .unwrap();
+        let mut upstream = Upstream::from_channel(channel);
+        upstream.max_in_flight = 2;
+        upstream.retry_delay = Duration::from_millis(10);
+        upstream
+    }
+
+    fn stream_request(sequence_number: i64) -> PublishBuildToolEventStreamRequest {
+        PublishBuildToolEventStreamRequest {
+            ordered_build_event: Some(OrderedBuildEvent {
+                sequence_number,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_stream_resends_after_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let stand_in = StandInUpstream::default();
+        stand_in.fail_next_stream.store(true, Ordering::SeqCst);
+        let upstream = start_stand_in(stand_in.clone(), &dir).await;
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", "Bearer token".parse().unwrap());
+        let (mut tx, rx) = mpsc::channel(1);
+        let forward_task = tokio::spawn(async move { upstream.forward_stream(metadata, rx).await });
+        for sequence_number in 1..=6 {
+            tx.send(stream_request(sequence_number)).await.unwrap();
+        }
+        drop(tx);
+        forward_task.await.unwrap().unwrap();
+
+        let mut received = stand_in.received.lock().unwrap().clone();
+        received.sort();
+        received.dedup();
+        assert_eq!(received, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            stand_in.auth_headers.lock().unwrap().clone(),
+            vec![String::from("Bearer token"), String::from("Bearer token")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_lifecycle_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let stand_in = StandInUpstream::default();
+        let upstream = start_stand_in(stand_in.clone(), &dir).await;
+
+        let request = PublishLifecycleEventRequest {
+            project_id: String::from("example"),
+            ..Default::default()
+        };
+        upstream
+            .forward_lifecycle_event(&MetadataMap::new(), request.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            stand_in.lifecycle_events.lock().unwrap().clone(),
+            vec![request]
+        );
+    }
+
+    #[test]
+    fn test_upstream_uri() {
+        assert_eq!(
+            upstream_uri("grpc://localhost:1985").unwrap(),
+            "http://localhost:1985"
+        );
+        assert!(upstream_uri("grpcs://localhost:1985").is_err());
+    }
+}
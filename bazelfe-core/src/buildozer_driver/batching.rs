@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use super::edit_plan::{BuildozerEdit, EditPlan, PlanningBuildozer};
+use super::{Buildozer, Result};
+
+// Queues edits rather than applying them as they arrive, so concurrent actions can't race
+// on the same BUILD file. Queued edits are applied by `flush`, one batch per package,
+// with only a single flush running at a time.
+#[derive(Clone, Debug)]
+pub struct BatchingBuildozer<T> {
+    planner: PlanningBuildozer<T>,
+    flush_lock: Arc<Mutex<()>>,
+}
+
+impl<T> BatchingBuildozer<T>
+where
+    T: Buildozer + Send + Sync,
+{
+    pub fn new(inner: T) -> Self {
+        Self {
+            planner: PlanningBuildozer::new(inner, EditPlan::new()),
+            flush_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn pending(&self) -> &EditPlan {
+        self.planner.plan()
+    }
+
+    // Applies everything queued so far, returning the edits which were applied.
+    pub async fn flush(&self) -> Vec<BuildozerEdit> {
+        let _guard = self.flush_lock.lock().await;
+        self.planner.plan().apply(self.planner.inner()).await
+    }
+}
+
+#[async_trait]
+impl<T> Buildozer for BatchingBuildozer<T>
+where
+    T: Buildozer + Send + Sync,
+{
+    async fn print_deps(&self, label: &String) -> Result<Vec<String>> {
+        self.planner.print_deps(label).await
+    }
+
+    async fn add_dependency(
+        &self,
+        target_to_operate_on: &String,
+        label_to_add: &String,
+    ) -> Result<()> {
+        self.planner
+            .add_dependency(target_to_operate_on, label_to_add)
+            .await
+    }
+
+    async fn remove_dependency(
+        &self,
+        target_to_operate_on: &String,
+        label_to_add: &String,
+    ) -> Result<()> {
+        self.planner
+            .remove_dependency(target_to_operate_on, label_to_add)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records each batch it is asked to apply
+    #[derive(Clone, Debug, Default)]
+    struct RecordingBuildozer {
+        batches: Arc<std::sync::Mutex<Vec<Vec<BuildozerEdit>>>>,
+    }
+
+    #[async_trait]
+    impl Buildozer for RecordingBuildozer {
+        async fn print_deps(&self, _label: &String) -> Result<Vec<String>> {
+            Ok(Vec::default())
+        }
+
+        async fn add_dependency(&self, _target: &String, _label: &String) -> Result<()> {
+            panic!("Edits should only be applied in batches")
+        }
+
+        async fn remove_dependency(&self, _target: &String, _label: &String) -> Result<()> {
+            panic!("Edits should only be applied in batches")
+        }
+
+        async fn apply_batch(&self, edits: &[BuildozerEdit]) -> Vec<Result<()>> {
+            self.batches.lock().unwrap().push(edits.to_vec());
+            edits.iter().map(|_| Ok(())).collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_groups_edits_by_package() {
+        let recorder = RecordingBuildozer::default();
+        let buildozer = BatchingBuildozer::new(recorder.clone());
+
+        let foo = String::from("//src/main/java/com/example/foo:foo");
+        let foo_test = String::from("//src/main/java/com/example/foo:foo_test");
+        let bar = String::from("//src/main/java/com/example/bar:bar");
+
+        buildozer
+            .add_dependency(&foo, &String::from("//a:a"))
+            .await
+            .unwrap();
+        buildozer
+            .add_dependency(&bar, &String::from("//a:a"))
+            .await
+            .unwrap();
+        buildozer
+            .add_dependency(&foo_test, &String::from("//b:b"))
+            .await
+            .unwrap();
+
+        // Queued edits are visible before they are flushed
+        assert_eq!(
+            buildozer.print_deps(&foo).await.unwrap(),
+            vec![String::from("//a:a")]
+        );
+        assert!(recorder.batches.lock().unwrap().is_empty());
+
+        assert_eq!(buildozer.flush().await.len(), 3);
+        assert!(buildozer.pending().is_empty());
+
+        let batches = recorder.batches.lock().unwrap().clone();
+        assert_eq!(
+            batches
+                .iter()
+                .map(|b| b
+                    .iter()
+                    .map(|e| e.target().clone())
+                    .collect::<Vec<String>>())
+                .collect::<Vec<Vec<String>>>(),
+            vec![vec![foo.clone(), foo_test.clone()], vec![bar.clone()]]
+        );
+    }
+}
@@ -0,0 +1,736 @@
+// Just enough of a Starlark lexer to find rule invocations in a BUILD file and
+// splice their deps lists. Everything we don't touch, formatting and comments included,
+// is left exactly as it was.
+
+use super::{ExecuteResultError, Result};
+
+#[derive(Clone, PartialEq, Debug)]
+enum TokenKind {
+    Ident(String),
+    Str(String),
+    Open(char),
+    Close(char),
+    Comma,
+    Equals,
+    Other,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Argument {
+    key: Option<String>,
+    // Token indices, end is exclusive
+    start: usize,
+    value_start: usize,
+    end: usize,
+    comma: Option<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Call {
+    kind: String,
+    name: Option<String>,
+    open: usize,
+    close: usize,
+    args: Vec<Argument>,
+}
+
+pub(super) fn edit_error(msg: String) -> ExecuteResultError {
+    ExecuteResultError {
+        exit_code: 2,
+        stdout: String::from(""),
+        stderr: msg,
+    }
+}
+
+fn scan_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let triple = bytes.len() >= start + 3 && bytes[start + 1] == quote && bytes[start + 2] == quote;
+    let mut idx = if triple { start + 3 } else { start + 1 };
+    while idx < bytes.len() {
+        let c = bytes[idx];
+        if c == b'\\' {
+            idx += 2;
+        } else if triple {
+            if c == quote
+                && idx + 2 < bytes.len()
+                && bytes[idx + 1] == quote
+                && bytes[idx + 2] == quote
+            {
+                return idx + 3;
+            }
+            idx += 1;
+        } else if c == quote {
+            return idx + 1;
+        } else if c == b'\n' {
+            // Unterminated, let the rest of the file carry on
+            return idx;
+        } else {
+            idx += 1;
+        }
+    }
+    bytes.len()
+}
+
+fn string_value(literal: &str) -> String {
+    let raw = literal
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .any(|c| c == 'r' || c == 'R');
+    let body = literal.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let quote = ["\"\"\"", "'''", "\"", "'"]
+        .iter()
+        .find(|quote| body.starts_with(*quote))
+        .copied()
+        .unwrap_or("\"");
+    let body = match body.strip_prefix(quote) {
+        Some(body) => body,
+        None => return String::from(""),
+    };
+    // An unterminated string stops at the end of its line, without a closing quote
+    let inner = body.strip_suffix(quote).unwrap_or(body);
+    if raw {
+        return inner.to_string();
+    }
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                value.push(escaped);
+            }
+        } else {
+            value.push(c);
+        }
+    }
+    value
+}
+
+fn tokenize(content: &str) -> Vec<Token> {
+    let bytes = content.as_bytes();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let start = idx;
+        let c = bytes[idx];
+        let kind = match c {
+            b'#' => {
+                while idx < bytes.len() && bytes[idx] != b'\n' {
+                    idx += 1;
+                }
+                continue;
+            }
+            b'"' | b'\'' => {
+                idx = scan_string(bytes, idx);
+                TokenKind::Str(string_value(&content[start..idx]))
+            }
+            b'(' | b'[' | b'{' => {
+                idx += 1;
+                TokenKind::Open(c as char)
+            }
+            b')' | b']' | b'}' => {
+                idx += 1;
+                TokenKind::Close(c as char)
+            }
+            b',' => {
+                idx += 1;
+                TokenKind::Comma
+            }
+            b'=' => {
+                if idx + 1 < bytes.len() && bytes[idx + 1] == b'=' {
+                    idx += 2;
+                    TokenKind::Other
+                } else {
+                    idx += 1;
+                    TokenKind::Equals
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while idx < bytes.len()
+                    && (bytes[idx].is_ascii_alphanumeric() || bytes[idx] == b'_')
+                {
+                    idx += 1;
+                }
+                let ident = &content[start..idx];
+                let is_string_prefix = ident.len() <= 2
+                    && ident.chars().all(|c| "rRbB".contains(c))
+                    && idx < bytes.len()
+                    && (bytes[idx] == b'"' || bytes[idx] == b'\'');
+                if is_string_prefix {
+                    idx = scan_string(bytes, idx);
+                    TokenKind::Str(string_value(&content[start..idx]))
+                } else {
+                    TokenKind::Ident(ident.to_string())
+                }
+            }
+            c if c.is_ascii_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            _ => {
+                idx += 1;
+                TokenKind::Other
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: idx,
+        });
+    }
+    tokens
+}
+
+fn make_argument(tokens: &[Token], start: usize, end: usize, comma: Option<usize>) -> Argument {
+    let key = match (&tokens[start].kind, tokens.get(start + 1).map(|t| &t.kind)) {
+        (TokenKind::Ident(key), Some(TokenKind::Equals)) if end - start >= 2 => Some(key.clone()),
+        _ => None,
+    };
+    Argument {
+        value_start: if key.is_some() { start + 2 } else { start },
+        key,
+        start,
+        end,
+        comma,
+    }
+}
+
+// Parses the call whose opening paren is at the given index
+fn parse_call(tokens: &[Token], kind: String, open: usize) -> Option<Call> {
+    let mut depth = 0;
+    let mut args = Vec::new();
+    let mut arg_start = open + 1;
+    for idx in open..tokens.len() {
+        match tokens[idx].kind {
+            TokenKind::Open(_) => depth += 1,
+            TokenKind::Close(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    if arg_start < idx {
+                        args.push(make_argument(tokens, arg_start, idx, None));
+                    }
+                    let name = args
+                        .iter()
+                        .find(|a| a.key.as_deref() == Some("name"))
+                        .and_then(
+                            |a| match (&tokens[a.value_start].kind, a.end - a.value_start) {
+                                (TokenKind::Str(name), 1) => Some(name.clone()),
+                                _ => None,
+                            },
+                        );
+                    return Some(Call {
+                        kind,
+                        name,
+                        open,
+                        close: idx,
+                        args,
+                    });
+                }
+            }
+            TokenKind::Comma if depth == 1 => {
+                if arg_start < idx {
+                    args.push(make_argument(tokens, arg_start, idx, Some(idx)));
+                }
+                arg_start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn top_level_calls(tokens: &[Token]) -> Vec<Call> {
+    let mut calls = Vec::new();
+    let mut depth = 0;
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match &tokens[idx].kind {
+            TokenKind::Ident(kind) if depth == 0 => {
+                if let Some(TokenKind::Open('(')) = tokens.get(idx + 1).map(|t| &t.kind) {
+                    if let Some(call) = parse_call(tokens, kind.clone(), idx + 1) {
+                        idx = call.close + 1;
+                        calls.push(call);
+                        continue;
+                    }
+                }
+            }
+            TokenKind::Open(_) => depth += 1,
+            TokenKind::Close(_) => depth -= 1,
+            _ => (),
+        }
+        idx += 1;
+    }
+    calls
+}
+
+fn find_rule(tokens: &[Token], rule_name: &str) -> Result<Call> {
+    top_level_calls(tokens)
+        .into_iter()
+        .find(|c| c.name.as_deref() == Some(rule_name))
+        .ok_or_else(|| edit_error(format!("Unable to find a rule named {:?}", rule_name)))
+}
+
+// The (open, close) token indices of the list literals making up an expression
+fn list_literals(tokens: &[Token], start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut lists = Vec::new();
+    let mut depth = 0;
+    let mut list_open = None;
+    for (idx, token) in tokens.iter().enumerate().take(end).skip(start) {
+        match token.kind {
+            TokenKind::Open(c) => {
+                if depth == 0 && c == '[' {
+                    list_open = Some(idx);
+                }
+                depth += 1;
+            }
+            TokenKind::Close(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(open) = list_open.take() {
+                        lists.push((open, idx));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    lists
+}
+
+// Token indices of the plain string entries in a list literal
+fn list_elements(tokens: &[Token], open: usize, close: usize) -> Vec<usize> {
+    let mut elements = Vec::new();
+    let mut depth = 0;
+    for (idx, token) in tokens.iter().enumerate().take(close).skip(open + 1) {
+        match token.kind {
+            TokenKind::Open(_) => depth += 1,
+            TokenKind::Close(_) => depth -= 1,
+            TokenKind::Str(_) if depth == 0 => elements.push(idx),
+            _ => (),
+        }
+    }
+    elements
+}
+
+fn str_value(token: &Token) -> &str {
+    match &token.kind {
+        TokenKind::Str(s) => s,
+        _ => "",
+    }
+}
+
+fn line_indent(content: &str, pos: usize) -> &str {
+    let line_start = content[..pos].rfind('\n').map(|e| e + 1).unwrap_or(0);
+    let line = &content[line_start..pos];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// Position of the end of the line containing pos, if that comes before limit
+fn line_end_before(content: &str, pos: usize, limit: usize) -> Option<usize> {
+    content[pos..]
+        .find('\n')
+        .map(|e| e + pos)
+        .filter(|e| *e < limit)
+}
+
+fn splice(content: &str, start: usize, end: usize, replacement: &str) -> String {
+    format!("{}{}{}", &content[..start], replacement, &content[end..])
+}
+
+// Expands a label as written in a BUILD file in the given package into the //pkg:name form
+pub(super) fn absolute_label(package: &str, label: &str) -> String {
+    let label = if label.starts_with(':') {
+        format!("//{}{}", package, label)
+    } else if label.starts_with("//") {
+        label.to_string()
+    } else if let Some(repo) = label.strip_prefix('@') {
+        if label.contains("//") {
+            label.to_string()
+        } else {
+            format!("{}//:{}", label, repo)
+        }
+    } else {
+        format!("//{}:{}", package, label)
+    };
+
+    let path_start = label.find("//").map(|e| e + 2).unwrap_or(0);
+    if label[path_start..].contains(':') {
+        label
+    } else {
+        let last_segment = label[path_start..].rsplit('/').next().unwrap_or("");
+        format!("{}:{}", label, last_segment)
+    }
+}
+
+// Shortens a label to the :name form when it lives in the given package, as buildozer would
+fn relative_label(package: &str, label: &str) -> String {
+    match label.strip_prefix(&format!("//{}:", package)) {
+        Some(name) => format!(":{}", name),
+        None => label.to_string(),
+    }
+}
+
+pub(super) fn print_deps(content: &str, package: &str, rule_name: &str) -> Result<Vec<String>> {
+    let tokens = tokenize(content);
+    let call = find_rule(&tokens, rule_name)?;
+    Ok(
+        match call.args.iter().find(|a| a.key.as_deref() == Some("deps")) {
+            None => Vec::default(),
+            Some(deps) => list_literals(&tokens, deps.value_start, deps.end)
+                .into_iter()
+                .flat_map(|(open, close)| list_elements(&tokens, open, close))
+                .map(|idx| absolute_label(package, str_value(&tokens[idx])))
+                .collect(),
+        },
+    )
+}
+
+// Returns the new content of the file, or None if the dependency was already present
+pub(super) fn add_dependency(
+    content: &str,
+    package: &str,
+    rule_name: &str,
+    label: &str,
+) -> Result<Option<String>> {
+    let tokens = tokenize(content);
+    let call = find_rule(&tokens, rule_name)?;
+    let label = absolute_label(package, label);
+    let literal = format!("\"{}\"", relative_label(package, &label));
+
+    let deps_arg = call.args.iter().find(|a| a.key.as_deref() == Some("deps"));
+    let deps_arg = match deps_arg {
+        Some(deps_arg) => deps_arg,
+        None => {
+            let attr = format!("deps = [{}]", literal);
+            let updated = match call.args.last() {
+                None => splice(content, tokens[call.open].end, tokens[call.open].end, &attr),
+                Some(last) => {
+                    let indent = line_indent(content, tokens[last.start].start);
+                    let multi_line =
+                        content[tokens[call.open].end..tokens[call.close].start].contains('\n');
+                    match last.comma {
+                        Some(comma) => {
+                            let comma_end = tokens[comma].end;
+                            match line_end_before(content, comma_end, tokens[call.close].start) {
+                                Some(line_end) if multi_line => splice(
+                                    content,
+                                    line_end,
+                                    line_end,
+                                    &format!("\n{}{},", indent, attr),
+                                ),
+                                _ => splice(content, comma_end, comma_end, &format!(" {},", attr)),
+                            }
+                        }
+                        None => {
+                            let last_end = tokens[last.end - 1].end;
+                            let separator = if multi_line {
+                                format!(",\n{}", indent)
+                            } else {
+                                String::from(", ")
+                            };
+                            splice(
+                                content,
+                                last_end,
+                                last_end,
+                                &format!("{}{}", separator, attr),
+                            )
+                        }
+                    }
+                }
+            };
+            return Ok(Some(updated));
+        }
+    };
+
+    let lists = list_literals(&tokens, deps_arg.value_start, deps_arg.end);
+    for (open, close) in lists.iter() {
+        for idx in list_elements(&tokens, *open, *close) {
+            if absolute_label(package, str_value(&tokens[idx])) == label {
+                return Ok(None);
+            }
+        }
+    }
+
+    let (open, close) = *lists.first().ok_or_else(|| {
+        edit_error(format!(
+            "The deps of {:?} are not a list we know how to edit",
+            rule_name
+        ))
+    })?;
+
+    let updated = match list_elements(&tokens, open, close).last() {
+        None => splice(content, tokens[open].end, tokens[open].end, &literal),
+        Some(last) => {
+            let last_token = &tokens[*last];
+            let comma = match tokens.get(last + 1).map(|t| &t.kind) {
+                Some(TokenKind::Comma) => Some(&tokens[last + 1]),
+                _ => None,
+            };
+            let after = comma.map(|c| c.end).unwrap_or(last_token.end);
+            let indent = line_indent(content, last_token.start);
+            match (line_end_before(content, after, tokens[close].start), comma) {
+                (Some(line_end), Some(_)) => splice(
+                    content,
+                    line_end,
+                    line_end,
+                    &format!("\n{}{},", indent, literal),
+                ),
+                (Some(_), None) => splice(
+                    content,
+                    last_token.end,
+                    last_token.end,
+                    &format!(",\n{}{}", indent, literal),
+                ),
+                (None, Some(_)) => splice(content, after, after, &format!(" {},", literal)),
+                (None, None) => splice(content, after, after, &format!(", {}", literal)),
+            }
+        }
+    };
+    Ok(Some(updated))
+}
+
+// Like gazelle, a dependency with a # keep comment is never removed
+fn is_keep_comment(comment: &str) -> bool {
+    comment
+        .strip_prefix('#')
+        .map(|e| e.trim_start().starts_with("keep"))
+        .unwrap_or(false)
+}
+
+// Returns the new content of the file, or None if the dependency wasn't present or is kept
+pub(super) fn remove_dependency(
+    content: &str,
+    package: &str,
+    rule_name: &str,
+    label: &str,
+) -> Result<Option<String>> {
+    let tokens = tokenize(content);
+    let call = find_rule(&tokens, rule_name)?;
+    let label = absolute_label(package, label);
+
+    let deps_arg = match call.args.iter().find(|a| a.key.as_deref() == Some("deps")) {
+        Some(deps_arg) => deps_arg,
+        None => return Ok(None),
+    };
+
+    for (open, close) in list_literals(&tokens, deps_arg.value_start, deps_arg.end) {
+        for idx in list_elements(&tokens, open, close) {
+            if absolute_label(package, str_value(&tokens[idx])) != label {
+                continue;
+            }
+            let token = &tokens[idx];
+            let comma = match tokens.get(idx + 1).map(|t| &t.kind) {
+                Some(TokenKind::Comma) => Some(&tokens[idx + 1]),
+                _ => None,
+            };
+            let after = comma.map(|c| c.end).unwrap_or(token.end);
+
+            let line_start = content[..token.start]
+                .rfind('\n')
+                .map(|e| e + 1)
+                .unwrap_or(0);
+            let own_line = content[line_start..token.start].trim().is_empty();
+            if own_line {
+                if let Some(line_end) = line_end_before(content, after, tokens[close].start) {
+                    let rest = content[after..line_end].trim();
+                    if is_keep_comment(rest) {
+                        return Ok(None);
+                    }
+                    if rest.is_empty() {
+                        return Ok(Some(splice(content, line_start, line_end + 1, "")));
+                    }
+                    if rest.starts_with('#') {
+                        // Leave the comment where the dependency was
+                        return Ok(Some(splice(content, token.start, line_end, rest)));
+                    }
+                }
+            }
+
+            let updated = match comma {
+                Some(comma) => {
+                    let trailing_space = content[comma.end..]
+                        .chars()
+                        .take_while(|c| *c == ' ' || *c == '\t')
+                        .count();
+                    splice(content, token.start, comma.end + trailing_space, "")
+                }
+                None => match tokens[idx - 1].kind {
+                    TokenKind::Comma => splice(content, tokens[idx - 1].start, token.end, ""),
+                    _ => splice(content, token.start, token.end, ""),
+                },
+            };
+            return Ok(Some(updated));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_BUILD_FILE: &str = r#"load("@io_bazel_rules_scala//scala:scala.bzl", "scala_library")
+
+# Comments with a ( or [ shouldn't confuse us
+scala_library(
+    name = "foo",
+    srcs = glob(["*.scala"]),
+    deps = [
+        ":bar",  # keep
+        "//src/main/scala/com/example/baz",
+    ],
+)
+
+java_library(name = "bar", srcs = ["Bar.java"], deps = ["//a:a", "//b:b"])
+
+java_library(
+    name = "no_deps",
+    srcs = ["NoDeps.java"],
+)
+"#;
+
+    #[test]
+    fn test_print_deps() {
+        assert_eq!(
+            print_deps(SAMPLE_BUILD_FILE, "src/main/scala/com/example", "foo").unwrap(),
+            vec![
+                String::from("//src/main/scala/com/example:bar"),
+                String::from("//src/main/scala/com/example/baz:baz"),
+            ]
+        );
+        assert_eq!(
+            print_deps(SAMPLE_BUILD_FILE, "src/main/scala/com/example", "no_deps").unwrap(),
+            Vec::<String>::new()
+        );
+        assert!(print_deps(SAMPLE_BUILD_FILE, "src/main/scala/com/example", "missing").is_err());
+    }
+
+    #[test]
+    fn test_add_dependency() {
+        let package = "src/main/scala/com/example";
+        let updated = add_dependency(SAMPLE_BUILD_FILE, package, "foo", "//c:c")
+            .unwrap()
+            .unwrap();
+        assert!(updated.contains(
+            r#"        "//src/main/scala/com/example/baz",
+        "//c:c",
+    ],"#
+        ));
+
+        let updated = add_dependency(&updated, package, "bar", "//src/main/scala/com/example:qux")
+            .unwrap()
+            .unwrap();
+        assert!(updated.contains(r#"deps = ["//a:a", "//b:b", ":qux"])"#));
+
+        let updated = add_dependency(&updated, package, "no_deps", "//a:a")
+            .unwrap()
+            .unwrap();
+        assert!(updated.contains(
+            r#"    srcs = ["NoDeps.java"],
+    deps = ["//a:a"],
+)"#
+        ));
+
+        // Everything else is untouched
+        assert_eq!(
+            updated.len(),
+            SAMPLE_BUILD_FILE.len()
+                + "\n        \"//c:c\",".len()
+                + ", \":qux\"".len()
+                + "\n    deps = [\"//a:a\"],".len()
+        );
+
+        // Already present under a different spelling
+        assert_eq!(
+            add_dependency(SAMPLE_BUILD_FILE, package, "foo", ":bar").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remove_dependency() {
+        let package = "src/main/scala/com/example";
+        assert_eq!(
+            remove_dependency(
+                SAMPLE_BUILD_FILE,
+                package,
+                "foo",
+                "//src/main/scala/com/example:bar",
+            )
+            .unwrap(),
+            None
+        );
+
+        let updated = remove_dependency(
+            SAMPLE_BUILD_FILE,
+            package,
+            "foo",
+            "//src/main/scala/com/example/baz",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(updated.contains(
+            r#"    deps = [
+        ":bar",  # keep
+    ],"#
+        ));
+
+        let commented = SAMPLE_BUILD_FILE.replace(
+            "\"//src/main/scala/com/example/baz\",",
+            "\"//src/main/scala/com/example/baz\",  # loaded by reflection",
+        );
+        let updated = remove_dependency(
+            &commented,
+            package,
+            "foo",
+            "//src/main/scala/com/example/baz",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(updated.contains(
+            r#"    deps = [
+        ":bar",  # keep
+        # loaded by reflection
+    ],"#
+        ));
+
+        let updated = remove_dependency(&updated, package, "bar", "//b:b")
+            .unwrap()
+            .unwrap();
+        assert!(updated.contains(r#"deps = ["//a:a"])"#));
+
+        let updated = remove_dependency(&updated, package, "bar", "//a:a")
+            .unwrap()
+            .unwrap();
+        assert!(updated.contains(r#"deps = [])"#));
+
+        assert_eq!(
+            remove_dependency(SAMPLE_BUILD_FILE, package, "no_deps", "//a:a").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let build_file = "java_library(\n    name = \"é\n    deps = [\"//a:a\"],\n)\n";
+        assert!(print_deps(build_file, "src/main/java/com/example", "foo").is_err());
+        assert_eq!(string_value("\"é"), "é");
+        assert_eq!(string_value("\"\"\"é\"\"\""), "é");
+        assert_eq!(string_value("'é'"), "é");
+    }
+
+    #[test]
+    fn test_absolute_label() {
+        assert_eq!(absolute_label("a/b", ":c"), "//a/b:c");
+        assert_eq!(absolute_label("a/b", "c"), "//a/b:c");
+        assert_eq!(absolute_label("a/b", "//d/e"), "//d/e:e");
+        assert_eq!(absolute_label("a/b", "@foo//d:e"), "@foo//d:e");
+        assert_eq!(absolute_label("a/b", "@foo"), "@foo//:foo");
+    }
+}
@@ -2,13 +2,13 @@ use clap::Clap;
 use std::error::Error;
 use std::path::PathBuf;
 
-use bazelfe_core::buildozer_driver::{from_binary_path, Buildozer};
+use bazelfe_core::buildozer_driver::{from_binary_path, from_workspace_root, Buildozer};
 
 #[derive(Clap, Debug)]
 #[clap(name = "basic")]
 struct Opt {
     #[clap(long, env = "BUILDOZER_PATH", parse(from_os_str))]
-    buildozer_path: PathBuf,
+    buildozer_path: Option<PathBuf>,
 
     target_name: String,
 }
@@ -16,8 +16,16 @@ struct Opt {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let opt = Opt::parse();
-    let buildozer = from_binary_path(opt.buildozer_path);
-    let buildozer_resp = buildozer.print_deps(&opt.target_name).await.unwrap();
+    let buildozer_resp = match opt.buildozer_path {
+        Some(buildozer_path) => from_binary_path(buildozer_path)
+            .print_deps(&opt.target_name)
+            .await
+            .unwrap(),
+        None => from_workspace_root(std::env::current_dir()?)
+            .print_deps(&opt.target_name)
+            .await
+            .unwrap(),
+    };
     println!("{:?}", buildozer_resp);
     Ok(())
 }
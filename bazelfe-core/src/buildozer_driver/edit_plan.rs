@@ -0,0 +1,299 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use super::{Buildozer, Result};
+
+// A single change to a BUILD file, as buildozer would express it.
+#[derive(Clone, PartialEq, Debug)]
+pub enum BuildozerEdit {
+    AddDependency { target: String, label: String },
+    RemoveDependency { target: String, label: String },
+}
+
+impl BuildozerEdit {
+    pub fn target(&self) -> &String {
+        match self {
+            BuildozerEdit::AddDependency { target, .. } => target,
+            BuildozerEdit::RemoveDependency { target, .. } => target,
+        }
+    }
+
+    pub fn label(&self) -> &String {
+        match self {
+            BuildozerEdit::AddDependency { label, .. } => label,
+            BuildozerEdit::RemoveDependency { label, .. } => label,
+        }
+    }
+
+    // The package holding the BUILD file this edit applies to
+    pub fn package(&self) -> &str {
+        let target = self.target();
+        match target.find(':') {
+            Some(idx) => &target[..idx],
+            None => target,
+        }
+    }
+
+    // The buildozer command (excluding the target) that performs this edit
+    pub fn command(&self) -> String {
+        match self {
+            BuildozerEdit::AddDependency { label, .. } => format!("add deps {}", label),
+            BuildozerEdit::RemoveDependency { label, .. } => format!("remove deps {}", label),
+        }
+    }
+
+    // The edit that undoes this one
+    pub fn reversed(&self) -> BuildozerEdit {
+        match self {
+            BuildozerEdit::AddDependency { target, label } => BuildozerEdit::RemoveDependency {
+                target: target.clone(),
+                label: label.clone(),
+            },
+            BuildozerEdit::RemoveDependency { target, label } => BuildozerEdit::AddDependency {
+                target: target.clone(),
+                label: label.clone(),
+            },
+        }
+    }
+
+    pub fn to_buildozer_command(&self) -> String {
+        format!("buildozer '{}' {}", self.command(), self.target())
+    }
+
+    pub async fn apply<T: Buildozer + Send + Sync>(&self, buildozer: &T) -> Result<()> {
+        match self {
+            BuildozerEdit::AddDependency { target, label } => {
+                buildozer.add_dependency(target, label).await
+            }
+            BuildozerEdit::RemoveDependency { target, label } => {
+                buildozer.remove_dependency(target, label).await
+            }
+        }
+    }
+}
+
+// An ordered, shareable, collection of edits that have been proposed but not yet applied.
+#[derive(Clone, Debug, Default)]
+pub struct EditPlan {
+    edits: Arc<Mutex<Vec<BuildozerEdit>>>,
+}
+
+impl EditPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, edit: BuildozerEdit) {
+        self.edits.lock().unwrap().push(edit);
+    }
+
+    pub fn edits(&self) -> Vec<BuildozerEdit> {
+        self.edits.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.edits.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn take(&self) -> Vec<BuildozerEdit> {
+        self.edits.lock().unwrap().drain(..).collect()
+    }
+
+    pub fn to_buildozer_script(&self) -> String {
+        let mut script = String::new();
+        for edit in self.edits().iter() {
+            script.push_str(&edit.to_buildozer_command());
+            script.push('\n');
+        }
+        script
+    }
+
+    // Applies every pending edit using the supplied buildozer, draining the plan.
+    // Edits are grouped by package so each BUILD file is handled in a single batch.
+    // Returns the edits that applied successfully.
+    pub async fn apply<T: Buildozer + Send + Sync>(&self, buildozer: &T) -> Vec<BuildozerEdit> {
+        let mut by_package: Vec<(String, Vec<BuildozerEdit>)> = Vec::default();
+        for edit in self.take().into_iter() {
+            match by_package.iter_mut().find(|(p, _)| p == edit.package()) {
+                Some((_, edits)) => edits.push(edit),
+                None => by_package.push((edit.package().to_string(), vec![edit])),
+            }
+        }
+
+        let mut applied: Vec<BuildozerEdit> = Vec::default();
+        for (_, edits) in by_package.into_iter() {
+            let results = buildozer.apply_batch(&edits).await;
+            for (edit, result) in edits.into_iter().zip(results) {
+                match result {
+                    Ok(_) => applied.push(edit),
+                    Err(e) => warn!("Failed to apply {}: {:?}", edit.to_buildozer_command(), e),
+                }
+            }
+        }
+        applied
+    }
+
+    // What the deps of a target would look like once the planned edits are applied
+    fn overlay_deps(&self, target: &str, mut deps: Vec<String>) -> Vec<String> {
+        for edit in self.edits.lock().unwrap().iter() {
+            if edit.target() != target {
+                continue;
+            }
+            match edit {
+                BuildozerEdit::AddDependency { label, .. } => {
+                    if !deps.contains(label) {
+                        deps.push(label.clone());
+                    }
+                }
+                BuildozerEdit::RemoveDependency { label, .. } => {
+                    deps.retain(|e| e != label);
+                }
+            }
+        }
+        deps
+    }
+}
+
+// Records add/remove operations into an EditPlan instead of applying them.
+// Reads go to the underlying buildozer, with the pending edits overlaid so the
+// rest of the pipeline behaves as though they had been applied.
+#[derive(Clone, Debug)]
+pub struct PlanningBuildozer<T> {
+    inner: T,
+    plan: EditPlan,
+}
+
+impl<T> PlanningBuildozer<T> {
+    pub fn new(inner: T, plan: EditPlan) -> Self {
+        Self { inner, plan }
+    }
+
+    pub fn plan(&self) -> &EditPlan {
+        &self.plan
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<T> Buildozer for PlanningBuildozer<T>
+where
+    T: Buildozer + Send + Sync,
+{
+    async fn print_deps(&self, label: &String) -> Result<Vec<String>> {
+        let deps = self.inner.print_deps(label).await?;
+        Ok(self.plan.overlay_deps(label, deps))
+    }
+
+    async fn add_dependency(
+        &self,
+        target_to_operate_on: &String,
+        label_to_add: &String,
+    ) -> Result<()> {
+        self.plan.push(BuildozerEdit::AddDependency {
+            target: target_to_operate_on.clone(),
+            label: label_to_add.clone(),
+        });
+        Ok(())
+    }
+
+    async fn remove_dependency(
+        &self,
+        target_to_operate_on: &String,
+        label_to_add: &String,
+    ) -> Result<()> {
+        self.plan.push(BuildozerEdit::RemoveDependency {
+            target: target_to_operate_on.clone(),
+            label: label_to_add.clone(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct StaticDepsBuildozer {
+        deps: Vec<String>,
+    }
+
+    #[async_trait]
+    impl Buildozer for StaticDepsBuildozer {
+        async fn print_deps(&self, _label: &String) -> Result<Vec<String>> {
+            Ok(self.deps.clone())
+        }
+
+        async fn add_dependency(&self, _target: &String, _label: &String) -> Result<()> {
+            panic!("Planning should never write through to the underlying buildozer")
+        }
+
+        async fn remove_dependency(&self, _target: &String, _label: &String) -> Result<()> {
+            panic!("Planning should never write through to the underlying buildozer")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_planned_edits_are_overlaid_on_reads() {
+        let plan = EditPlan::new();
+        let buildozer = PlanningBuildozer::new(
+            StaticDepsBuildozer {
+                deps: vec![String::from("//a:a"), String::from("//b:b")],
+            },
+            plan.clone(),
+        );
+        let target = String::from("//src/main/java/com/example:example");
+
+        buildozer
+            .add_dependency(&target, &String::from("//c:c"))
+            .await
+            .unwrap();
+        buildozer
+            .remove_dependency(&target, &String::from("//a:a"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            buildozer.print_deps(&target).await.unwrap(),
+            vec![String::from("//b:b"), String::from("//c:c")]
+        );
+
+        // Other targets don't see the edits
+        assert_eq!(
+            buildozer
+                .print_deps(&String::from("//other:other"))
+                .await
+                .unwrap(),
+            vec![String::from("//a:a"), String::from("//b:b")]
+        );
+        assert_eq!(plan.len(), 2);
+    }
+
+    #[test]
+    fn test_to_buildozer_script() {
+        let plan = EditPlan::new();
+        plan.push(BuildozerEdit::AddDependency {
+            target: String::from("//src/main/java/com/example:example"),
+            label: String::from("//src/main/java/com/example/foo:foo"),
+        });
+        plan.push(BuildozerEdit::RemoveDependency {
+            target: String::from("//src/main/java/com/example:example"),
+            label: String::from("//src/main/java/com/example/bar:bar"),
+        });
+
+        assert_eq!(
+            plan.to_buildozer_script(),
+            "buildozer 'add deps //src/main/java/com/example/foo:foo' //src/main/java/com/example:example
+buildozer 'remove deps //src/main/java/com/example/bar:bar' //src/main/java/com/example:example
+"
+        );
+    }
+}
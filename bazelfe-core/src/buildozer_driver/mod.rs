@@ -2,9 +2,15 @@ use bazelfe_protos::*;
 
 use ::prost::Message;
 use async_trait::async_trait;
-use std::{ffi::OsString, path::PathBuf};
+use std::{ffi::OsString, path::PathBuf, process::Stdio};
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+pub mod batching;
+mod build_file;
+pub mod edit_plan;
+pub mod native_buildozer;
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct ExecuteResultError {
     pub exit_code: i32,
@@ -43,6 +49,26 @@ pub trait Buildozer {
         target_to_operate_on: &String,
         label_to_add: &String,
     ) -> Result<()>;
+
+    // Applies a group of edits, implementations can override this to apply them together.
+    // Returns the result of each edit, so one failing doesn't stop the others.
+    async fn apply_batch(&self, edits: &[edit_plan::BuildozerEdit]) -> Vec<Result<()>>
+    where
+        Self: Sync,
+    {
+        let mut results = Vec::default();
+        for edit in edits.iter() {
+            results.push(match edit {
+                edit_plan::BuildozerEdit::AddDependency { target, label } => {
+                    self.add_dependency(target, label).await
+                }
+                edit_plan::BuildozerEdit::RemoveDependency { target, label } => {
+                    self.remove_dependency(target, label).await
+                }
+            });
+        }
+        results
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -56,6 +82,10 @@ pub fn from_binary_path(pb: PathBuf) -> BuildozerBinaryImpl {
     }
 }
 
+pub fn from_workspace_root(pb: PathBuf) -> native_buildozer::NativeBuildozer {
+    native_buildozer::NativeBuildozer::new(pb)
+}
+
 impl BuildozerBinaryImpl {
     fn decode_str(data: &Vec<u8>) -> String {
         if data.len() > 0 {
@@ -95,6 +125,32 @@ impl BuildozerBinaryImpl {
         let out = devtools::buildozer::Output::decode(&*command_result.stdout).unwrap();
         Ok((command, out))
     }
+
+    // Runs a buildozer command file, one `command|target` per line, fed over stdin.
+    async fn execute_command_file(&self, commands: &str) -> Result<()> {
+        let mut child = Command::new(&self.buildozer_executable_path)
+            .arg("-f")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(commands.as_bytes()).await?;
+        }
+        let command_result = child.wait_with_output().await?;
+
+        let exit_code = command_result.status.code().unwrap_or(-1);
+        if exit_code < 0 {
+            return Err(ExecuteResultError {
+                exit_code,
+                stdout: BuildozerBinaryImpl::decode_str(&command_result.stdout),
+                stderr: BuildozerBinaryImpl::decode_str(&command_result.stderr),
+            });
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -157,4 +213,23 @@ impl Buildozer for BuildozerBinaryImpl {
             .await?;
         Ok(())
     }
+
+    async fn apply_batch(&self, edits: &[edit_plan::BuildozerEdit]) -> Vec<Result<()>> {
+        let mut commands = String::new();
+        for edit in edits.iter() {
+            commands.push_str(&format!("{}|{}\n", edit.command(), edit.target()));
+        }
+        match self.execute_command_file(&commands).await {
+            Ok(_) => edits.iter().map(|_| Ok(())).collect(),
+            Err(e) => {
+                // We can't tell which edit failed, so find out by applying them one at a time
+                warn!("Failed to apply a batch of edits, retrying each: {:?}", e);
+                let mut results = Vec::default();
+                for edit in edits.iter() {
+                    results.push(edit.apply(self).await);
+                }
+                results
+            }
+        }
+    }
 }
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use super::build_file::{self, edit_error};
+use super::edit_plan::BuildozerEdit;
+use super::{Buildozer, Result};
+
+// Edits BUILD files in process, without needing the buildozer binary.
+// Reads and writes are serialized so concurrent edits to the same BUILD file can't race,
+// and nothing reads a BUILD file while it's half written.
+#[derive(Clone, Debug)]
+pub struct NativeBuildozer {
+    workspace_root: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl NativeBuildozer {
+    pub fn new(workspace_root: PathBuf) -> Self {
+        Self {
+            workspace_root,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    // Splits a target into the BUILD file that defines it and the rule name
+    fn locate(&self, target: &str) -> Result<(PathBuf, String, String)> {
+        if target.starts_with('@') && !target.starts_with("@//") {
+            return Err(edit_error(format!(
+                "Cannot edit {:?}, it lives in an external repository",
+                target
+            )));
+        }
+        let target = build_file::absolute_label("", target.trim_start_matches('@'));
+        let target = target.trim_start_matches("//");
+        let (package, name) = match target.find(':') {
+            Some(idx) => (&target[..idx], &target[idx + 1..]),
+            None => (target, ""),
+        };
+
+        let package_dir = self.workspace_root.join(package);
+        let build_file = vec!["BUILD.bazel", "BUILD"]
+            .into_iter()
+            .map(|f| package_dir.join(f))
+            .find(|f| f.exists())
+            .ok_or_else(|| edit_error(format!("No BUILD file found in {:?}", package_dir)))?;
+        Ok((build_file, package.to_string(), name.to_string()))
+    }
+
+    fn apply_in_memory(
+        &self,
+        edit: &BuildozerEdit,
+        contents: &mut HashMap<PathBuf, (String, bool)>,
+        file_order: &mut Vec<(PathBuf, Vec<usize>)>,
+        idx: usize,
+    ) -> Result<()> {
+        let (path, package, name) = self.locate(edit.target())?;
+        if !contents.contains_key(&path) {
+            let content = std::fs::read_to_string(&path)?;
+            file_order.push((path.clone(), Vec::default()));
+            contents.insert(path.clone(), (content, false));
+        }
+        if let Some((_, edit_indices)) = file_order.iter_mut().find(|(p, _)| p == &path) {
+            edit_indices.push(idx);
+        }
+        let (content, changed) = contents.get_mut(&path).unwrap();
+        let updated = match edit {
+            BuildozerEdit::AddDependency { label, .. } => {
+                build_file::add_dependency(content, &package, &name, label)?
+            }
+            BuildozerEdit::RemoveDependency { label, .. } => {
+                build_file::remove_dependency(content, &package, &name, label)?
+            }
+        };
+        if let Some(updated) = updated {
+            *content = updated;
+            *changed = true;
+        }
+        Ok(())
+    }
+
+    async fn edit<F>(&self, target: &str, f: F) -> Result<()>
+    where
+        F: FnOnce(&str, &str, &str) -> Result<Option<String>>,
+    {
+        let (path, package, name) = self.locate(target)?;
+        let _guard = self.lock.lock().await;
+        let content = std::fs::read_to_string(&path)?;
+        if let Some(updated) = f(&content, &package, &name)? {
+            std::fs::write(&path, updated)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Buildozer for NativeBuildozer {
+    async fn print_deps(&self, label: &String) -> Result<Vec<String>> {
+        // Like buildozer, a target we can't find just has no deps.
+        let (path, package, name) = match self.locate(label) {
+            Ok(e) => e,
+            Err(e) => {
+                debug!("Unable to print deps for {}: {}", label, e.stderr);
+                return Ok(Vec::default());
+            }
+        };
+        let content = {
+            let _guard = self.lock.lock().await;
+            std::fs::read_to_string(&path)?
+        };
+        match build_file::print_deps(&content, &package, &name) {
+            Ok(deps) => Ok(deps),
+            Err(e) => {
+                debug!("Unable to print deps for {}: {}", label, e.stderr);
+                Ok(Vec::default())
+            }
+        }
+    }
+
+    async fn add_dependency(
+        &self,
+        target_to_operate_on: &String,
+        label_to_add: &String,
+    ) -> Result<()> {
+        self.edit(target_to_operate_on, |content, package, name| {
+            build_file::add_dependency(content, package, name, label_to_add)
+        })
+        .await
+    }
+
+    async fn remove_dependency(
+        &self,
+        target_to_operate_on: &String,
+        label_to_add: &String,
+    ) -> Result<()> {
+        self.edit(target_to_operate_on, |content, package, name| {
+            build_file::remove_dependency(content, package, name, label_to_add)
+        })
+        .await
+    }
+
+    // Applies every edit in memory first, so each BUILD file is read and written once.
+    // An edit which fails leaves the file as the other edits made it.
+    async fn apply_batch(&self, edits: &[BuildozerEdit]) -> Vec<Result<()>> {
+        let _guard = self.lock.lock().await;
+        let mut results: Vec<Result<()>> = Vec::default();
+        // The edits to each file, by their index in edits
+        let mut file_order: Vec<(PathBuf, Vec<usize>)> = Vec::default();
+        let mut contents: HashMap<PathBuf, (String, bool)> = HashMap::default();
+        for (idx, edit) in edits.iter().enumerate() {
+            results.push(self.apply_in_memory(edit, &mut contents, &mut file_order, idx));
+        }
+
+        for (path, edit_indices) in file_order.into_iter() {
+            let (content, changed) = &contents[&path];
+            if *changed {
+                if let Err(e) = std::fs::write(&path, content) {
+                    let e: super::ExecuteResultError = e.into();
+                    for idx in edit_indices.into_iter() {
+                        if results[idx].is_ok() {
+                            results[idx] = Err(e.clone());
+                        }
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_edits_build_file_in_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("src/main/java/com/example");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(
+            package_dir.join("BUILD.bazel"),
+            "java_library(\n    name = \"example\",\n    deps = [\n        \"//a:a\",\n    ],\n)\n",
+        )
+        .unwrap();
+
+        let buildozer = NativeBuildozer::new(dir.path().to_path_buf());
+        let target = String::from("//src/main/java/com/example");
+
+        buildozer
+            .add_dependency(&target, &String::from("//src/main/java/com/example:other"))
+            .await
+            .unwrap();
+        buildozer
+            .remove_dependency(&target, &String::from("//a:a"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(package_dir.join("BUILD.bazel")).unwrap(),
+            "java_library(\n    name = \"example\",\n    deps = [\n        \":other\",\n    ],\n)\n"
+        );
+        assert_eq!(
+            buildozer.print_deps(&target).await.unwrap(),
+            vec![String::from("//src/main/java/com/example:other")]
+        );
+
+        buildozer
+            .apply_batch(&[
+                BuildozerEdit::AddDependency {
+                    target: target.clone(),
+                    label: String::from("//b:b"),
+                },
+                BuildozerEdit::RemoveDependency {
+                    target: target.clone(),
+                    label: String::from(":other"),
+                },
+            ])
+            .await
+            .into_iter()
+            .collect::<Result<Vec<()>>>()
+            .unwrap();
+        assert_eq!(
+            buildozer.print_deps(&target).await.unwrap(),
+            vec![String::from("//b:b")]
+        );
+
+        assert!(buildozer
+            .add_dependency(
+                &String::from("@third_party//foo:bar"),
+                &String::from("//a:a")
+            )
+            .await
+            .is_err());
+        assert_eq!(
+            buildozer
+                .print_deps(&String::from("//does/not/exist:exist"))
+                .await
+                .unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_applies_edits_which_succeed() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("src/main/java/com/example");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(
+            package_dir.join("BUILD"),
+            "java_library(\n    name = \"example\",\n    deps = [],\n)\n",
+        )
+        .unwrap();
+
+        let buildozer = NativeBuildozer::new(dir.path().to_path_buf());
+        let results = buildozer
+            .apply_batch(&[
+                BuildozerEdit::AddDependency {
+                    target: String::from("//src/main/java/com/example:missing"),
+                    label: String::from("//a:a"),
+                },
+                BuildozerEdit::AddDependency {
+                    target: String::from("//src/main/java/com/example:example"),
+                    label: String::from("//b:b"),
+                },
+            ])
+            .await;
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+        assert_eq!(
+            buildozer
+                .print_deps(&String::from("//src/main/java/com/example:example"))
+                .await
+                .unwrap(),
+            vec![String::from("//b:b")]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::buildozer_driver::edit_plan::BuildozerEdit;
+
+// Strict deps checks in both rules_scala and java print the buildozer command that fixes
+// them, these are more reliable than anything we'd find in the index.
+//
+// Example usage:
+// SCALA:
+// error: Target '//src/main/scala/com/example/foo:foo' is used but isn't included as a direct dependency.
+// You can use the following buildozer command:
+// buildozer 'add deps //src/main/scala/com/example/foo:foo' //src/main/scala/com/example:example
+//
+// JAVA:
+//  ** You can use the following buildozer command:
+// buildozer 'add deps @com_google_protobuf//:protobuf_java_util' //src/main/java/com/example:example
+
+fn normalize_label(label: &str) -> String {
+    let label = label.trim_matches(|c| c == '\'' || c == '"');
+    match label.strip_prefix("@//") {
+        Some(rest) => format!("//{}", rest),
+        None => label.to_string(),
+    }
+}
+
+pub fn extract(input: &str) -> Vec<BuildozerEdit> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r#"buildozer\s+['"](add|remove) deps ([^'"]+)['"]\s+(\S+)"#).unwrap();
+    }
+
+    let mut result: Vec<BuildozerEdit> = Vec::default();
+    for captures in input.lines().flat_map(|ln| RE.captures(ln)) {
+        let target = normalize_label(captures.get(3).unwrap().as_str());
+        for label in captures.get(2).unwrap().as_str().split_whitespace() {
+            let label = normalize_label(label);
+            let edit = match captures.get(1).unwrap().as_str() {
+                "add" => BuildozerEdit::AddDependency {
+                    target: target.clone(),
+                    label,
+                },
+                _ => BuildozerEdit::RemoveDependency {
+                    target: target.clone(),
+                    label,
+                },
+            };
+            // The same command is suggested once per offending reference
+            if !result.contains(&edit) {
+                result.push(edit);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_scala_strict_deps_hint() {
+        let sample_output =
+            "error: Target '//src/main/scala/com/example/foo:foo' is used but isn't included as a direct dependency.
+This is an error since the strict dependency mode is enabled.
+    import com.example.foo.Foo
+                           ^
+You can use the following buildozer command:
+buildozer 'add deps //src/main/scala/com/example/foo:foo' //src/main/scala/com/example:example
+error: Target '//src/main/scala/com/example/foo:foo' is used but isn't included as a direct dependency.
+You can use the following buildozer command:
+buildozer 'add deps //src/main/scala/com/example/foo:foo' //src/main/scala/com/example:example
+two errors found
+";
+        assert_eq!(
+            extract(sample_output),
+            vec![BuildozerEdit::AddDependency {
+                target: String::from("//src/main/scala/com/example:example"),
+                label: String::from("//src/main/scala/com/example/foo:foo"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_java_strict_deps_hint() {
+        let sample_output =
+            "src/main/java/com/example/Example.java:3: error: [strict] Using type com.google.protobuf.util.JsonFormat.Printer from an indirect dependency (TOOL_INFO: \"@com_google_protobuf//:protobuf_java_util\"). See command below **
+  private static final Printer JSON_PRINTER =
+ ** Please add the following dependencies:
+  @com_google_protobuf//:protobuf_java_util @//src/main/java/com/example/bar to //src/main/java/com/example:example
+ ** You can use the following buildozer command:
+buildozer 'add deps @com_google_protobuf//:protobuf_java_util @//src/main/java/com/example/bar' //src/main/java/com/example:example
+";
+        assert_eq!(
+            extract(sample_output),
+            vec![
+                BuildozerEdit::AddDependency {
+                    target: String::from("//src/main/java/com/example:example"),
+                    label: String::from("@com_google_protobuf//:protobuf_java_util"),
+                },
+                BuildozerEdit::AddDependency {
+                    target: String::from("//src/main/java/com/example:example"),
+                    label: String::from("//src/main/java/com/example/bar"),
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_deps_hint() {
+        let sample_output = "error: Target '//src/main/scala/com/example/foo:foo' is specified as a dependency to //src/main/scala/com/example:example but isn't used, please remove it from the deps.
+You can use the following buildozer command:
+buildozer 'remove deps //src/main/scala/com/example/foo:foo' //src/main/scala/com/example:example
+";
+        assert_eq!(
+            extract(sample_output),
+            vec![BuildozerEdit::RemoveDependency {
+                target: String::from("//src/main/scala/com/example:example"),
+                label: String::from("//src/main/scala/com/example/foo:foo"),
+            }]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::CppHeaderImportRequest;
+
+// Example usage:
+// GCC:
+// src/main/cpp/com/example/foo.cc:3:10: fatal error: com/example/bar/bar.h: No such file or directory
+//
+// CLANG:
+// src/main/cpp/com/example/foo.cc:3:10: fatal error: 'com/example/bar/bar.h' file not found
+
+fn build_header_import_request(header_path: String) -> CppHeaderImportRequest {
+    CppHeaderImportRequest {
+        header_path,
+        src_fn: "header_not_found",
+        priority: 1,
+    }
+}
+
+pub(in crate::error_extraction) fn extract(input: &str) -> Option<Vec<CppHeaderImportRequest>> {
+    lazy_static! {
+        static ref GCC_RE: Regex =
+            Regex::new(r"^.*:\d+:\d+: fatal error: (.*): No such file or directory\s*$").unwrap();
+        static ref CLANG_RE: Regex =
+            Regex::new(r"^.*:\d+:\d+: fatal error: '(.*)' file not found\s*$").unwrap();
+    }
+
+    let mut result: Vec<CppHeaderImportRequest> = input
+        .lines()
+        .flat_map(|ln| GCC_RE.captures(ln).or_else(|| CLANG_RE.captures(ln)))
+        .map(|captures| build_header_import_request(captures.get(1).unwrap().as_str().to_string()))
+        .collect();
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_header_not_found_error() {
+        let sample_output =
+            "src/main/cpp/com/example/foo.cc:3:10: fatal error: com/example/bar/bar.h: No such file or directory
+    3 | #include \"com/example/bar/bar.h\"
+      |          ^~~~~~~~~~~~~~~~~~~~~~~
+compilation terminated.
+src/main/cpp/com/example/baz.cc:1:10: fatal error: 'absl/strings/str_cat.h' file not found
+#include \"absl/strings/str_cat.h\"
+         ^~~~~~~~~~~~~~~~~~~~~~~~
+1 error generated.
+";
+        assert_eq!(
+            extract(sample_output),
+            Some(vec![
+                build_header_import_request("absl/strings/str_cat.h".to_string()),
+                build_header_import_request("com/example/bar/bar.h".to_string()),
+            ])
+        );
+        assert_eq!(extract("compilation terminated."), None);
+    }
+}
@@ -0,0 +1,132 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::CppHeaderImportRequest;
+
+// Example usage:
+// BAZEL:
+// ERROR: /home/user/repo/src/main/cpp/com/example/BUILD:1:11: undeclared inclusion(s) in rule '//src/main/cpp/com/example:foo':
+// this rule is missing dependency declarations for the following files included by 'src/main/cpp/com/example/foo.cc':
+//   'src/main/cpp/com/example/bar/bar.h'
+//   'bazel-out/k8-fastbuild/bin/external/com_google_absl/absl/strings/_virtual_includes/strings/absl/strings/str_cat.h'
+
+lazy_static! {
+    static ref RULE_RE: Regex = Regex::new(r"undeclared inclusion\(s\) in rule '([^']+)':").unwrap();
+    static ref INCLUDED_BY_RE: Regex = Regex::new(
+        r"^\s*this rule is missing dependency declarations for the following files included by '(.*)':\s*$"
+    )
+    .unwrap();
+    static ref FILE_RE: Regex = Regex::new(r"^\s+'(.*)'\s*$").unwrap();
+}
+
+// Bazel reports these itself rather than through the output of the compile action, so they
+// are split out of its progress output along with the rules they're for.
+pub fn split_undeclared_inclusions(input: &str) -> Vec<(String, String)> {
+    let mut results = Vec::default();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    for ln in input.lines() {
+        if let Some(captures) = RULE_RE.captures(ln) {
+            results.extend(current.take());
+            current = Some((captures.get(1).unwrap().as_str().to_string(), vec![ln]));
+        } else if INCLUDED_BY_RE.is_match(ln) || FILE_RE.is_match(ln) {
+            if let Some((_, lines)) = current.as_mut() {
+                lines.push(ln);
+            }
+        } else {
+            results.extend(current.take());
+        }
+    }
+    results.extend(current);
+    results
+        .into_iter()
+        .map(|(label, lines)| (label, lines.join("\n")))
+        .collect()
+}
+
+// Headers bazel generates, or copies under _virtual_includes for strip_include_prefix and
+// include_prefix, are reported by where they're written rather than how they're included
+fn header_path(reported_path: &str) -> String {
+    lazy_static! {
+        static ref OUTPUT_RE: Regex =
+            Regex::new(r"^bazel-out/[^/]+/(?:bin|genfiles)/(.*)$").unwrap();
+        static ref VIRTUAL_INCLUDES_RE: Regex =
+            Regex::new(r"^(?:.*/)?_virtual_includes/[^/]+/(.*)$").unwrap();
+    }
+    let path = match OUTPUT_RE.captures(reported_path) {
+        Some(captures) => captures.get(1).unwrap().as_str(),
+        None => reported_path,
+    };
+    match VIRTUAL_INCLUDES_RE.captures(path) {
+        Some(captures) => captures.get(1).unwrap().as_str().to_string(),
+        None => path.to_string(),
+    }
+}
+
+fn build_header_import_request(header_path: String) -> CppHeaderImportRequest {
+    CppHeaderImportRequest {
+        header_path,
+        src_fn: "undeclared_inclusion",
+        priority: 1,
+    }
+}
+
+pub(in crate::error_extraction) fn extract(input: &str) -> Option<Vec<CppHeaderImportRequest>> {
+    let mut result: Vec<CppHeaderImportRequest> = Vec::default();
+    // The files follow the line saying which source included them
+    let mut in_file_list = false;
+    for ln in input.lines() {
+        if INCLUDED_BY_RE.is_match(ln) {
+            in_file_list = true;
+            continue;
+        }
+        match FILE_RE.captures(ln) {
+            Some(captures) if in_file_list => {
+                result.push(build_header_import_request(header_path(
+                    captures.get(1).unwrap().as_str(),
+                )));
+            }
+            _ => in_file_list = false,
+        }
+    }
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SAMPLE_OUTPUT: &str = "INFO: Analyzed target //src/main/cpp/com/example:foo (0 packages loaded, 0 targets configured).
+\x1b[31m\x1b[1mERROR: \x1b[0m/home/user/repo/src/main/cpp/com/example/BUILD:1:11: undeclared inclusion(s) in rule '//src/main/cpp/com/example:foo':
+this rule is missing dependency declarations for the following files included by 'src/main/cpp/com/example/foo.cc':
+  'src/main/cpp/com/example/bar/bar.h'
+  'bazel-out/k8-fastbuild/bin/external/com_google_absl/absl/strings/_virtual_includes/strings/absl/strings/str_cat.h'
+Target //src/main/cpp/com/example:foo failed to build
+";
+
+    #[test]
+    fn test_split_undeclared_inclusions() {
+        let results = split_undeclared_inclusions(SAMPLE_OUTPUT);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "//src/main/cpp/com/example:foo");
+        assert_eq!(results[0].1.lines().count(), 4);
+        assert_eq!(split_undeclared_inclusions("INFO: Build completed"), vec![]);
+    }
+
+    #[test]
+    fn test_undeclared_inclusion_error() {
+        assert_eq!(
+            extract(SAMPLE_OUTPUT),
+            Some(vec![
+                build_header_import_request("absl/strings/str_cat.h".to_string()),
+                build_header_import_request("src/main/cpp/com/example/bar/bar.h".to_string()),
+            ])
+        );
+        assert_eq!(extract("Target //foo:foo failed to build"), None);
+    }
+}
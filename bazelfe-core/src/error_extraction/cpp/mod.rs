@@ -0,0 +1,41 @@
+use crate::index_table::source_keys;
+
+mod error_header_not_found;
+mod error_undeclared_inclusion;
+
+pub use error_undeclared_inclusion::split_undeclared_inclusions;
+
+// Headers are looked up by the path they're included as, or for those bazel finds undeclared,
+// their path from the execution root
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct CppHeaderImportRequest {
+    pub header_path: String,
+    pub src_fn: &'static str,
+    pub priority: i32,
+}
+
+impl CppHeaderImportRequest {
+    pub fn to_class_import_request(self) -> super::ClassImportRequest {
+        super::ClassImportRequest {
+            class_name: format!("{}{}", source_keys::CC_KEY_PREFIX, self.header_path),
+            exact_only: true,
+            src_fn: format!("cpp::{}", self.src_fn),
+            priority: self.priority,
+        }
+    }
+}
+
+pub fn extract_errors(input: &str) -> Vec<super::ClassImportRequest> {
+    vec![
+        error_header_not_found::extract(input),
+        error_undeclared_inclusion::extract(input),
+    ]
+    .into_iter()
+    .flat_map(|e| e.into_iter().flat_map(|inner| inner.into_iter()))
+    .map(|o| o.to_class_import_request())
+    .collect()
+}
+
+pub fn extract_suffix_errors(_input: &str) -> Vec<super::ClassSuffixMatch> {
+    Vec::new()
+}
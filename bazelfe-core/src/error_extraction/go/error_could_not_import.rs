@@ -0,0 +1,64 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::GoImportRequest;
+
+// Example usage:
+// GO:
+// src/example/foo/foo.go:5:2: could not import example.com/bar (no such package)
+// src/example/foo/foo.go:6:2: cannot find package "example.com/baz" in any of:
+
+fn build_go_import_request(import_path: String) -> GoImportRequest {
+    GoImportRequest {
+        import_path,
+        src_fn: "could_not_import",
+        priority: 1,
+    }
+}
+
+pub(in crate::error_extraction) fn extract(input: &str) -> Option<Vec<GoImportRequest>> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^.*\.go:\d+:\d+: could not import (\S+) \(.*\)\s*$").unwrap();
+        static ref CANNOT_FIND_RE: Regex =
+            Regex::new(r#"^.*\.go:\d+:\d+: cannot find package "([^"]+)""#).unwrap();
+    }
+
+    let mut result: Vec<GoImportRequest> = input
+        .lines()
+        .flat_map(|ln| RE.captures(ln).or_else(|| CANNOT_FIND_RE.captures(ln)))
+        .map(|captures| build_go_import_request(captures.get(1).unwrap().as_str().to_string()))
+        .collect();
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_could_not_import_error() {
+        let sample_output =
+            "src/example/foo/foo.go:5:2: could not import example.com/bar (no such package)
+src/example/foo/foo.go:6:2: cannot find package \"example.com/baz\" in any of:
+\t/usr/local/go/src/example.com/baz (from $GOROOT)
+src/example/foo/foo.go:9:5: undefined: bar.Bar
+";
+        assert_eq!(
+            extract(sample_output),
+            Some(vec![
+                build_go_import_request("example.com/bar".to_string()),
+                build_go_import_request("example.com/baz".to_string()),
+            ])
+        );
+        assert_eq!(
+            extract("src/example/foo/foo.go:9:5: undefined: bar.Bar"),
+            None
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::GoImportRequest;
+
+// Example usage:
+// RULES_GO:
+// compilepkg: missing strict dependencies:
+// 	/home/user/.cache/bazel/.../execroot/__main__/src/example/foo/foo.go: import of "example.com/bar"
+// No dependencies were provided.
+// Check that imports in Go sources match importpath attributes in deps.
+
+fn build_go_import_request(import_path: String) -> GoImportRequest {
+    GoImportRequest {
+        import_path,
+        src_fn: "missing_strict_dependencies",
+        priority: 1,
+    }
+}
+
+pub(in crate::error_extraction) fn extract(input: &str) -> Option<Vec<GoImportRequest>> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r#"^\s*.*\.go: import of "([^"]+)"\s*$"#).unwrap();
+    }
+
+    let mut result: Vec<GoImportRequest> = input
+        .lines()
+        .flat_map(|ln| RE.captures(ln))
+        .map(|captures| build_go_import_request(captures.get(1).unwrap().as_str().to_string()))
+        .collect();
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_missing_strict_dependencies_error() {
+        let sample_output = "compilepkg: missing strict dependencies:
+\t/tmp/execroot/__main__/src/example/foo/foo.go: import of \"example.com/bar\"
+\t/tmp/execroot/__main__/src/example/foo/foo.go: import of \"github.com/pkg/errors\"
+No dependencies were provided.
+Check that imports in Go sources match importpath attributes in deps.
+";
+        assert_eq!(
+            extract(sample_output),
+            Some(vec![
+                build_go_import_request("example.com/bar".to_string()),
+                build_go_import_request("github.com/pkg/errors".to_string()),
+            ])
+        );
+        assert_eq!(extract("No dependencies were provided."), None);
+    }
+}
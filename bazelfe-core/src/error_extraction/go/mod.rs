@@ -0,0 +1,38 @@
+use crate::index_table::source_keys;
+
+mod error_could_not_import;
+mod error_missing_strict_dependencies;
+
+// Go packages are looked up by their import path, e.g. example.com/foo/bar
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct GoImportRequest {
+    pub import_path: String,
+    pub src_fn: &'static str,
+    pub priority: i32,
+}
+
+impl GoImportRequest {
+    pub fn to_class_import_request(self) -> super::ClassImportRequest {
+        super::ClassImportRequest {
+            class_name: format!("{}{}", source_keys::GO_KEY_PREFIX, self.import_path),
+            exact_only: true,
+            src_fn: format!("go::{}", self.src_fn),
+            priority: self.priority,
+        }
+    }
+}
+
+pub fn extract_errors(input: &str) -> Vec<super::ClassImportRequest> {
+    vec![
+        error_missing_strict_dependencies::extract(input),
+        error_could_not_import::extract(input),
+    ]
+    .into_iter()
+    .flat_map(|e| e.into_iter().flat_map(|inner| inner.into_iter()))
+    .map(|o| o.to_class_import_request())
+    .collect()
+}
+
+pub fn extract_suffix_errors(_input: &str) -> Vec<super::ClassSuffixMatch> {
+    Vec::new()
+}
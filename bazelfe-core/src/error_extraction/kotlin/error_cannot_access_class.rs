@@ -0,0 +1,55 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::super::ClassSuffixMatch;
+static SRC_FN: &str = "kotlin::error_cannot_access_class";
+
+// Example usage:
+// KOTLIN:
+// src/main/kotlin/com/example/foo/Example.kt:12:5: error: cannot access class 'com.example.bar.Baz'. Check your module classpath for missing or conflicting dependencies
+// or, from kotlinc directly:
+// e: src/main/kotlin/com/example/foo/Example.kt: (12, 5): Cannot access class 'Baz'. Check your module classpath for missing or conflicting dependencies
+
+pub fn extract(input: &str) -> Vec<ClassSuffixMatch> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^(?:e: )?(.*\.kts?).*[Cc]annot access class '([A-Za-z0-9.$_]+)'").unwrap();
+    }
+
+    let mut result = vec![];
+    for captures in input.lines().flat_map(|ln| RE.captures(ln)) {
+        result.push(ClassSuffixMatch {
+            suffix: captures.get(2).unwrap().as_str().replace('$', "."),
+            src_fn: String::from(SRC_FN),
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_cannot_access_class_error() {
+        let sample_output =
+            "src/main/kotlin/com/example/foo/Example.kt:12:5: error: cannot access class 'com.example.bar.Baz'. Check your module classpath for missing or conflicting dependencies
+    val baz = Foo.makeBaz()
+              ^
+e: src/main/kotlin/com/example/foo/Other.kt: (3, 9): Cannot access class 'Outer$Inner'. Check your module classpath for missing or conflicting dependencies
+";
+        assert_eq!(
+            extract(sample_output),
+            vec![
+                ClassSuffixMatch {
+                    suffix: String::from("com.example.bar.Baz"),
+                    src_fn: String::from(SRC_FN),
+                },
+                ClassSuffixMatch {
+                    suffix: String::from("Outer.Inner"),
+                    src_fn: String::from(SRC_FN),
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::source_dependencies::{ParsedFile, SelectorType};
+
+use super::KotlinClassImportRequest;
+
+// Example usage:
+// KOTLIN:
+// src/main/kotlin/com/example/foo/Example.kt:5:26: error: unresolved reference: Baz
+// import com.example.foo.bar.Baz
+//                            ^
+// or, from kotlinc directly:
+// e: src/main/kotlin/com/example/foo/Example.kt: (5, 26): Unresolved reference: Baz
+
+fn build_class_import_request(
+    source_file_name: String,
+    class_name: String,
+) -> KotlinClassImportRequest {
+    KotlinClassImportRequest {
+        src_file_name: source_file_name,
+        class_name,
+        exact_only: false,
+        src_fn: "unresolved_reference",
+        priority: 1,
+    }
+}
+
+fn build_class_import_request_low_priority(
+    source_file_name: String,
+    class_name: String,
+) -> KotlinClassImportRequest {
+    KotlinClassImportRequest {
+        src_file_name: source_file_name,
+        class_name,
+        exact_only: true,
+        src_fn: "unresolved_reference",
+        priority: -50,
+    }
+}
+
+// The reference is used somewhere in the body of the file, so it could come from any of
+// the imports or from the file's own package.
+fn extract_symbol_in_body(
+    missing_symbol: &str,
+    src_file_name: &str,
+    parsed_file: &ParsedFile,
+    result: &mut Vec<KotlinClassImportRequest>,
+) {
+    let mut packages: Vec<String> = Vec::new();
+    if let Some(ref package_name) = &parsed_file.package_name {
+        packages.push(format!("{}.{}", package_name, missing_symbol));
+    }
+
+    for import in parsed_file.imports.iter() {
+        if let SelectorType::WildcardSelector = import.suffix {
+            packages.push(format!("{}.{}", import.prefix_section, missing_symbol));
+        }
+    }
+
+    // this is a high priority one, if it matches we will ignore the others/clear them out.
+    for import in parsed_file.imports.iter() {
+        match import.suffix {
+            SelectorType::NoSelector => {
+                if import
+                    .prefix_section
+                    .strip_suffix(missing_symbol)
+                    .map(|e| e.ends_with('.'))
+                    .unwrap_or(false)
+                {
+                    packages.clear();
+                    packages.push(import.prefix_section.clone());
+                }
+            }
+            SelectorType::SelectorList(ref selectors) => {
+                for (name, alias) in selectors.iter() {
+                    if alias.as_deref() == Some(missing_symbol) {
+                        packages.clear();
+                        packages.push(format!("{}.{}", import.prefix_section, name));
+                    }
+                }
+            }
+            SelectorType::WildcardSelector => (),
+        }
+    }
+
+    for class_name in packages {
+        result.push(build_class_import_request_low_priority(
+            src_file_name.to_string(),
+            class_name,
+        ));
+    }
+}
+
+pub(in crate::error_extraction) fn extract(
+    input: &str,
+    file_parse_cache: &mut super::FileParseCache,
+) -> Option<Vec<KotlinClassImportRequest>> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^(.*\.kts?):(\d+):\d+: error: unresolved reference: (\w+)").unwrap();
+        static ref KOTLINC_RE: Regex =
+            Regex::new(r"^e: (.*\.kts?): \((\d+), \d+\): [Uu]nresolved reference: (\w+)").unwrap();
+    }
+
+    let mut result = Vec::default();
+    for captures in input
+        .lines()
+        .flat_map(|ln| RE.captures(ln).or_else(|| KOTLINC_RE.captures(ln)))
+    {
+        let src_file_name = captures.get(1).unwrap().as_str();
+        let src_line_number: u32 = captures.get(2).unwrap().as_str().parse().unwrap();
+        let missing_symbol = captures.get(3).unwrap().as_str();
+
+        if let Some(file_data) = file_parse_cache.load_file(src_file_name) {
+            match file_data
+                .imports
+                .iter()
+                .find(|e| e.line_number == src_line_number)
+            {
+                Some(import) => {
+                    let class_name = match import.suffix {
+                        SelectorType::SelectorList(ref selectors) => match selectors.first() {
+                            Some((name, _)) => format!("{}.{}", import.prefix_section, name),
+                            None => import.prefix_section.clone(),
+                        },
+                        _ => import.prefix_section.clone(),
+                    };
+                    result.push(build_class_import_request(
+                        src_file_name.to_string(),
+                        class_name,
+                    ));
+                }
+                None => {
+                    extract_symbol_in_body(missing_symbol, src_file_name, file_data, &mut result)
+                }
+            }
+        }
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::source_dependencies::Import;
+
+    fn file_cache(imports: Vec<Import>) -> super::super::FileParseCache {
+        super::super::FileParseCache::init_from_par(
+            String::from("src/main/kotlin/com/example/foo/Example.kt"),
+            ParsedFile {
+                package_name: Some(String::from("com.example.foo")),
+                imports,
+            },
+        )
+    }
+
+    #[test]
+    fn test_unresolved_reference_on_import() {
+        let mut file_cache = file_cache(vec![Import {
+            line_number: 3,
+            prefix_section: String::from("com.example.bar.Baz"),
+            suffix: SelectorType::NoSelector,
+        }]);
+        let sample_output =
+            "src/main/kotlin/com/example/foo/Example.kt:3:24: error: unresolved reference: bar
+import com.example.bar.Baz
+                   ^";
+        assert_eq!(
+            extract(sample_output, &mut file_cache),
+            Some(vec![build_class_import_request(
+                String::from("src/main/kotlin/com/example/foo/Example.kt"),
+                "com.example.bar.Baz".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_unresolved_reference_in_body() {
+        let mut file_cache = file_cache(vec![Import {
+            line_number: 3,
+            prefix_section: String::from("com.example.bar"),
+            suffix: SelectorType::WildcardSelector,
+        }]);
+        let sample_output =
+            "e: src/main/kotlin/com/example/foo/Example.kt: (12, 5): Unresolved reference: Baz";
+        assert_eq!(
+            extract(sample_output, &mut file_cache),
+            Some(vec![
+                build_class_import_request_low_priority(
+                    String::from("src/main/kotlin/com/example/foo/Example.kt"),
+                    "com.example.bar.Baz".to_string()
+                ),
+                build_class_import_request_low_priority(
+                    String::from("src/main/kotlin/com/example/foo/Example.kt"),
+                    "com.example.foo.Baz".to_string()
+                )
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unresolved_reference_to_aliased_import() {
+        let mut file_cache = file_cache(vec![
+            Import {
+                line_number: 3,
+                prefix_section: String::from("com.example.bar"),
+                suffix: SelectorType::WildcardSelector,
+            },
+            Import {
+                line_number: 4,
+                prefix_section: String::from("com.example.baz"),
+                suffix: SelectorType::SelectorList(vec![(
+                    String::from("RichDate"),
+                    Some(String::from("Date")),
+                )]),
+            },
+        ]);
+        let sample_output =
+            "src/main/kotlin/com/example/foo/Example.kt:12:5: error: unresolved reference: Date";
+        assert_eq!(
+            extract(sample_output, &mut file_cache),
+            Some(vec![build_class_import_request_low_priority(
+                String::from("src/main/kotlin/com/example/foo/Example.kt"),
+                "com.example.baz.RichDate".to_string()
+            )])
+        );
+    }
+}
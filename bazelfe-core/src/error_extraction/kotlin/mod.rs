@@ -0,0 +1,79 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::source_dependencies::ParsedFile;
+
+mod error_cannot_access_class;
+mod error_unresolved_reference;
+
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct KotlinClassImportRequest {
+    pub src_file_name: String,
+    pub class_name: String,
+    pub exact_only: bool,
+    pub src_fn: &'static str,
+    pub priority: i32,
+}
+
+impl KotlinClassImportRequest {
+    pub fn to_class_import_request(self) -> super::ClassImportRequest {
+        super::ClassImportRequest {
+            class_name: self.class_name,
+            exact_only: self.exact_only,
+            src_fn: format!("kotlin::{}", self.src_fn),
+            priority: self.priority,
+        }
+    }
+}
+
+fn do_load_file(path_str: &str) -> Option<ParsedFile> {
+    let path = Path::new(path_str);
+
+    if path.exists() {
+        let file_contents = std::fs::read_to_string(path).ok()?;
+        crate::source_dependencies::kotlin::parse_file(&file_contents).ok()
+    } else {
+        None
+    }
+}
+
+pub(in crate::error_extraction) struct FileParseCache {
+    file_parse_cache: HashMap<String, ParsedFile>,
+}
+impl FileParseCache {
+    pub fn new() -> Self {
+        Self {
+            file_parse_cache: HashMap::new(),
+        }
+    }
+    // used in tests
+    #[allow(dead_code)]
+    pub fn init_from_par(key: String, v: ParsedFile) -> Self {
+        let mut map = HashMap::new();
+        map.insert(key, v);
+        Self {
+            file_parse_cache: map,
+        }
+    }
+    pub fn load_file(&mut self, file_path: &str) -> Option<&ParsedFile> {
+        if !self.file_parse_cache.contains_key(file_path) {
+            if let Some(parsed_file) = do_load_file(file_path) {
+                self.file_parse_cache
+                    .insert(file_path.to_string(), parsed_file);
+            }
+        }
+        self.file_parse_cache.get(file_path)
+    }
+}
+
+pub fn extract_errors(input: &str) -> Vec<super::ClassImportRequest> {
+    let mut file_parse_cache: FileParseCache = FileParseCache::new();
+    error_unresolved_reference::extract(input, &mut file_parse_cache)
+        .into_iter()
+        .flat_map(|e| e.into_iter())
+        .map(|o| o.to_class_import_request())
+        .collect()
+}
+
+pub fn extract_suffix_errors(input: &str) -> Vec<super::ClassSuffixMatch> {
+    error_cannot_access_class::extract(input)
+}
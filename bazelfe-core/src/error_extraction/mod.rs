@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct ClassImportRequest {
     pub class_name: String,
     pub exact_only: bool,
@@ -6,37 +8,112 @@ pub struct ClassImportRequest {
     pub priority: i32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct ClassSuffixMatch {
     pub suffix: String,
     pub src_fn: String,
 }
 
+pub mod buildozer_hints;
+pub mod cpp;
+pub mod go;
 pub mod java;
+pub mod kotlin;
+pub mod proto;
+pub mod python;
+pub mod rust;
 pub mod scala;
 
-pub fn extract_errors(target_kind: &Option<String>, input: &str) -> Vec<ClassImportRequest> {
-    match target_kind.as_ref() {
+// Fixes suggested by the compiler itself, so they apply to every kind of rule
+pub fn extract_buildozer_hints(
+    input: &str,
+) -> Vec<crate::buildozer_driver::edit_plan::BuildozerEdit> {
+    buildozer_hints::extract(input)
+}
+
+// Whose compiler output the errors of a failed action are read as
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractorFamily {
+    Cpp,
+    Go,
+    Java,
+    Kotlin,
+    Proto,
+    Python,
+    Rust,
+    Scala,
+}
+
+impl ExtractorFamily {
+    // The families of the rules we know without any configuration
+    pub fn for_builtin_rule_kind(rule_kind: &str) -> Option<ExtractorFamily> {
+        match rule_kind {
+            "scala_library" => Some(ExtractorFamily::Scala),
+            "scala_test" => Some(ExtractorFamily::Scala),
+            "java_library" => Some(ExtractorFamily::Java),
+            "java_test" => Some(ExtractorFamily::Java),
+            "kt_jvm_library" => Some(ExtractorFamily::Kotlin),
+            "kt_jvm_test" => Some(ExtractorFamily::Kotlin),
+            "proto_library" => Some(ExtractorFamily::Proto),
+            "py_library" => Some(ExtractorFamily::Python),
+            "py_binary" => Some(ExtractorFamily::Python),
+            "py_test" => Some(ExtractorFamily::Python),
+            "cc_library" => Some(ExtractorFamily::Cpp),
+            "cc_binary" => Some(ExtractorFamily::Cpp),
+            "cc_test" => Some(ExtractorFamily::Cpp),
+            "go_library" => Some(ExtractorFamily::Go),
+            "go_binary" => Some(ExtractorFamily::Go),
+            "go_test" => Some(ExtractorFamily::Go),
+            "rust_library" => Some(ExtractorFamily::Rust),
+            "rust_binary" => Some(ExtractorFamily::Rust),
+            "rust_test" => Some(ExtractorFamily::Rust),
+            "rust_proc_macro" => Some(ExtractorFamily::Rust),
+            _ => None,
+        }
+    }
+
+    // Whether what's missing is named like a JVM class, so guessing a target from its
+    // package makes sense
+    pub fn is_jvm(&self) -> bool {
+        match self {
+            ExtractorFamily::Java | ExtractorFamily::Kotlin | ExtractorFamily::Scala => true,
+            ExtractorFamily::Cpp
+            | ExtractorFamily::Go
+            | ExtractorFamily::Proto
+            | ExtractorFamily::Python
+            | ExtractorFamily::Rust => false,
+        }
+    }
+}
+
+pub fn extract_errors(extractor: Option<ExtractorFamily>, input: &str) -> Vec<ClassImportRequest> {
+    match extractor {
         None => Vec::default(),
-        Some(kind) => match kind.as_ref() {
-            "scala_library" => scala::extract_errors(input),
-            "scala_test" => scala::extract_errors(input),
-            "java_library" => java::extract_errors(input),
-            "java_test" => java::extract_errors(input),
-            _ => Vec::default(),
-        },
+        Some(ExtractorFamily::Cpp) => cpp::extract_errors(input),
+        Some(ExtractorFamily::Go) => go::extract_errors(input),
+        Some(ExtractorFamily::Java) => java::extract_errors(input),
+        Some(ExtractorFamily::Kotlin) => kotlin::extract_errors(input),
+        Some(ExtractorFamily::Proto) => proto::extract_errors(input),
+        Some(ExtractorFamily::Python) => python::extract_errors(input),
+        Some(ExtractorFamily::Rust) => rust::extract_errors(input),
+        Some(ExtractorFamily::Scala) => scala::extract_errors(input),
     }
 }
 
-pub fn extract_suffix_errors(target_kind: &Option<String>, input: &str) -> Vec<ClassSuffixMatch> {
-    match target_kind.as_ref() {
+pub fn extract_suffix_errors(
+    extractor: Option<ExtractorFamily>,
+    input: &str,
+) -> Vec<ClassSuffixMatch> {
+    match extractor {
         None => Vec::default(),
-        Some(kind) => match kind.as_ref() {
-            "scala_library" => scala::extract_suffix_errors(input),
-            "scala_test" => scala::extract_suffix_errors(input),
-            "java_library" => java::extract_suffix_errors(input),
-            "java_test" => java::extract_suffix_errors(input),
-            _ => Vec::default(),
-        },
+        Some(ExtractorFamily::Cpp) => cpp::extract_suffix_errors(input),
+        Some(ExtractorFamily::Go) => go::extract_suffix_errors(input),
+        Some(ExtractorFamily::Java) => java::extract_suffix_errors(input),
+        Some(ExtractorFamily::Kotlin) => kotlin::extract_suffix_errors(input),
+        Some(ExtractorFamily::Proto) => proto::extract_suffix_errors(input),
+        Some(ExtractorFamily::Python) => python::extract_suffix_errors(input),
+        Some(ExtractorFamily::Rust) => rust::extract_suffix_errors(input),
+        Some(ExtractorFamily::Scala) => scala::extract_suffix_errors(input),
     }
 }
@@ -0,0 +1,63 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::ProtoImportRequest;
+
+// Example usage:
+// PROTO:
+// src/main/proto/com/example/foo.proto:5:1: Import "com/example/bar/bar.proto" was not found or had errors.
+// src/main/proto/com/example/foo.proto:9:3: "com.example.bar.Bar" is not defined.
+
+fn build_proto_import_request(import_path: String) -> ProtoImportRequest {
+    ProtoImportRequest {
+        import_path,
+        src_fn: "import_not_found",
+        priority: 1,
+    }
+}
+
+pub fn extract(input: &str) -> Option<Vec<ProtoImportRequest>> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r#"^.*\.proto:\d+:\d+: Import "(.*\.proto)" was not found or had errors\.?\s*$"#
+        )
+        .unwrap();
+    }
+
+    let mut result: Vec<ProtoImportRequest> = input
+        .lines()
+        .flat_map(|ln| RE.captures(ln))
+        .map(|captures| build_proto_import_request(captures.get(1).unwrap().as_str().to_string()))
+        .collect();
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_import_not_found_error() {
+        let sample_output =
+            "src/main/proto/com/example/foo.proto: warning: Import google/protobuf/empty.proto is unused.
+com/example/bar/bar.proto: File not found.
+src/main/proto/com/example/foo.proto:5:1: Import \"com/example/bar/bar.proto\" was not found or had errors.
+src/main/proto/com/example/foo.proto:9:3: \"com.example.bar.Bar\" is not defined.
+";
+        assert_eq!(
+            extract(sample_output),
+            Some(vec![build_proto_import_request(
+                "com/example/bar/bar.proto".to_string()
+            )])
+        );
+        assert_eq!(
+            extract("src/main/proto/com/example/foo.proto: File not found."),
+            None
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use crate::index_table::source_keys;
+
+mod error_import_not_found;
+
+// Protos are looked up by the path they're imported as, rather than a class name
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct ProtoImportRequest {
+    pub import_path: String,
+    pub src_fn: &'static str,
+    pub priority: i32,
+}
+
+impl ProtoImportRequest {
+    pub fn to_class_import_request(self) -> super::ClassImportRequest {
+        super::ClassImportRequest {
+            class_name: format!("{}{}", source_keys::PROTO_KEY_PREFIX, self.import_path),
+            exact_only: true,
+            src_fn: format!("proto::{}", self.src_fn),
+            priority: self.priority,
+        }
+    }
+}
+
+pub fn extract_errors(input: &str) -> Vec<super::ClassImportRequest> {
+    error_import_not_found::extract(input)
+        .into_iter()
+        .flat_map(|e| e.into_iter())
+        .map(|o| o.to_class_import_request())
+        .collect()
+}
+
+pub fn extract_suffix_errors(_input: &str) -> Vec<super::ClassSuffixMatch> {
+    Vec::new()
+}
@@ -0,0 +1,169 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::PythonModuleImportRequest;
+use crate::source_dependencies::Import;
+
+// Example usage:
+// PYTHON:
+// Traceback (most recent call last):
+//   File "/home/user/.cache/bazel/.../company/api/client_test.runfiles/__main__/company/api/client_test.py", line 3, in <module>
+//     import company.util.strings
+// ModuleNotFoundError: No module named 'company.util'
+//
+// PYTEST:
+// company/api/client_test.py:3: in <module>
+//     import company.util.strings
+// E   ModuleNotFoundError: No module named 'company.util'
+//
+// PYTHON 2:
+// ImportError: No module named util.strings
+
+fn build_module_import_request(module_name: String, priority: i32) -> PythonModuleImportRequest {
+    PythonModuleImportRequest {
+        module_name,
+        src_fn: "module_not_found",
+        priority,
+    }
+}
+
+// The module an import brings in, for imports that name it absolutely
+fn imported_module(import: &Import) -> Option<&str> {
+    if import.prefix_section.starts_with('.') {
+        None
+    } else {
+        Some(import.prefix_section.as_str())
+    }
+}
+
+// Python only reports the first package of an import it can't find, so from the import
+// itself we can look up the module that was wanted, rather than whichever library happens
+// to have files in the package.
+fn module_from_import(
+    missing_module: &str,
+    imports: &[Import],
+    src_line_number: u32,
+) -> Option<String> {
+    imports
+        .iter()
+        .filter(|e| e.line_number == src_line_number)
+        .flat_map(|e| imported_module(e))
+        .find(|module| {
+            *module == missing_module
+                || module
+                    .strip_prefix(missing_module)
+                    .map(|e| e.starts_with('.'))
+                    .unwrap_or(false)
+        })
+        .map(|e| e.to_string())
+}
+
+pub(in crate::error_extraction) fn extract(
+    input: &str,
+    file_parse_cache: &mut super::FileParseCache,
+) -> Option<Vec<PythonModuleImportRequest>> {
+    lazy_static! {
+        static ref FRAME_RE: Regex =
+            Regex::new(r#"^\s*File "(.*\.py)", line (\d+)"#).unwrap();
+        static ref PYTEST_FRAME_RE: Regex = Regex::new(r"^(\S*\.py):(\d+): in ").unwrap();
+        static ref RE: Regex = Regex::new(
+            r"^(?:E\s+)?(?:ModuleNotFoundError|ImportError): No module named '?([A-Za-z0-9_.]+)'?\s*$"
+        )
+        .unwrap();
+    }
+
+    let mut result: Vec<PythonModuleImportRequest> = Vec::default();
+    // The innermost frame of a traceback, the import that failed, comes just before the error
+    let mut last_frame: Option<(String, u32)> = None;
+    for ln in input.lines() {
+        if let Some(captures) = FRAME_RE
+            .captures(ln)
+            .or_else(|| PYTEST_FRAME_RE.captures(ln))
+        {
+            last_frame = captures
+                .get(2)
+                .unwrap()
+                .as_str()
+                .parse()
+                .ok()
+                .map(|line| (captures.get(1).unwrap().as_str().to_string(), line));
+            continue;
+        }
+        if let Some(captures) = RE.captures(ln) {
+            let missing_module = captures.get(1).unwrap().as_str();
+
+            let mut module_import_request = None;
+            if let Some((src_file_name, src_line_number)) = last_frame.take() {
+                if let Some(file_data) = file_parse_cache.load_file(&src_file_name) {
+                    module_import_request =
+                        module_from_import(missing_module, &file_data.imports, src_line_number)
+                            .map(|module| build_module_import_request(module, 30));
+                }
+            }
+
+            result.push(
+                module_import_request
+                    .unwrap_or_else(|| build_module_import_request(missing_module.to_string(), 2)),
+            );
+        }
+    }
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::source_dependencies::{ParsedFile, SelectorType};
+
+    #[test]
+    fn test_module_not_found_error() {
+        let sample_output = "Traceback (most recent call last):
+  File \"/tmp/client_test.runfiles/__main__/company/api/client_test.py\", line 3, in <module>
+    import company.util.strings
+ModuleNotFoundError: No module named 'company.util'
+";
+        let mut file_cache = super::super::FileParseCache::init_from_par(
+            String::from("/tmp/client_test.runfiles/__main__/company/api/client_test.py"),
+            ParsedFile {
+                package_name: None,
+                imports: vec![Import {
+                    line_number: 3,
+                    prefix_section: "company.util.strings".to_string(),
+                    suffix: SelectorType::NoSelector,
+                }],
+            },
+        );
+        assert_eq!(
+            extract(sample_output, &mut file_cache),
+            Some(vec![build_module_import_request(
+                "company.util.strings".to_string(),
+                30
+            )])
+        );
+    }
+
+    #[test]
+    fn test_module_not_found_error_without_source() {
+        let sample_output = "company/api/client_test.py:3: in <module>
+    from requests import adapters
+E   ModuleNotFoundError: No module named 'requests'
+ImportError: No module named yaml
+ImportError: cannot import name 'foo' from 'company.api'
+";
+        let mut file_cache = super::super::FileParseCache::new();
+        assert_eq!(
+            extract(sample_output, &mut file_cache),
+            Some(vec![
+                build_module_import_request("requests".to_string(), 2),
+                build_module_import_request("yaml".to_string(), 2),
+            ])
+        );
+        assert_eq!(extract("AssertionError: 1 != 2", &mut file_cache), None);
+    }
+}
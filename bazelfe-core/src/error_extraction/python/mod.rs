@@ -0,0 +1,79 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::index_table::source_keys;
+use crate::source_dependencies::ParsedFile;
+
+mod error_module_not_found;
+
+// Python modules are looked up by their dotted name, e.g. company.util.strings
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct PythonModuleImportRequest {
+    pub module_name: String,
+    pub src_fn: &'static str,
+    pub priority: i32,
+}
+
+impl PythonModuleImportRequest {
+    pub fn to_class_import_request(self) -> super::ClassImportRequest {
+        super::ClassImportRequest {
+            class_name: format!("{}{}", source_keys::PYTHON_KEY_PREFIX, self.module_name),
+            exact_only: true,
+            src_fn: format!("python::{}", self.src_fn),
+            priority: self.priority,
+        }
+    }
+}
+
+fn do_load_file(path_str: &str) -> Option<ParsedFile> {
+    let path = Path::new(path_str);
+
+    if path.exists() {
+        let file_contents = std::fs::read_to_string(path).ok()?;
+        crate::source_dependencies::python::parse_file(&file_contents).ok()
+    } else {
+        None
+    }
+}
+pub(in crate::error_extraction) struct FileParseCache {
+    file_parse_cache: HashMap<String, ParsedFile>,
+}
+impl FileParseCache {
+    pub fn new() -> Self {
+        Self {
+            file_parse_cache: HashMap::new(),
+        }
+    }
+    // used in tests
+    #[allow(dead_code)]
+    pub fn init_from_par(key: String, v: ParsedFile) -> Self {
+        let mut map = HashMap::new();
+        map.insert(key, v);
+        Self {
+            file_parse_cache: map,
+        }
+    }
+    pub fn load_file(&mut self, file_path: &str) -> Option<&ParsedFile> {
+        if !self.file_parse_cache.contains_key(file_path) {
+            if let Some(parsed_file) = do_load_file(file_path) {
+                self.file_parse_cache
+                    .insert(file_path.to_string(), parsed_file);
+            }
+        }
+        self.file_parse_cache.get(file_path)
+    }
+}
+
+// Python only finds out what's missing when the code runs, so these come from the logs of
+// failed tests rather than a compiler.
+pub fn extract_errors(input: &str) -> Vec<super::ClassImportRequest> {
+    let mut file_parse_cache: FileParseCache = FileParseCache::new();
+    error_module_not_found::extract(input, &mut file_parse_cache)
+        .into_iter()
+        .flat_map(|e| e.into_iter())
+        .map(|o| o.to_class_import_request())
+        .collect()
+}
+
+pub fn extract_suffix_errors(_input: &str) -> Vec<super::ClassSuffixMatch> {
+    Vec::new()
+}
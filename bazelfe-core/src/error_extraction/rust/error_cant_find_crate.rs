@@ -0,0 +1,64 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::RustCrateImportRequest;
+
+// Example usage:
+// RUST:
+// error[E0463]: can't find crate for `serde_json`
+//  --> src/main/rust/com/example/foo/lib.rs:1:1
+//   |
+// 1 | extern crate serde_json;
+//   | ^^^^^^^^^^^^^^^^^^^^^^^^ can't find crate
+
+fn build_crate_import_request(crate_name: String) -> RustCrateImportRequest {
+    RustCrateImportRequest {
+        crate_name,
+        src_fn: "cant_find_crate",
+        priority: 1,
+    }
+}
+
+pub(in crate::error_extraction) fn extract(input: &str) -> Option<Vec<RustCrateImportRequest>> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^error\[E0463\]: can't find crate for `([^`]+)`").unwrap();
+    }
+
+    let mut result: Vec<RustCrateImportRequest> = input
+        .lines()
+        .flat_map(|ln| RE.captures(ln))
+        .flat_map(|captures| super::external_crate_name(captures.get(1).unwrap().as_str()))
+        .map(|crate_name| build_crate_import_request(crate_name.to_string()))
+        .collect();
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_cant_find_crate_error() {
+        let sample_output = "error[E0463]: can't find crate for `serde_json`
+ --> src/main/rust/com/example/foo/lib.rs:1:1
+  |
+1 | extern crate serde_json;
+  | ^^^^^^^^^^^^^^^^^^^^^^^^ can't find crate
+
+error[E0463]: can't find crate for `std`
+  |
+  = note: the `wasm32-unknown-unknown` target may not be installed
+";
+        assert_eq!(
+            extract(sample_output),
+            Some(vec![build_crate_import_request("serde_json".to_string())])
+        );
+        assert_eq!(extract("error: aborting due to previous error"), None);
+    }
+}
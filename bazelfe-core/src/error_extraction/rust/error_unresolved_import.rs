@@ -0,0 +1,72 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::RustCrateImportRequest;
+
+// Example usage:
+// RUST:
+// error[E0432]: unresolved import `serde_json`
+//  --> src/main/rust/com/example/foo/lib.rs:1:5
+//   |
+// 1 | use serde_json::Value;
+//   |     ^^^^^^^^^^ use of undeclared crate or module `serde_json`
+
+fn build_crate_import_request(crate_name: String) -> RustCrateImportRequest {
+    RustCrateImportRequest {
+        crate_name,
+        src_fn: "unresolved_import",
+        priority: 1,
+    }
+}
+
+pub(in crate::error_extraction) fn extract(input: &str) -> Option<Vec<RustCrateImportRequest>> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^error\[E0432\]: unresolved imports? `([^`]+)`").unwrap();
+    }
+
+    let mut result: Vec<RustCrateImportRequest> = input
+        .lines()
+        .flat_map(|ln| RE.captures(ln))
+        .flat_map(|captures| super::external_crate_name(captures.get(1).unwrap().as_str()))
+        .map(|crate_name| build_crate_import_request(crate_name.to_string()))
+        .collect();
+    if result.is_empty() {
+        return None;
+    }
+    result.sort();
+    result.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[test]
+    fn test_unresolved_import_error() {
+        let sample_output = "error[E0432]: unresolved import `serde_json`
+ --> src/main/rust/com/example/foo/lib.rs:1:5
+  |
+1 | use serde_json::Value;
+  |     ^^^^^^^^^^ use of undeclared crate or module `serde_json`
+
+error[E0432]: unresolved import `crate::bar`
+ --> src/main/rust/com/example/foo/lib.rs:2:5
+  |
+2 | use crate::bar;
+  |     ^^^^^^^^^^ no `bar` in the root
+
+error[E0432]: unresolved import `tokio::sync::mpsc`
+ --> src/main/rust/com/example/foo/lib.rs:3:5
+";
+        assert_eq!(
+            extract(sample_output),
+            Some(vec![
+                build_crate_import_request("serde_json".to_string()),
+                build_crate_import_request("tokio".to_string()),
+            ])
+        );
+        assert_eq!(extract("error: aborting due to previous error"), None);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::index_table::source_keys;
+
+mod error_cant_find_crate;
+mod error_unresolved_import;
+
+// Crates are looked up by the name they're used as, e.g. serde_json
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct RustCrateImportRequest {
+    pub crate_name: String,
+    pub src_fn: &'static str,
+    pub priority: i32,
+}
+
+impl RustCrateImportRequest {
+    pub fn to_class_import_request(self) -> super::ClassImportRequest {
+        super::ClassImportRequest {
+            class_name: format!("{}{}", source_keys::RUST_KEY_PREFIX, self.crate_name),
+            exact_only: true,
+            src_fn: format!("rust::{}", self.src_fn),
+            priority: self.priority,
+        }
+    }
+}
+
+// The first segment of a path that names another crate, rather than somewhere in this one
+// or a crate that comes with the compiler
+fn external_crate_name(path: &str) -> Option<&str> {
+    let crate_name = path.trim_start_matches("::").split("::").next()?;
+    match crate_name {
+        "" | "crate" | "self" | "super" | "std" | "core" | "alloc" | "proc_macro" | "test" => None,
+        crate_name => Some(crate_name),
+    }
+}
+
+pub fn extract_errors(input: &str) -> Vec<super::ClassImportRequest> {
+    vec![
+        error_unresolved_import::extract(input),
+        error_cant_find_crate::extract(input),
+    ]
+    .into_iter()
+    .flat_map(|e| e.into_iter().flat_map(|inner| inner.into_iter()))
+    .map(|o| o.to_class_import_request())
+    .collect()
+}
+
+pub fn extract_suffix_errors(_input: &str) -> Vec<super::ClassSuffixMatch> {
+    Vec::new()
+}
@@ -0,0 +1,30 @@
+use clap::Clap;
+use std::error::Error;
+use std::fs;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use bazelfe_core::index_table::parse_file;
+
+#[derive(Clap, Debug)]
+#[clap(name = "basic")]
+struct Opt {
+    /// Text index, as written by the jvm-indexer
+    #[clap(long, parse(from_os_str))]
+    input: PathBuf,
+
+    /// Where to write the binary, memory mappable, index
+    #[clap(long, parse(from_os_str))]
+    output: PathBuf,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opt = Opt::parse();
+
+    let content = fs::read_to_string(&opt.input)?;
+    let parsed_file = parse_file(&content)?;
+
+    let mut writer = BufWriter::new(fs::File::create(&opt.output)?);
+    parsed_file.write_binary(&mut writer)?;
+    Ok(())
+}
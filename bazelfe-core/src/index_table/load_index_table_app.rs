@@ -1,9 +1,8 @@
 use clap::Clap;
 use std::error::Error;
-use std::fs;
 use std::path::PathBuf;
 
-use bazelfe_core::index_table::parse_file;
+use bazelfe_core::index_table::load_file;
 
 #[derive(Clap, Debug)]
 #[clap(name = "basic")]
@@ -17,9 +16,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let opt = Opt::parse();
 
     for f in opt.files.iter() {
-        let content = fs::read_to_string(f)?;
-
-        let _parsed_file = parse_file(&content).unwrap();
+        let _parsed_file = load_file(f).unwrap();
     }
     Ok(())
 }
@@ -0,0 +1,329 @@
+// A compact on-disk form of the index, which is memory mapped rather than parsed.
+//
+// Layout, all integers little endian:
+//   magic                      8 bytes
+//   label_count, key_count, value_count, strings_len   u32 each
+//   labels: label_count * (u32 string offset, u32 length)
+//   keys:   key_count * (u32 string offset, u32 length, u32 first value, u32 value count)
+//           sorted by key bytes
+//   suffix order: key_count * u32 key index, sorted by the reversed key bytes
+//   values: value_count * (u32 label index, u16 frequency, u16 padding)
+//   strings: utf-8 blob holding every key and (interned) label
+
+use memmap::Mmap;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const MAGIC_PREFIX: &[u8; 7] = b"BZFEIDX";
+pub const MAGIC: &[u8; 8] = b"BZFEIDX2";
+const HEADER_LEN: usize = 8 + 4 * 4;
+const LABEL_ENTRY_LEN: usize = 8;
+const KEY_ENTRY_LEN: usize = 16;
+const SUFFIX_ENTRY_LEN: usize = 4;
+const VALUE_ENTRY_LEN: usize = 8;
+
+#[derive(Debug)]
+pub struct MappedIndex {
+    mmap: Mmap,
+    label_count: usize,
+    key_count: usize,
+    labels_start: usize,
+    keys_start: usize,
+    suffix_order_start: usize,
+    values_start: usize,
+    strings_start: usize,
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&data[pos..pos + 4]);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u16(data: &[u8], pos: usize) -> u16 {
+    let mut buf = [0u8; 2];
+    buf.copy_from_slice(&data[pos..pos + 2]);
+    u16::from_le_bytes(buf)
+}
+
+pub fn is_binary_index(path: &Path) -> bool {
+    let mut buf = [0u8; 8];
+    match File::open(path).and_then(|mut f| f.read_exact(&mut buf)) {
+        Ok(_) => &buf[0..7] == MAGIC_PREFIX,
+        Err(_) => false,
+    }
+}
+
+impl MappedIndex {
+    pub fn open(path: &Path) -> Result<MappedIndex, Box<dyn Error>> {
+        let file = File::open(path)?;
+        // The index files are written once by the indexer and then only read.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[0..7] != MAGIC_PREFIX {
+            return Err(format!("{:?} is not a binary index file", path).into());
+        }
+        if &mmap[0..8] != MAGIC {
+            return Err(format!(
+                "{:?} was written by a different version, please regenerate it",
+                path
+            )
+            .into());
+        }
+        let label_count = read_u32(&mmap, 8) as usize;
+        let key_count = read_u32(&mmap, 12) as usize;
+        let value_count = read_u32(&mmap, 16) as usize;
+        let strings_len = read_u32(&mmap, 20) as usize;
+
+        let labels_start = HEADER_LEN;
+        let keys_start = labels_start + label_count * LABEL_ENTRY_LEN;
+        let suffix_order_start = keys_start + key_count * KEY_ENTRY_LEN;
+        let values_start = suffix_order_start + key_count * SUFFIX_ENTRY_LEN;
+        let strings_start = values_start + value_count * VALUE_ENTRY_LEN;
+        if mmap.len() != strings_start + strings_len {
+            return Err(format!("Binary index file {:?} is truncated", path).into());
+        }
+
+        Ok(MappedIndex {
+            mmap,
+            label_count,
+            key_count,
+            labels_start,
+            keys_start,
+            suffix_order_start,
+            values_start,
+            strings_start,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.key_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.key_count == 0
+    }
+
+    fn string_at(&self, entry_pos: usize) -> &str {
+        let offset = read_u32(&self.mmap, entry_pos) as usize;
+        let len = read_u32(&self.mmap, entry_pos + 4) as usize;
+        let start = self.strings_start + offset;
+        std::str::from_utf8(&self.mmap[start..start + len]).unwrap_or("")
+    }
+
+    fn label(&self, idx: usize) -> &str {
+        debug_assert!(idx < self.label_count);
+        self.string_at(self.labels_start + idx * LABEL_ENTRY_LEN)
+    }
+
+    pub fn key(&self, idx: usize) -> &str {
+        self.string_at(self.keys_start + idx * KEY_ENTRY_LEN)
+    }
+
+    pub fn values(&self, idx: usize) -> Vec<(u16, String)> {
+        let entry_pos = self.keys_start + idx * KEY_ENTRY_LEN;
+        let first = read_u32(&self.mmap, entry_pos + 8) as usize;
+        let count = read_u32(&self.mmap, entry_pos + 12) as usize;
+        (first..first + count)
+            .map(|value_idx| {
+                let pos = self.values_start + value_idx * VALUE_ENTRY_LEN;
+                let label_idx = read_u32(&self.mmap, pos) as usize;
+                let freq = read_u16(&self.mmap, pos + 4);
+                (freq, self.label(label_idx).to_string())
+            })
+            .collect()
+    }
+
+    pub fn find(&self, key: &str) -> Option<usize> {
+        let mut low = 0;
+        let mut high = self.key_count;
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.key(mid).cmp(key) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<(u16, String)>> {
+        self.find(key).map(|idx| self.values(idx))
+    }
+
+    // Indices of every key ending with the given suffix
+    pub fn find_suffix(&self, suffix: &str) -> Vec<usize> {
+        let key_for = |order_idx: usize| -> usize {
+            read_u32(
+                &self.mmap,
+                self.suffix_order_start + order_idx * SUFFIX_ENTRY_LEN,
+            ) as usize
+        };
+        let start = super::reversed_lower_bound(self.key_count, suffix, |order_idx| {
+            self.key(key_for(order_idx))
+        });
+        (start..self.key_count)
+            .map(key_for)
+            .take_while(|idx| self.key(*idx).ends_with(suffix))
+            .filter(|idx| !super::source_keys::is_source_index_key(self.key(*idx)))
+            .collect()
+    }
+}
+
+pub fn write_index<W: Write>(
+    tbl_map: &HashMap<String, Vec<(u16, String)>>,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let mut strings: Vec<u8> = Vec::default();
+    let mut push_string = |s: &str| -> (u32, u32) {
+        let offset = strings.len() as u32;
+        strings.extend_from_slice(s.as_bytes());
+        (offset, s.len() as u32)
+    };
+
+    let mut keys: Vec<&String> = tbl_map.keys().collect();
+    keys.sort();
+
+    let mut label_ids: HashMap<&str, u32> = HashMap::default();
+    let mut labels: Vec<(u32, u32)> = Vec::default();
+    let mut key_entries: Vec<(u32, u32, u32, u32)> = Vec::default();
+    let mut values: Vec<(u32, u16)> = Vec::default();
+
+    for key in keys.into_iter() {
+        let (key_offset, key_len) = push_string(key);
+        let first_value = values.len() as u32;
+        for (freq, label) in tbl_map[key].iter() {
+            let label_id = match label_ids.get(label.as_str()) {
+                Some(id) => *id,
+                None => {
+                    let id = labels.len() as u32;
+                    labels.push(push_string(label));
+                    label_ids.insert(label, id);
+                    id
+                }
+            };
+            values.push((label_id, *freq));
+        }
+        key_entries.push((
+            key_offset,
+            key_len,
+            first_value,
+            values.len() as u32 - first_value,
+        ));
+    }
+
+    let mut suffix_order: Vec<u32> = (0..key_entries.len() as u32).collect();
+    {
+        let key_str = |idx: u32| -> &[u8] {
+            let (offset, len, _, _) = key_entries[idx as usize];
+            &strings[offset as usize..(offset + len) as usize]
+        };
+        suffix_order.sort_by(|a, b| key_str(*a).iter().rev().cmp(key_str(*b).iter().rev()));
+    }
+
+    writer.write_all(MAGIC)?;
+    for v in [
+        labels.len() as u32,
+        key_entries.len() as u32,
+        values.len() as u32,
+        strings.len() as u32,
+    ]
+    .iter()
+    {
+        writer.write_all(&v.to_le_bytes())?;
+    }
+    for (offset, len) in labels.iter() {
+        writer.write_all(&offset.to_le_bytes())?;
+        writer.write_all(&len.to_le_bytes())?;
+    }
+    for (offset, len, first_value, value_count) in key_entries.iter() {
+        writer.write_all(&offset.to_le_bytes())?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&first_value.to_le_bytes())?;
+        writer.write_all(&value_count.to_le_bytes())?;
+    }
+    for key_idx in suffix_order.iter() {
+        writer.write_all(&key_idx.to_le_bytes())?;
+    }
+    for (label_id, freq) in values.iter() {
+        writer.write_all(&label_id.to_le_bytes())?;
+        writer.write_all(&freq.to_le_bytes())?;
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    writer.write_all(&strings)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut tbl_map = HashMap::new();
+        tbl_map.insert(
+            String::from("javax.annotation.Nullable"),
+            vec![
+                (
+                    236,
+                    String::from("@third_party_jvm//3rdparty/jvm/com/google/code/findbugs:jsr305"),
+                ),
+                (
+                    75,
+                    String::from(
+                        "@third_party_jvm//3rdparty/jvm/com/google/code/findbugs:annotations",
+                    ),
+                ),
+            ],
+        );
+        tbl_map.insert(
+            String::from("javax.annotation.Nonnull"),
+            vec![(
+                236,
+                String::from("@third_party_jvm//3rdparty/jvm/com/google/code/findbugs:jsr305"),
+            )],
+        );
+        tbl_map.insert(
+            String::from("com.example.Foo"),
+            vec![(0, String::from("//src/main/java/com/example:example"))],
+        );
+        tbl_map.insert(
+            String::from("py:company.Foo"),
+            vec![(0, String::from("//company:company"))],
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.bin");
+        let mut file = File::create(&path).unwrap();
+        write_index(&tbl_map, &mut file).unwrap();
+        drop(file);
+
+        assert!(is_binary_index(&path));
+        let mapped = MappedIndex::open(&path).unwrap();
+        assert_eq!(mapped.len(), 4);
+        // Keys are sorted, labels are interned
+        assert_eq!(mapped.key(0), "com.example.Foo");
+        assert_eq!(mapped.label_count, 4);
+
+        for (k, v) in tbl_map.iter() {
+            assert_eq!(mapped.get(k).as_ref(), Some(v));
+        }
+        assert_eq!(mapped.get("javax.annotation"), None);
+        assert_eq!(mapped.get("zzz"), None);
+
+        let mut matches: Vec<&str> = mapped
+            .find_suffix("annotation.Nullable")
+            .into_iter()
+            .map(|idx| mapped.key(idx))
+            .collect();
+        matches.sort();
+        assert_eq!(matches, vec!["javax.annotation.Nullable"]);
+        assert_eq!(mapped.find_suffix("Nonnull").len(), 1);
+        assert_eq!(mapped.find_suffix("").len(), 3);
+        assert_eq!(mapped.find_suffix("Foo").len(), 1);
+        assert!(mapped.find_suffix("Bar").is_empty());
+    }
+}
@@ -4,16 +4,45 @@ use nom::character::complete::line_ending;
 use nom::error::ParseError;
 use nom::multi::{many0, many1};
 use nom::{bytes::complete::tag, combinator::map, combinator::opt, sequence::tuple, IResult};
+use std::path::Path;
+use std::sync::Arc;
 use std::{collections::HashMap, collections::HashSet, error::Error};
 
+pub mod mapped;
+pub mod source_keys;
+
 #[derive(Clone, Debug)]
 pub struct IndexTable {
     tbl_map: HashMap<String, Vec<(u16, String)>>,
+    // Keys of tbl_map, sorted by their reversed bytes to answer suffix queries
+    suffix_index: Vec<String>,
+    mapped: Option<Arc<mapped::MappedIndex>>,
+}
+
+// The first position in a list sorted by reversed key whose key, reversed, is not less than the reversed suffix.
+// All the keys ending with the suffix follow on from here.
+fn reversed_lower_bound<'a, F>(len: usize, suffix: &str, key_at: F) -> usize
+where
+    F: Fn(usize) -> &'a str,
+{
+    let mut low = 0;
+    let mut high = len;
+    while low < high {
+        let mid = (low + high) / 2;
+        if key_at(mid).bytes().rev().lt(suffix.bytes().rev()) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
 }
 impl Default for IndexTable {
     fn default() -> Self {
         Self {
             tbl_map: HashMap::default(),
+            suffix_index: Vec::default(),
+            mapped: None,
         }
     }
 }
@@ -21,35 +50,175 @@ impl IndexTable {
     pub fn new() -> Self {
         Self {
             tbl_map: HashMap::new(),
+            suffix_index: Vec::default(),
+            mapped: None,
         }
     }
 
     pub fn from_hashmap(m: HashMap<String, Vec<(u16, String)>>) -> Self {
-        Self { tbl_map: m }
+        let mut suffix_index: Vec<String> = m.keys().cloned().collect();
+        suffix_index.sort_by(|a, b| a.bytes().rev().cmp(b.bytes().rev()));
+        Self {
+            tbl_map: m,
+            suffix_index,
+            mapped: None,
+        }
+    }
+
+    pub fn from_mapped(m: mapped::MappedIndex) -> Self {
+        Self {
+            tbl_map: HashMap::new(),
+            suffix_index: Vec::default(),
+            mapped: Some(Arc::new(m)),
+        }
     }
 
-    pub fn get<S>(&self, key: S) -> Option<&Vec<(u16, String)>>
+    pub fn get<S>(&self, key: S) -> Option<Vec<(u16, String)>>
     where
         S: Into<String>,
     {
-        self.tbl_map.get(&key.into())
+        let key = key.into();
+        match self.tbl_map.get(&key) {
+            Some(v) => Some(v.clone()),
+            None => self.mapped.as_ref().and_then(|m| m.get(&key)),
+        }
     }
 
+    // Suffixes are only looked up for JVM classes, so the source indexed keys are skipped
     pub fn get_from_suffix<S>(&self, key: S) -> Vec<(u16, String)>
     where
         S: Into<String>,
     {
         let passed_k = key.into();
         let mut result: HashSet<(u16, String)> = HashSet::default();
-        for (k, v) in self.tbl_map.iter() {
-            if k.ends_with(&passed_k) {
-                for e in v {
-                    result.insert(e.clone());
-                }
+        let start = reversed_lower_bound(self.suffix_index.len(), &passed_k, |idx| {
+            &self.suffix_index[idx]
+        });
+        for k in self.suffix_index[start..]
+            .iter()
+            .take_while(|k| k.ends_with(&passed_k))
+            .filter(|k| !source_keys::is_source_index_key(k))
+        {
+            for e in &self.tbl_map[k] {
+                result.insert(e.clone());
+            }
+        }
+        if let Some(m) = &self.mapped {
+            for idx in m.find_suffix(&passed_k) {
+                result.extend(m.values(idx));
             }
         }
         result.into_iter().collect()
     }
+
+    // Records that the target with this label provides these classes, returning true if
+    // the table changed. Labels new to a class are added with no popularity.
+    pub fn add_target_classes(&mut self, label: &str, classes: &[String]) -> bool {
+        let mut changed = false;
+        let mut new_keys: Vec<String> = Vec::default();
+        for class_name in classes.iter() {
+            if !self.tbl_map.contains_key(class_name) {
+                let existing = self
+                    .mapped
+                    .as_ref()
+                    .and_then(|m| m.get(class_name))
+                    .unwrap_or_default();
+                new_keys.push(class_name.clone());
+                self.tbl_map.insert(class_name.clone(), existing);
+            }
+            let entries = self.tbl_map.get_mut(class_name).unwrap();
+            if !entries.iter().any(|(_, l)| l == label) {
+                entries.push((0, label.to_string()));
+                changed = true;
+            }
+        }
+        self.merge_into_suffix_index(new_keys);
+        changed
+    }
+
+    // Adds keys to the suffix index with a single merge, rather than shifting the index
+    // along for every key
+    fn merge_into_suffix_index(&mut self, mut new_keys: Vec<String>) {
+        if new_keys.is_empty() {
+            return;
+        }
+        new_keys.sort_by(|a, b| a.bytes().rev().cmp(b.bytes().rev()));
+        let existing = std::mem::take(&mut self.suffix_index);
+        let mut merged = Vec::with_capacity(existing.len() + new_keys.len());
+        let mut existing = existing.into_iter().peekable();
+        for key in new_keys.into_iter() {
+            while let Some(e) = existing.peek() {
+                if !e.bytes().rev().lt(key.bytes().rev()) {
+                    break;
+                }
+                merged.push(existing.next().unwrap());
+            }
+            merged.push(key);
+        }
+        merged.extend(existing);
+        self.suffix_index = merged;
+    }
+
+    // Every entry in the table, regardless of how it's backed
+    pub fn to_hashmap(&self) -> HashMap<String, Vec<(u16, String)>> {
+        let mut merged: HashMap<String, Vec<(u16, String)>> = HashMap::new();
+        if let Some(m) = &self.mapped {
+            for idx in 0..m.len() {
+                merged.insert(m.key(idx).to_string(), m.values(idx));
+            }
+        }
+        for (k, v) in self.tbl_map.iter() {
+            merged.insert(k.clone(), v.clone());
+        }
+        merged
+    }
+
+    // Writes the table out in the binary format, see `mapped`
+    pub fn write_binary<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        mapped::write_index(&self.to_hashmap(), writer)
+    }
+
+    // Writes the table out in the tab separated text format the jvm-indexer produces
+    pub fn write_text<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut entries: Vec<(String, Vec<(u16, String)>)> =
+            self.to_hashmap().into_iter().collect();
+        entries.sort();
+        for (k, mut v) in entries.into_iter() {
+            v.sort_by_key(|e| std::cmp::Reverse(e.0));
+            let values: Vec<String> = v
+                .into_iter()
+                .map(|(freq, label)| format!("{}:{}", freq, label))
+                .collect();
+            writeln!(writer, "{}\t{}", k, values.join(","))?;
+        }
+        Ok(())
+    }
+
+    // Writes the table back to the path it was loaded from, keeping that file's format.
+    pub fn persist(&self, path: &Path) -> std::io::Result<()> {
+        write_replacing(path, |file| {
+            if mapped::is_binary_index(path) {
+                self.write_binary(file)
+            } else {
+                self.write_text(file)
+            }
+        })
+    }
+}
+
+// Writes an index to a temporary file then moves it into place, so any existing mapping of
+// the file stays intact and a failed write leaves the previous index as it was.
+pub fn write_replacing<F>(path: &Path, write: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut std::io::BufWriter<std::fs::File>) -> std::io::Result<()>,
+{
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+        write(&mut file)?;
+        std::io::Write::flush(&mut file)?;
+    }
+    std::fs::rename(&tmp_path, path)
 }
 fn element_extractor<'a, E>() -> impl Fn(&'a str) -> IResult<&str, (u16, &str), E>
 where
@@ -87,6 +256,17 @@ fn parse_file_e(input: &str) -> IResult<&str, Vec<(String, Vec<(u16, String)>)>>
     many0(map(tuple((parse_index_line(), opt(line_ending))), |e| e.0))(input)
 }
 
+// Loads an index from disk, memory mapping it when it's in the binary format
+// and falling back to parsing the text format otherwise.
+pub fn load_file(path: &Path) -> Result<IndexTable, Box<dyn Error>> {
+    if mapped::is_binary_index(path) {
+        Ok(IndexTable::from_mapped(mapped::MappedIndex::open(path)?))
+    } else {
+        let content = std::fs::read_to_string(path)?;
+        parse_file(&content)
+    }
+}
+
 pub fn parse_file(input: &str) -> Result<IndexTable, Box<dyn Error>> {
     debug!("Start parsing");
 
@@ -99,9 +279,7 @@ pub fn parse_file(input: &str) -> Result<IndexTable, Box<dyn Error>> {
     for (k, v) in extracted_result.into_iter() {
         index_data.insert(k, v);
     }
-    Ok(IndexTable {
-        tbl_map: index_data,
-    })
+    Ok(IndexTable::from_hashmap(index_data))
 }
 
 #[cfg(test)]
@@ -152,6 +330,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_from_suffix() {
+        let parsed_file = parse_file(
+            "com.example.foo.Bar\t1:@third_party_jvm//3rdparty/jvm/com/example:foo
+com.example.foo.Baz\t1:@third_party_jvm//3rdparty/jvm/com/example:foo
+com.example.other.Bar\t3:@third_party_jvm//3rdparty/jvm/com/example:other
+com.example.FooBar\t2:@third_party_jvm//3rdparty/jvm/com/example:foo_bar
+py:company.util.Bar\t0://company/util:util",
+        )
+        .unwrap();
+
+        let mut results = parsed_file.get_from_suffix(".Bar");
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    1,
+                    String::from("@third_party_jvm//3rdparty/jvm/com/example:foo")
+                ),
+                (
+                    3,
+                    String::from("@third_party_jvm//3rdparty/jvm/com/example:other")
+                ),
+            ]
+        );
+        assert_eq!(parsed_file.get_from_suffix("Bar").len(), 3);
+        assert!(parsed_file.get_from_suffix("Qux").is_empty());
+    }
+
+    #[test]
+    fn test_add_target_classes() {
+        let mut index_table =
+            parse_file("com.example.foo.Bar\t1:@third_party_jvm//3rdparty/jvm/com/example:foo")
+                .unwrap();
+
+        let classes = vec![
+            String::from("com.example.foo.Bar"),
+            String::from("com.example.local.Bar"),
+        ];
+        assert!(index_table.add_target_classes("//src/main/java/com/example/local", &classes));
+        assert!(!index_table.add_target_classes("//src/main/java/com/example/local", &classes));
+
+        assert_eq!(
+            index_table.get("com.example.foo.Bar"),
+            Some(vec![
+                (
+                    1,
+                    String::from("@third_party_jvm//3rdparty/jvm/com/example:foo")
+                ),
+                (0, String::from("//src/main/java/com/example/local")),
+            ])
+        );
+        assert_eq!(index_table.get_from_suffix(".local.Bar").len(), 1);
+        assert_eq!(index_table.get_from_suffix("Bar").len(), 2);
+
+        let mut written = Vec::default();
+        index_table.write_text(&mut written).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "com.example.foo.Bar\t1:@third_party_jvm//3rdparty/jvm/com/example:foo,0://src/main/java/com/example/local
+com.example.local.Bar\t0://src/main/java/com/example/local
+"
+        );
+
+        let other_classes = vec![
+            String::from("com.example.other.Baz"),
+            String::from("com.example.other.Bar"),
+            String::from("com.example.other.Abar"),
+        ];
+        assert!(index_table.add_target_classes("//src/main/java/com/example/other", &other_classes));
+        assert_eq!(index_table.get_from_suffix("Bar").len(), 3);
+        assert_eq!(index_table.get_from_suffix("bar").len(), 1);
+        assert_eq!(index_table.get_from_suffix("other.Baz").len(), 1);
+    }
+
     #[test]
     fn parse_multiple_lines() {
         let parsed_file = parse_file(
@@ -186,7 +440,7 @@ javax.annotation.ParametersAreNullableByDefault\t236:@third_party_jvm//3rdparty/
 
         assert_eq!(
             parsed_file.get("org.apache.parquet.thrift.test.TestPerson.TestPersonTupleScheme"),
-            Some(&vec![(
+            Some(vec![(
                 0,
                 String::from(
                     "@third_party_jvm//3rdparty/jvm/org/apache/parquet:parquet_thrift_jar_tests"
@@ -196,7 +450,7 @@ javax.annotation.ParametersAreNullableByDefault\t236:@third_party_jvm//3rdparty/
 
         assert_eq!(
             parsed_file.get("javax.annotation.Nullable"),
-            Some(&vec![
+            Some(vec![
                 (
                     236,
                     String::from("@third_party_jvm//3rdparty/jvm/com/google/code/findbugs:jsr305")
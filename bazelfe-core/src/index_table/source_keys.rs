@@ -0,0 +1,31 @@
+// What each family of source indexed rules is indexed under is namespaced, so that a Python
+// module, a Go importpath or a header can't collide with a JVM class or each other
+pub const CC_KEY_PREFIX: &str = "cc:";
+pub const GO_KEY_PREFIX: &str = "go:";
+pub const PROTO_KEY_PREFIX: &str = "proto:";
+pub const PYTHON_KEY_PREFIX: &str = "py:";
+pub const RUST_KEY_PREFIX: &str = "rust:";
+
+const KEY_PREFIXES: &[&str] = &[
+    CC_KEY_PREFIX,
+    GO_KEY_PREFIX,
+    PROTO_KEY_PREFIX,
+    PYTHON_KEY_PREFIX,
+    RUST_KEY_PREFIX,
+];
+
+pub fn is_source_index_key(key: &str) -> bool {
+    KEY_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_source_index_key() {
+        assert!(is_source_index_key("go:example.com/bar"));
+        assert!(is_source_index_key("py:company.util"));
+        assert!(!is_source_index_key("com.example.bar.Bar"));
+    }
+}
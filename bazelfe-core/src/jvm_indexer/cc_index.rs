@@ -0,0 +1,113 @@
+use std::collections::BTreeSet;
+
+use super::source_index::{join_path, label_to_path, repository, virtual_path, QueriedRule};
+
+// The index maps the paths a cc_library's headers can be included as straight to it:
+// - their path from the execution root, which bazel reports undeclared inclusions by, and
+//   which they can be included as, being on the include path
+// - their path from the root of their repository, also on the include path
+// - their path after strip_include_prefix and include_prefix
+// - their path from each of the rule's includes, relative to its package
+pub fn index_keys(rule: &QueriedRule) -> Vec<String> {
+    let package = rule.package();
+    let mut results = BTreeSet::new();
+    for path in rule
+        .list("hdrs")
+        .iter()
+        .chain(rule.list("textual_hdrs").iter())
+        .flat_map(|hdr| label_to_path(hdr))
+    {
+        if let Some(repository) = repository(&rule.label) {
+            results.insert(format!("external/{}/{}", repository, path));
+        }
+
+        if rule.string("strip_include_prefix").is_some() || rule.string("include_prefix").is_some()
+        {
+            results.insert(virtual_path(
+                package,
+                &path,
+                rule.string("strip_include_prefix"),
+                rule.string("include_prefix"),
+            ));
+        }
+
+        for include in rule.list("includes") {
+            let include_root = match include.trim_matches('/') {
+                "" | "." => package.to_string(),
+                include => join_path(package, include),
+            };
+            if include_root.is_empty() {
+                continue;
+            }
+            if let Some(relative_path) = path
+                .strip_prefix(include_root.as_str())
+                .and_then(|e| e.strip_prefix('/'))
+            {
+                results.insert(relative_path.to_string());
+            }
+        }
+
+        results.insert(path);
+    }
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm_indexer::source_index::parse_query_xml;
+
+    #[test]
+    fn test_index_keys() {
+        let xml = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?>
+<query version="2">
+    <rule class="cc_library" location="/home/user/repo/src/main/cpp/com/example/bar/BUILD:1:11" name="//src/main/cpp/com/example/bar:bar">
+        <list name="hdrs">
+            <label value="//src/main/cpp/com/example/bar:bar.h"/>
+        </list>
+        <string name="strip_include_prefix" value="/src/main/cpp"/>
+    </rule>
+    <rule class="cc_library" location="/home/user/.cache/bazel/external/com_google_absl/absl/strings/BUILD:1:11" name="@com_google_absl//absl/strings:strings">
+        <list name="hdrs">
+            <label value="@com_google_absl//absl/strings:str_cat.h"/>
+        </list>
+        <list name="textual_hdrs">
+            <label value="@com_google_absl//absl/strings:internal/str_format.inc"/>
+        </list>
+    </rule>
+    <rule class="cc_library" location="/home/user/repo/third_party/zlib/BUILD:1:11" name="//third_party/zlib:zlib">
+        <list name="hdrs">
+            <label value="//third_party/zlib:include/zlib.h"/>
+        </list>
+        <list name="includes">
+            <string value="include"/>
+        </list>
+    </rule>
+</query>
+"#;
+        let rules = parse_query_xml(xml);
+        assert_eq!(
+            index_keys(&rules[0]),
+            vec![
+                String::from("com/example/bar/bar.h"),
+                String::from("src/main/cpp/com/example/bar/bar.h"),
+            ]
+        );
+        assert_eq!(
+            index_keys(&rules[1]),
+            vec![
+                String::from("absl/strings/internal/str_format.inc"),
+                String::from("absl/strings/str_cat.h"),
+                String::from("external/com_google_absl/absl/strings/internal/str_format.inc"),
+                String::from("external/com_google_absl/absl/strings/str_cat.h"),
+            ]
+        );
+        assert_eq!(
+            index_keys(&rules[2]),
+            vec![
+                String::from("third_party/zlib/include/zlib.h"),
+                String::from("zlib.h"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use super::source_index::QueriedRule;
+
+// Go packages are imported by the importpath of the go_library providing them, which gazelle
+// fills in, so the index maps that straight to it.
+pub fn index_keys(rule: &QueriedRule) -> Vec<String> {
+    rule.string("importpath")
+        .filter(|e| !e.is_empty())
+        .map(|e| vec![e.to_string()])
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm_indexer::source_index::parse_query_xml;
+
+    #[test]
+    fn test_index_keys() {
+        let xml = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?>
+<query version="2">
+    <rule class="go_library" location="/home/user/repo/src/example/bar/BUILD.bazel:3:11" name="//src/example/bar:go_default_library">
+        <list name="srcs">
+            <label value="//src/example/bar:bar.go"/>
+        </list>
+        <string name="importpath" value="example.com/bar"/>
+    </rule>
+    <rule class="go_library" location="/home/user/repo/src/example/baz/BUILD.bazel:3:11" name="//src/example/baz:baz">
+        <string name="importpath" value=""/>
+    </rule>
+</query>
+"#;
+        let rules = parse_query_xml(xml);
+        assert_eq!(index_keys(&rules[0]), vec![String::from("example.com/bar")]);
+        assert_eq!(index_keys(&rules[1]), Vec::<String>::new());
+    }
+}
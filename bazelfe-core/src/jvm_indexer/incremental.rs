@@ -0,0 +1,156 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::index_table::source_keys;
+
+// Builds a query for the targets of an allowed kind which own one of the changed files,
+// or which live in a package whose BUILD file changed.
+pub fn changed_targets_query(
+    allowed_rule_kinds: &[String],
+    changed_files: &[String],
+) -> Option<String> {
+    let mut source_files = Vec::default();
+    let mut changed_packages = Vec::default();
+    for f in changed_files.iter() {
+        let f = f.trim();
+        if f.is_empty() {
+            continue;
+        }
+        let (package, file_name) = match f.rfind('/') {
+            Some(idx) => (&f[..idx], &f[idx + 1..]),
+            None => ("", f),
+        };
+        if file_name == "BUILD" || file_name == "BUILD.bazel" {
+            changed_packages.push(format!("//{}:*", package));
+        } else {
+            source_files.push(f.to_string());
+        }
+    }
+
+    let mut universe: Vec<String> = Vec::default();
+    if !source_files.is_empty() {
+        universe.push(format!("rdeps(//..., set({}), 1)", source_files.join(" ")));
+    }
+    universe.extend(changed_packages);
+    if universe.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "kind(\"^({}) rule$\", {})",
+        allowed_rule_kinds.join("|"),
+        universe.join(" union ")
+    ))
+}
+
+// Parses `bazel query --output label_kind` output into a map of rule kind to targets.
+pub fn parse_label_kind_output(stdout: &str, targets: &mut HashMap<String, Vec<String>>) {
+    for ln in stdout.lines() {
+        let entries: Vec<&str> = ln.split_whitespace().collect();
+        if entries.len() == 3 {
+            targets
+                .entry(entries[0].to_string())
+                .or_default()
+                .push(entries[2].to_string());
+        }
+    }
+}
+
+// The entries of a previous index which weren't produced by any of the rebuilt targets,
+// ready to have the rebuilt targets' classes merged back in. Targets which no longer
+// exist are dropped. Source indexed keys are kept as they are, they're replaced once the
+// source indexed rules have been queried.
+pub fn retain_unchanged(
+    previous_index: HashMap<String, Vec<(u16, String)>>,
+    rebuilt_labels: &HashSet<String>,
+    existing_labels: &HashSet<String>,
+) -> HashMap<String, Vec<(usize, String)>> {
+    previous_index
+        .into_iter()
+        .filter_map(|(k, v)| {
+            let source_indexed = source_keys::is_source_index_key(&k);
+            let kept: Vec<(usize, String)> = v
+                .into_iter()
+                .filter(|(_, label)| {
+                    source_indexed
+                        || (!rebuilt_labels.contains(label) && existing_labels.contains(label))
+                })
+                .map(|(freq, label)| (freq as usize, label))
+                .collect();
+            if kept.is_empty() {
+                None
+            } else {
+                Some((k, kept))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_targets_query() {
+        let allowed_rule_kinds = vec![String::from("java_library"), String::from("scala_library")];
+        assert_eq!(
+            changed_targets_query(
+                &allowed_rule_kinds,
+                &[
+                    String::from("src/main/java/com/example/Foo.java"),
+                    String::from("src/main/java/com/example/Bar.java"),
+                    String::from("src/main/scala/com/example/BUILD"),
+                ]
+            ),
+            Some(String::from(
+                "kind(\"^(java_library|scala_library) rule$\", rdeps(//..., set(src/main/java/com/example/Foo.java src/main/java/com/example/Bar.java), 1) union //src/main/scala/com/example:*)"
+            ))
+        );
+        assert_eq!(changed_targets_query(&allowed_rule_kinds, &[]), None);
+    }
+
+    #[test]
+    fn test_retain_unchanged() {
+        let mut previous_index = HashMap::new();
+        previous_index.insert(
+            String::from("com.example.Foo"),
+            vec![
+                (3, String::from("//src/main/java/com/example:foo")),
+                (1, String::from("//src/main/java/com/example:foo_copy")),
+                (2, String::from("//src/main/java/com/example:deleted")),
+            ],
+        );
+        previous_index.insert(
+            String::from("com.example.Removed"),
+            vec![(3, String::from("//src/main/java/com/example:foo"))],
+        );
+        previous_index.insert(
+            String::from("com.example.Deleted"),
+            vec![(2, String::from("//src/main/java/com/example:deleted"))],
+        );
+        previous_index.insert(
+            String::from("go:example.com/bar"),
+            vec![(0, String::from("//src/example/bar:bar"))],
+        );
+
+        let mut rebuilt_labels = HashSet::new();
+        rebuilt_labels.insert(String::from("//src/main/java/com/example:foo"));
+
+        let mut existing_labels = HashSet::new();
+        existing_labels.insert(String::from("//src/main/java/com/example:foo"));
+        existing_labels.insert(String::from("//src/main/java/com/example:foo_copy"));
+
+        let mut expected = HashMap::new();
+        expected.insert(
+            String::from("com.example.Foo"),
+            vec![(1, String::from("//src/main/java/com/example:foo_copy"))],
+        );
+        expected.insert(
+            String::from("go:example.com/bar"),
+            vec![(0, String::from("//src/example/bar:bar"))],
+        );
+        assert_eq!(
+            retain_unchanged(previous_index, &rebuilt_labels, &existing_labels),
+            expected
+        );
+    }
+}
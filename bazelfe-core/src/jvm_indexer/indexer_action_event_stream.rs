@@ -68,24 +68,8 @@ impl IndexerActionEventStream {
                                 hydrated_stream::HydratedInfo::TargetComplete(tce) => {
                                     if let Some(ref target_kind) = tce.target_kind {
                                         if allowed_rule_kind.contains(target_kind) {
-                                            let mut found_classes = Vec::default();
-
-                                            for of in tce.output_files.iter() {
-                                                if let build_event_stream::file::File::Uri(e) = of {
-                                                    if e.starts_with("file://") {
-                                                        let u: PathBuf = e
-                                                            .strip_prefix("file://")
-                                                            .unwrap()
-                                                            .into();
-                                                        let extracted_zip = crate::zip_parse::extract_classes_from_zip(u);
-                                                        found_classes.extend(
-                                                            transform_file_names_into_class_names(
-                                                                extracted_zip,
-                                                            ),
-                                                        );
-                                                    }
-                                                }
-                                            }
+                                            let mut found_classes =
+                                                classes_from_output_files(&tce.output_files);
                                             tx.send(Some(found_classes.len())).await.unwrap();
                                             found_classes.sort();
                                             found_classes.dedup();
@@ -113,6 +97,23 @@ fn remove_from<'a>(haystack: &'a str, needle: &str) -> &'a str {
         Some(pos) => &haystack[0..pos],
     }
 }
+// The classes found in the jars among a target's output files
+pub(crate) fn classes_from_output_files(
+    output_files: &[build_event_stream::file::File],
+) -> Vec<String> {
+    let mut found_classes = Vec::default();
+    for of in output_files.iter() {
+        if let build_event_stream::file::File::Uri(e) = of {
+            if let Some(path) = e.strip_prefix("file://") {
+                let u: PathBuf = path.into();
+                let extracted_zip = crate::zip_parse::extract_classes_from_zip(u);
+                found_classes.extend(transform_file_names_into_class_names(extracted_zip));
+            }
+        }
+    }
+    found_classes
+}
+
 fn transform_file_names_into_class_names(class_names: Vec<String>) -> Vec<String> {
     lazy_static! {
         static ref SUFFIX_ANON_CLAZZES: Regex = Regex::new(r"(\$\d*)?\.class$").unwrap();
@@ -18,7 +18,10 @@ use bazelfe_core::bazel_runner;
 use bazelfe_core::build_events::build_event_server::bazel_event;
 use bazelfe_core::build_events::build_event_server::BuildEventAction;
 use bazelfe_core::build_events::hydrated_stream::HydratedInfo;
+use bazelfe_core::index_table::source_keys;
 use bazelfe_core::jvm_indexer::bazel_query::BazelQuery;
+use bazelfe_core::jvm_indexer::incremental;
+use bazelfe_core::jvm_indexer::source_index;
 use dashmap::{DashMap};
 use google::devtools::build::v1::publish_build_event_server::PublishBuildEventServer;
 use rand::Rng;
@@ -57,6 +60,20 @@ struct Opt {
     /// will use the bazel deps entry rather than the raw jar.
     #[clap(long)]
     bazel_deps_root: Option<String>,
+
+    /// Load the existing index at the output location and only rebuild the targets affected by
+    /// the changed files, merging their classes back in. Changes to external repositories
+    /// aren't detected, a full index is needed to pick those up.
+    #[clap(long)]
+    incremental: bool,
+
+    /// Workspace relative paths of the files changed since the index was last built, used with --incremental
+    #[clap(long)]
+    changed_files: Vec<String>,
+
+    /// Find the changed files with `git diff --name-only` against this revision, used with --incremental
+    #[clap(long)]
+    changed_since: Option<String>,
 }
 
 fn build_rule_queries(allowed_rule_kinds: &Vec<String>, target_roots: &Vec<String>) -> Vec<String> {
@@ -135,6 +152,119 @@ fn parse_current_repo_name() -> Option<String> {
     None
 }
 
+// The main repository along with the external repositories that aren't blacklisted
+async fn query_target_roots<B: BazelQuery>(
+    bazel_query: &B,
+    blacklist_remote_roots: Vec<String>,
+) -> Vec<String> {
+    info!("Executing initial query to find all external repos in this bazel repository");
+
+    let res = bazel_query
+        .execute(&vec![String::from("query"), String::from("//external:*")])
+        .await;
+
+    let mut target_roots = vec![String::from("//...")];
+
+    let mut blacklist_repos = vec![String::from("bazel-"), String::from("WORKSPACE")];
+    if let Some(r) = parse_current_repo_name() {
+        info!("Current repo name identified as {}", r);
+        blacklist_repos.push(r);
+    }
+    blacklist_repos.extend(blacklist_remote_roots);
+
+    for line in res.stdout.lines().into_iter() {
+        if let Some(ln) = line.strip_prefix("//external:") {
+            let mut ok = true;
+            for root in &blacklist_repos {
+                if ln.contains(root) {
+                    ok = false;
+                }
+            }
+
+            if ok {
+                target_roots.push(format!("@{}//...", ln));
+            }
+        }
+    }
+
+    if res.exit_code != 0 {
+        info!("The bazel query returned something other than exit code zero, this unfortunately can often happen, so we will continue with the data received. We have identified {} target roots", target_roots.len());
+    } else {
+        info!("We have identified {} target roots", target_roots.len());
+    }
+    target_roots
+}
+
+async fn query_all_targets<B: BazelQuery>(
+    bazel_query: &B,
+    allowed_rule_kinds: &Vec<String>,
+    target_roots: &Vec<String>,
+) -> HashMap<String, Vec<String>> {
+    let all_queries = build_rule_queries(allowed_rule_kinds, target_roots);
+
+    let query_rule_attr_batch_size: usize = 2000;
+    info!("Extracting targets with an allowed rule kind, gives rise to {} total queries, we will union them to bazel in batches of size: {}", all_queries.len(), query_rule_attr_batch_size);
+
+    let union_with_spaces_bytes = " union ".as_bytes();
+
+    let mut all_targets_to_use: HashMap<String, Vec<String>> = HashMap::default();
+    let mut processed_count = 0;
+    for chunk in all_queries.chunks(query_rule_attr_batch_size) {
+        let merged = {
+            let mut buffer = Vec::default();
+
+            for x in chunk {
+                if buffer.is_empty() {
+                    buffer.write_all(&x.as_bytes()).unwrap();
+                } else {
+                    buffer.write_all(&union_with_spaces_bytes).unwrap();
+                    buffer.write_all(&x.as_bytes()).unwrap();
+                }
+            }
+            String::from_utf8(buffer).unwrap()
+        };
+        let res = bazel_query
+            .execute(&vec![
+                String::from("query"),
+                String::from("--keep_going"),
+                String::from("--noimplicit_deps"),
+                String::from("--output"),
+                String::from("label_kind"),
+                merged,
+            ])
+            .await;
+
+        incremental::parse_label_kind_output(&res.stdout, &mut all_targets_to_use);
+        processed_count += chunk.len();
+        info!(
+            "After {} queries, found {} matching targets",
+            processed_count,
+            all_targets_to_use.values().fold(0, |acc, e| acc + e.len())
+        );
+    }
+    all_targets_to_use
+}
+
+async fn git_changed_files(changed_since: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = tokio::process::Command::new("git")
+        .arg("diff")
+        .arg("--name-only")
+        .arg(changed_since)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(format!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|e| e.to_string())
+        .collect())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opt = Opt::parse();
@@ -151,18 +281,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     builder.init();
     let bazel_binary_path: String = (&opt.bazel_binary_path.to_str().unwrap()).to_string();
 
-    let allowed_rule_kinds: Vec<String> = vec![
-        "java_library",
-        "java_import",
-        "scala_import",
-        "scala_library",
-        "scala_proto_library",
-        "scala_macro_library",
-        "java_proto_library",
-        "_java_grpc_library",
-    ]
-    .into_iter()
-    .map(|e| e.to_string())
+    let allowed_rule_kinds: Vec<String> = bazelfe_core::jvm_indexer::DEFAULT_INDEXED_RULE_KINDS
+        .iter()
+        .map(|e| e.to_string())
     .chain(opt.extra_allowed_rule_kinds.unwrap_or_default().into_iter())
     .collect();
 
@@ -232,93 +353,70 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    info!("Executing initial query to find all external repos in this bazel repository");
-
-    let res = bazel_query
-        .execute(&vec![String::from("query"), String::from("//external:*")])
-        .await;
-
-    let mut target_roots = vec![String::from("//...")];
-
-    let mut blacklist_repos = vec![String::from("bazel-"), String::from("WORKSPACE")];
-    if let Some(r) = parse_current_repo_name() {
-        info!("Current repo name identified as {}", r);
-        blacklist_repos.push(r);
-    }
-    blacklist_repos.extend(opt.blacklist_remote_roots.into_iter());
-
-    for line in res.stdout.lines().into_iter() {
-        if let Some(ln) = line.strip_prefix("//external:") {
-            let mut ok = true;
-            for root in &blacklist_repos {
-                if ln.contains(root) {
-                    ok = false;
-                }
-            }
-
-            if ok {
-                target_roots.push(format!("@{}//...", ln));
-            }
-        }
-    }
-
-    if res.exit_code != 0 {
-        info!("The bazel query returned something other than exit code zero, this unfortunately can often happen, so we will continue with the data received. We have identified {} target roots", target_roots.len());
+    // An incremental run writes the index back out in whichever format it was loaded from
+    let binary_output = opt.incremental
+        && bazelfe_core::index_table::mapped::is_binary_index(&opt.index_output_location);
+    let previous_index = if opt.incremental && opt.index_output_location.exists() {
+        info!(
+            "Loading the previous index from {:?}",
+            opt.index_output_location
+        );
+        Some(bazelfe_core::index_table::load_file(&opt.index_output_location)?)
     } else {
-        info!("We have identified {} target roots", target_roots.len());
-    }
-
-    let all_queries = build_rule_queries(&allowed_rule_kinds, &target_roots);
-
-    let query_rule_attr_batch_size: usize = 2000;
-    info!("Extracting targets with an allowed rule kind, gives rise to {} total queries, we will union them to bazel in batches of size: {}", all_queries.len(), query_rule_attr_batch_size);
-
-    let union_with_spaces_bytes = " union ".as_bytes();
+        None
+    };
 
-    let mut all_targets_to_use: HashMap<String, Vec<String>> = HashMap::default();
-    let mut processed_count = 0;
-    for chunk in all_queries.chunks(query_rule_attr_batch_size) {
-        let merged = {
-            let mut buffer = Vec::default();
+    let target_roots = query_target_roots(&bazel_query, opt.blacklist_remote_roots.clone()).await;
+
+    // Everything of an allowed kind that still exists, so the previous index can drop targets
+    // which have since been deleted
+    let existing_labels: Option<HashSet<String>> = match &previous_index {
+        Some(_) => Some(
+            query_all_targets(&bazel_query, &allowed_rule_kinds, &target_roots)
+                .await
+                .values()
+                .flat_map(|e| e.iter())
+                .map(|e| bazel_deps_replacement_map.get(e).unwrap_or(e).clone())
+                .collect(),
+        ),
+        None => None,
+    };
 
-            for x in chunk {
-                if buffer.is_empty() {
-                    buffer.write_all(&x.as_bytes()).unwrap();
-                } else {
-                    buffer.write_all(&union_with_spaces_bytes).unwrap();
-                    buffer.write_all(&x.as_bytes()).unwrap();
-                }
+    let all_targets_to_use = match &previous_index {
+        Some(_) => {
+            let mut changed_files = opt.changed_files.clone();
+            if let Some(changed_since) = &opt.changed_since {
+                changed_files.extend(git_changed_files(changed_since).await?);
             }
-            String::from_utf8(buffer).unwrap()
-        };
-        let res = bazel_query
-            .execute(&vec![
-                String::from("query"),
-                String::from("--keep_going"),
-                String::from("--noimplicit_deps"),
-                String::from("--output"),
-                String::from("label_kind"),
-                merged,
-            ])
-            .await;
-
-        for ln in res.stdout.lines() {
-            let entries: Vec<&str> = ln.split_whitespace().collect();
-            if entries.len() == 3 {
-                let entry = all_targets_to_use
-                    .entry(entries[0].to_string())
-                    .or_insert(Vec::default());
-                entry.push(entries[2].to_string());
+            info!("Finding targets affected by {} changed files", changed_files.len());
+            let mut all_targets_to_use = HashMap::default();
+            if let Some(query) =
+                incremental::changed_targets_query(&allowed_rule_kinds, &changed_files)
+            {
+                let res = bazel_query
+                    .execute(&vec![
+                        String::from("query"),
+                        String::from("--keep_going"),
+                        String::from("--noimplicit_deps"),
+                        String::from("--output"),
+                        String::from("label_kind"),
+                        query,
+                    ])
+                    .await;
+                incremental::parse_label_kind_output(&res.stdout, &mut all_targets_to_use);
             }
-            // all_targets_to_use.push(ln.to_string());
+            all_targets_to_use
         }
-        processed_count += chunk.len();
-        info!(
-            "After {} queries, found {} matching targets",
-            processed_count,
-            all_targets_to_use.values().fold(0, |acc, e| acc + e.len())
-        );
-    }
+        None => {
+            query_all_targets(&bazel_query, &allowed_rule_kinds, &target_roots).await
+        }
+    };
+
+    let rebuilt_labels: HashSet<String> = all_targets_to_use
+        .values()
+        .flat_map(|e| e.iter())
+        .map(|e| bazel_deps_replacement_map.get(e).unwrap_or(e).clone())
+        .collect();
 
     info!("Found targets");
     for (k, v) in all_targets_to_use.iter() {
@@ -431,7 +529,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Building a target popularity map");
     let ret = bazelfe_core::jvm_indexer::popularity_parser::build_popularity_map();
 
-    let mut reverse_hashmap = HashMap::new();
+    let mut reverse_hashmap = match previous_index {
+        Some(previous_index) => {
+            incremental::retain_unchanged(
+                previous_index.to_hashmap(),
+                &rebuilt_labels,
+                &existing_labels.unwrap_or_default(),
+            )
+        }
+        None => HashMap::new(),
+    };
 
     info!("Building results map, and injecting popularity data");
     for kv in results_map.iter() {
@@ -445,6 +552,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    info!(
+        "Indexing the srcs of {} targets",
+        source_index::SOURCE_INDEXED_RULE_KINDS.join(", ")
+    );
+    let source_rules = bazel_query
+        .execute(&vec![
+            String::from("query"),
+            String::from("--keep_going"),
+            String::from("--output"),
+            String::from("xml"),
+            source_index::rules_query(&target_roots),
+        ])
+        .await;
+    // Every source indexed rule is queried each time, so whatever the previous index had for
+    // them is replaced, dropping the keys of rules that have since been deleted. When the
+    // query failed we can't tell what was deleted, so the previous keys are kept.
+    let queried = source_index::query_succeeded(source_rules.exit_code, &source_rules.stdout);
+    let source_rules = source_index::parse_query_xml(&source_rules.stdout);
+    if queried {
+        reverse_hashmap.retain(|key, _| !source_keys::is_source_index_key(key));
+    } else {
+        warn!("Querying the source indexed rules failed, keeping what the previous index had for them");
+    }
+    for rule in source_rules.iter() {
+        let freq: usize = *ret.get(&rule.label).unwrap_or(&0);
+        for key in source_index::index_keys(rule) {
+            let v = reverse_hashmap.entry(key).or_insert(vec![]);
+            v.push((freq, rule.label.clone()));
+        }
+    }
+
     let res_vec = {
         let mut v1: Vec<(String, Vec<(usize, String)>)> = reverse_hashmap.into_iter().collect();
 
@@ -453,23 +591,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     info!("Writing out index data");
-    let mut file = std::fs::File::create(&opt.index_output_location).unwrap();
-
-    for (k, mut innerv) in res_vec.into_iter() {
-        file.write_all(k.as_bytes())?;
-        file.write_all("\t".as_bytes())?;
-        // reverse sort
-        innerv.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-        let mut idx = 0;
-        for (cnt, v) in innerv.into_iter() {
-            if idx > 0 {
-                file.write_all(",".as_bytes()).unwrap();
+    if binary_output {
+        let tbl_map: HashMap<String, Vec<(u16, String)>> = res_vec
+            .into_iter()
+            .map(|(k, mut innerv)| {
+                innerv.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+                let innerv = innerv
+                    .into_iter()
+                    .map(|(cnt, v)| (cnt.min(u16::MAX as usize) as u16, v))
+                    .collect();
+                (k, innerv)
+            })
+            .collect();
+        bazelfe_core::index_table::write_replacing(&opt.index_output_location, |file| {
+            bazelfe_core::index_table::mapped::write_index(&tbl_map, file)
+        })?;
+        return Ok(());
+    }
+
+    bazelfe_core::index_table::write_replacing(&opt.index_output_location, |file| {
+        for (k, mut innerv) in res_vec.into_iter() {
+            file.write_all(k.as_bytes())?;
+            file.write_all("\t".as_bytes())?;
+            // reverse sort
+            innerv.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            let mut idx = 0;
+            for (cnt, v) in innerv.into_iter() {
+                if idx > 0 {
+                    file.write_all(",".as_bytes())?;
+                }
+                file.write_all(format!("{}:{}", cnt, v).as_bytes())?;
+                idx += 1;
             }
-            file.write_all(format!("{}:{}", cnt, v).as_bytes()).unwrap();
-            idx += 1;
+            file.write_all("\n".as_bytes())?;
         }
-        file.write_all("\n".as_bytes()).unwrap();
-    }
+        Ok(())
+    })?;
 
     Ok(())
 }
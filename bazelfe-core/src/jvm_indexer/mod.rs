@@ -1,3 +1,24 @@
 pub mod bazel_query;
+pub mod cc_index;
+pub mod go_index;
+pub mod incremental;
 pub mod indexer_action_event_stream;
 pub mod popularity_parser;
+pub mod proto_index;
+pub mod python_index;
+pub mod rust_index;
+pub mod source_index;
+
+// Rule kinds whose outputs are jars of classes worth indexing
+pub const DEFAULT_INDEXED_RULE_KINDS: &[&str] = &[
+    "java_library",
+    "java_import",
+    "scala_import",
+    "scala_library",
+    "scala_proto_library",
+    "scala_macro_library",
+    "java_proto_library",
+    "_java_grpc_library",
+    "kt_jvm_library",
+    "kt_jvm_import",
+];
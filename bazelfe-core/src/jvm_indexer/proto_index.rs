@@ -0,0 +1,82 @@
+use super::source_index::{label_to_path, virtual_path, QueriedRule};
+
+// Protos don't end up in jars, so the index maps the paths a proto_library's srcs are
+// imported as straight to it.
+pub fn index_keys(rule: &QueriedRule) -> Vec<String> {
+    rule.list("srcs")
+        .iter()
+        .flat_map(|src| label_to_path(src))
+        .map(|path| {
+            virtual_path(
+                rule.package(),
+                &path,
+                rule.string("strip_import_prefix"),
+                rule.string("import_prefix"),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm_indexer::source_index::parse_query_xml;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_index_keys() {
+        let xml = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?>
+<query version="2">
+    <rule class="proto_library" location="/home/user/repo/src/main/proto/com/example/BUILD:3:14" name="//src/main/proto/com/example:example_proto">
+        <string name="name" value="example_proto"/>
+        <list name="srcs">
+            <label value="//src/main/proto/com/example:foo.proto"/>
+            <label value="//src/main/proto/com/example:bar/bar.proto"/>
+        </list>
+        <string name="strip_import_prefix" value="/src/main/proto"/>
+        <list name="deps">
+            <label value="@com_google_protobuf//:timestamp_proto"/>
+        </list>
+        <rule-input name="//src/main/proto/com/example:foo.proto"/>
+    </rule>
+    <rule class="proto_library" location="/home/user/repo/protos/BUILD:1:14" name="//protos:api_proto">
+        <list name="srcs">
+            <label value="//protos:v1/api.proto"/>
+        </list>
+        <string name="strip_import_prefix" value="v1"/>
+        <string name="import_prefix" value="company/api"/>
+    </rule>
+    <rule class="proto_library" location="/home/user/.cache/bazel/external/com_google_protobuf/BUILD:1:14" name="@com_google_protobuf//:timestamp_proto">
+        <list name="srcs">
+            <label value="@com_google_protobuf//:src/google/protobuf/timestamp.proto"/>
+        </list>
+        <string name="strip_import_prefix" value="src"/>
+    </rule>
+</query>
+"#;
+        let mut expected = HashMap::new();
+        expected.insert(
+            String::from("com/example/foo.proto"),
+            vec![String::from("//src/main/proto/com/example:example_proto")],
+        );
+        expected.insert(
+            String::from("com/example/bar/bar.proto"),
+            vec![String::from("//src/main/proto/com/example:example_proto")],
+        );
+        expected.insert(
+            String::from("company/api/api.proto"),
+            vec![String::from("//protos:api_proto")],
+        );
+        expected.insert(
+            String::from("google/protobuf/timestamp.proto"),
+            vec![String::from("@com_google_protobuf//:timestamp_proto")],
+        );
+        let mut index: HashMap<String, Vec<String>> = HashMap::new();
+        for rule in parse_query_xml(xml) {
+            for key in index_keys(&rule) {
+                index.entry(key).or_default().push(rule.label.clone());
+            }
+        }
+        assert_eq!(index, expected);
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+
+use super::source_index::{join_path, label_to_path, QueriedRule};
+
+fn is_identifier(segment: &str) -> bool {
+    match segment.chars().next() {
+        Some(c) if c.is_ascii_digit() => false,
+        Some(_) => segment.chars().all(|c| c.is_alphanumeric() || c == '_'),
+        None => false,
+    }
+}
+
+// foo.bar.baz for foo/bar/baz.py, a package's __init__.py being the package itself. Paths
+// that couldn't be imported, like site-packages/foo.py from the root, have no module name.
+fn module_name(path: &str) -> Option<String> {
+    let path = path.strip_suffix(".py")?;
+    let path = match path.strip_suffix("__init__") {
+        Some(package) => package.trim_end_matches('/'),
+        None => path,
+    };
+    if path.split('/').all(is_identifier) {
+        Some(path.replace('/', "."))
+    } else {
+        None
+    }
+}
+
+// The roots a py_library's srcs are imported relative to. Bazel puts the root of the
+// repository on the python path, along with each of the rule's imports, which are relative
+// to its package.
+fn import_roots(rule: &QueriedRule) -> Vec<String> {
+    let mut roots = vec![String::default()];
+    for import in rule.list("imports") {
+        let root = match import.trim_matches('/') {
+            "" | "." => rule.package().to_string(),
+            import => join_path(rule.package(), import),
+        };
+        roots.push(root);
+    }
+    roots
+}
+
+// Python has nothing to build, so the index maps the modules a py_library's srcs are
+// imported as, along with the packages they're in, straight to it.
+pub fn index_keys(rule: &QueriedRule) -> Vec<String> {
+    let roots = import_roots(rule);
+    let mut results = BTreeSet::new();
+    for path in rule.list("srcs").iter().flat_map(|src| label_to_path(src)) {
+        for root in roots.iter() {
+            let relative_path = if root.is_empty() {
+                Some(path.as_str())
+            } else {
+                path.strip_prefix(root.as_str())
+                    .and_then(|e| e.strip_prefix('/'))
+            };
+            if let Some(module) = relative_path.and_then(module_name) {
+                // Importing foo.bar needs whichever libraries have files in foo/bar
+                if let Some(idx) = module.rfind('.') {
+                    results.insert(module[..idx].to_string());
+                }
+                results.insert(module);
+            }
+        }
+    }
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm_indexer::source_index::parse_query_xml;
+
+    #[test]
+    fn test_index_keys() {
+        let xml = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?>
+<query version="2">
+    <rule class="py_library" location="/home/user/repo/company/util/BUILD:1:11" name="//company/util:util">
+        <list name="srcs">
+            <label value="//company/util:__init__.py"/>
+            <label value="//company/util:strings.py"/>
+            <label value="//company/util:data.json"/>
+        </list>
+    </rule>
+    <rule class="py_library" location="/home/user/.cache/bazel/external/pypi_requests/BUILD:1:11" name="@pypi_requests//:pkg">
+        <list name="srcs">
+            <label value="@pypi_requests//:site-packages/requests/__init__.py"/>
+            <label value="@pypi_requests//:site-packages/requests/adapters.py"/>
+        </list>
+        <list name="imports">
+            <string value="site-packages"/>
+        </list>
+    </rule>
+</query>
+"#;
+        let rules = parse_query_xml(xml);
+        assert_eq!(
+            index_keys(&rules[0]),
+            vec![
+                String::from("company"),
+                String::from("company.util"),
+                String::from("company.util.strings"),
+            ]
+        );
+        assert_eq!(
+            index_keys(&rules[1]),
+            vec![String::from("requests"), String::from("requests.adapters")]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use super::source_index::QueriedRule;
+
+// Crates are used by their crate_name, which rules_rust takes from the target's name when it
+// isn't set, with any dashes made underscores
+pub fn index_keys(rule: &QueriedRule) -> Vec<String> {
+    let crate_name = match rule.string("crate_name").filter(|e| !e.is_empty()) {
+        Some(crate_name) => crate_name,
+        None => match rule.label.rfind(':') {
+            Some(idx) => &rule.label[idx + 1..],
+            None => return Vec::default(),
+        },
+    };
+    vec![crate_name.replace('-', "_")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm_indexer::source_index::parse_query_xml;
+
+    #[test]
+    fn test_index_keys() {
+        let xml = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?>
+<query version="2">
+    <rule class="rust_library" location="/home/user/repo/src/main/rust/com/example/bar/BUILD:1:13" name="//src/main/rust/com/example/bar:bar-utils">
+        <list name="srcs">
+            <label value="//src/main/rust/com/example/bar:lib.rs"/>
+        </list>
+    </rule>
+    <rule class="rust_library" location="/home/user/.cache/bazel/external/raze__serde_json__1_0_64/BUILD.bazel:30:13" name="@raze__serde_json__1_0_64//:serde_json">
+        <string name="crate_name" value="serde_json"/>
+    </rule>
+    <rule class="rust_proc_macro" location="/home/user/repo/macros/BUILD:1:16" name="//macros:macros">
+        <string name="crate_name" value="example_macros"/>
+    </rule>
+</query>
+"#;
+        let rules = parse_query_xml(xml);
+        assert_eq!(index_keys(&rules[0]), vec![String::from("bar_utils")]);
+        assert_eq!(index_keys(&rules[1]), vec![String::from("serde_json")]);
+        assert_eq!(index_keys(&rules[2]), vec![String::from("example_macros")]);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{cc_index, go_index, proto_index, python_index, rust_index};
+use crate::index_table::source_keys::{
+    CC_KEY_PREFIX, GO_KEY_PREFIX, PROTO_KEY_PREFIX, PYTHON_KEY_PREFIX, RUST_KEY_PREFIX,
+};
+
+// Rules whose outputs aren't jars, so rather than building them the index maps what they
+// provide, worked out from their srcs and attributes, straight to their labels.
+pub const SOURCE_INDEXED_RULE_KINDS: &[&str] = &[
+    "cc_library",
+    "go_library",
+    "proto_library",
+    "py_library",
+    "rust_library",
+    "rust_proc_macro",
+];
+
+// A rule as `bazel query --output xml` describes it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueriedRule {
+    pub class: String,
+    pub label: String,
+    pub strings: HashMap<String, String>,
+    pub lists: HashMap<String, Vec<String>>,
+}
+
+impl QueriedRule {
+    pub fn string(&self, name: &str) -> Option<&str> {
+        self.strings.get(name).map(|e| e.as_str())
+    }
+
+    pub fn list(&self, name: &str) -> &[String] {
+        self.lists.get(name).map(|e| e.as_slice()).unwrap_or(&[])
+    }
+
+    // The package the rule is in, foo/bar for @repo//foo/bar:baz
+    pub fn package(&self) -> &str {
+        split_label(&self.label).map(|e| e.0).unwrap_or("")
+    }
+}
+
+pub fn rules_query(target_roots: &[String]) -> String {
+    format!(
+        "kind(\"^({}) rule$\", {})",
+        SOURCE_INDEXED_RULE_KINDS.join("|"),
+        target_roots.join(" union ")
+    )
+}
+
+// Whether the rules query gave a complete answer. With --keep_going bazel exits with 3 when
+// only some of the query failed, still listing the rules it could load.
+pub fn query_succeeded(exit_code: i32, xml: &str) -> bool {
+    (exit_code == 0 || exit_code == 3) && xml.contains("<query")
+}
+
+// What the index should map to the rule
+pub fn index_keys(rule: &QueriedRule) -> Vec<String> {
+    let (prefix, keys) = match rule.class.as_str() {
+        "cc_library" => (CC_KEY_PREFIX, cc_index::index_keys(rule)),
+        "go_library" => (GO_KEY_PREFIX, go_index::index_keys(rule)),
+        "proto_library" => (PROTO_KEY_PREFIX, proto_index::index_keys(rule)),
+        "py_library" => (PYTHON_KEY_PREFIX, python_index::index_keys(rule)),
+        "rust_library" | "rust_proc_macro" => (RUST_KEY_PREFIX, rust_index::index_keys(rule)),
+        _ => return Vec::default(),
+    };
+    keys.into_iter()
+        .map(|key| format!("{}{}", prefix, key))
+        .collect()
+}
+
+// The package and file name of a source file label, @repo//foo/bar:baz.proto being
+// foo/bar and baz.proto
+pub fn split_label(label: &str) -> Option<(&str, &str)> {
+    let label = &label[label.find("//")? + 2..];
+    match label.find(':') {
+        Some(idx) => Some((&label[..idx], &label[idx + 1..])),
+        None => None,
+    }
+}
+
+// The external repository of a label, repo for @repo//foo/bar:baz
+pub fn repository(label: &str) -> Option<&str> {
+    let repository = label.strip_prefix('@')?;
+    let repository = &repository[..repository.find("//")?];
+    if repository.is_empty() {
+        None
+    } else {
+        Some(repository)
+    }
+}
+
+// The path of a source file label from the root of its repository
+pub fn label_to_path(label: &str) -> Option<String> {
+    split_label(label).map(|(package, file_name)| join_path(package, file_name))
+}
+
+pub fn join_path(prefix: &str, suffix: &str) -> String {
+    if prefix.is_empty() {
+        suffix.to_string()
+    } else {
+        format!("{}/{}", prefix, suffix)
+    }
+}
+
+// The path of a file after a rule's prefix to strip and prefix to add, like a proto_library's
+// strip_import_prefix and import_prefix. A prefix to strip starting with / is relative to the
+// repository, otherwise it's relative to the rule's package.
+pub fn virtual_path(
+    rule_package: &str,
+    path: &str,
+    strip_prefix: Option<&str>,
+    prefix: Option<&str>,
+) -> String {
+    let mut path = path.to_string();
+
+    if let Some(strip_prefix) = strip_prefix {
+        let strip_prefix = match strip_prefix.strip_prefix('/') {
+            Some(repository_relative) => repository_relative.to_string(),
+            None => join_path(rule_package, strip_prefix),
+        };
+        let strip_prefix = strip_prefix.trim_end_matches('/');
+        if !strip_prefix.is_empty() {
+            if let Some(stripped) = path
+                .strip_prefix(strip_prefix)
+                .and_then(|e| e.strip_prefix('/'))
+            {
+                path = stripped.to_string();
+            }
+        }
+    }
+
+    if let Some(prefix) = prefix {
+        path = join_path(prefix.trim_matches('/'), &path);
+    }
+    path
+}
+
+// Parses the rules out of `bazel query --output xml` output, keeping their string and list
+// attributes.
+pub fn parse_query_xml(xml: &str) -> Vec<QueriedRule> {
+    lazy_static! {
+        static ref RULE_RE: Regex =
+            Regex::new(r#"^\s*<rule class="([^"]+)"[^>]* name="([^"]+)""#).unwrap();
+        static ref LIST_RE: Regex = Regex::new(r#"^\s*<list name="([^"]+)">"#).unwrap();
+        static ref LIST_ENTRY_RE: Regex =
+            Regex::new(r#"^\s*<(?:label|string|output) value="([^"]*)"/>"#).unwrap();
+        static ref STRING_RE: Regex =
+            Regex::new(r#"^\s*<string name="([^"]+)" value="([^"]*)"/>"#).unwrap();
+    }
+
+    let mut results = Vec::default();
+    let mut current: Option<QueriedRule> = None;
+    let mut current_list: Option<String> = None;
+    for ln in xml.lines() {
+        if let Some(captures) = RULE_RE.captures(ln) {
+            current = Some(QueriedRule {
+                class: captures.get(1).unwrap().as_str().to_string(),
+                label: captures.get(2).unwrap().as_str().to_string(),
+                ..Default::default()
+            });
+            current_list = None;
+            continue;
+        }
+        let rule = match current.as_mut() {
+            Some(rule) => rule,
+            None => continue,
+        };
+        let trimmed = ln.trim();
+        if trimmed == "</rule>" {
+            results.extend(current.take());
+        } else if trimmed == "</list>" {
+            current_list = None;
+        } else if let Some(captures) = LIST_RE.captures(ln) {
+            let name = captures.get(1).unwrap().as_str().to_string();
+            rule.lists.entry(name.clone()).or_default();
+            current_list = Some(name);
+        } else if let Some(list) = &current_list {
+            if let Some(captures) = LIST_ENTRY_RE.captures(ln) {
+                rule.lists
+                    .entry(list.clone())
+                    .or_default()
+                    .push(unescape(captures.get(1).unwrap().as_str()));
+            }
+        } else if let Some(captures) = STRING_RE.captures(ln) {
+            rule.strings.insert(
+                captures.get(1).unwrap().as_str().to_string(),
+                unescape(captures.get(2).unwrap().as_str()),
+            );
+        }
+    }
+    results
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_query() {
+        assert_eq!(
+            rules_query(&[
+                String::from("//..."),
+                String::from("@com_google_protobuf//...")
+            ]),
+            "kind(\"^(cc_library|go_library|proto_library|py_library|rust_library|rust_proc_macro) rule$\", //... union @com_google_protobuf//...)"
+        );
+    }
+
+    #[test]
+    fn test_query_succeeded() {
+        let xml = "<?xml version=\"1.1\" encoding=\"UTF-8\" standalone=\"no\"?>\n<query version=\"2\">\n</query>\n";
+        assert!(query_succeeded(0, xml));
+        assert!(query_succeeded(3, xml));
+        assert!(!query_succeeded(7, xml));
+        assert!(!query_succeeded(0, ""));
+    }
+
+    #[test]
+    fn test_index_keys() {
+        let mut go_library = QueriedRule {
+            class: String::from("go_library"),
+            label: String::from("//src/example/bar:bar"),
+            ..Default::default()
+        };
+        go_library
+            .strings
+            .insert(String::from("importpath"), String::from("example.com/bar"));
+        let mut java_library = go_library.clone();
+        java_library.class = String::from("java_library");
+
+        assert_eq!(
+            index_keys(&go_library),
+            vec![String::from("go:example.com/bar")]
+        );
+        assert_eq!(index_keys(&java_library), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_query_xml() {
+        let xml = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?>
+<query version="2">
+    <rule class="proto_library" location="/home/user/repo/src/main/proto/com/example/BUILD:3:14" name="//src/main/proto/com/example:example_proto">
+        <string name="name" value="example_proto"/>
+        <list name="srcs">
+            <label value="//src/main/proto/com/example:foo.proto"/>
+        </list>
+        <string name="strip_import_prefix" value="/src/main/proto"/>
+        <list name="deps">
+            <label value="@com_google_protobuf//:timestamp_proto"/>
+        </list>
+        <list name="tags"/>
+        <rule-input name="//src/main/proto/com/example:foo.proto"/>
+    </rule>
+    <source-file location="/home/user/repo/src/main/proto/com/example/BUILD:1:1" name="//src/main/proto/com/example:foo.proto"/>
+    <rule class="py_library" location="/home/user/repo/py/BUILD:1:11" name="//py:lib">
+        <list name="imports">
+            <string value="src"/>
+        </list>
+    </rule>
+</query>
+"#;
+        let mut proto_library = QueriedRule {
+            class: String::from("proto_library"),
+            label: String::from("//src/main/proto/com/example:example_proto"),
+            ..Default::default()
+        };
+        proto_library
+            .strings
+            .insert(String::from("name"), String::from("example_proto"));
+        proto_library.strings.insert(
+            String::from("strip_import_prefix"),
+            String::from("/src/main/proto"),
+        );
+        proto_library.lists.insert(
+            String::from("srcs"),
+            vec![String::from("//src/main/proto/com/example:foo.proto")],
+        );
+        proto_library.lists.insert(
+            String::from("deps"),
+            vec![String::from("@com_google_protobuf//:timestamp_proto")],
+        );
+
+        let mut py_library = QueriedRule {
+            class: String::from("py_library"),
+            label: String::from("//py:lib"),
+            ..Default::default()
+        };
+        py_library
+            .lists
+            .insert(String::from("imports"), vec![String::from("src")]);
+
+        assert_eq!(parse_query_xml(xml), vec![proto_library, py_library]);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::source_dependencies::parser_helpers::*;
+use crate::source_dependencies::{Import, ParsedFile, Result, SelectorType};
+
+use nom::character::complete::{alphanumeric1, multispace0, space0, space1};
+use nom::combinator::recognize;
+use nom::multi::many1;
+use nom::{
+    bytes::complete::tag,
+    combinator::{map, opt},
+    sequence::tuple,
+    IResult,
+};
+
+fn is_valid_import_segment_item(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn parse_identifier(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        alphanumeric1,
+        nom::bytes::complete::take_while(is_valid_import_segment_item),
+    )))(input)
+}
+
+// Unlike java, kotlin imports can rename what they import and don't need a semicolon
+pub fn parse_import(line_number: u32, input: &str) -> IResult<&str, Import> {
+    let (input, _) = tuple((multispace0, tag("import"), space1, multispace0))(input)?;
+
+    let (input, (extracted, opt_wildcard, opt_alias)) = map(
+        tuple((
+            recognize(many1(tuple((
+                opt(tag(".")),
+                alphanumeric1,
+                nom::bytes::complete::take_while(is_valid_import_segment_item),
+            )))),
+            opt(tag(".*")),
+            opt(map(
+                tuple((space1, tag("as"), space1, parse_identifier)),
+                |r| r.3,
+            )),
+            space0,
+            opt(tag(";")),
+        )),
+        |r| (r.0, r.1, r.2),
+    )(input)?;
+
+    let (prefix_section, selector) = match (opt_wildcard, opt_alias) {
+        (Some(_), _) => (extracted, SelectorType::WildcardSelector),
+        (None, Some(alias)) => match extracted.rfind('.') {
+            Some(idx) => (
+                &extracted[..idx],
+                SelectorType::SelectorList(vec![(
+                    extracted[idx + 1..].to_string(),
+                    Some(alias.to_string()),
+                )]),
+            ),
+            None => (extracted, SelectorType::NoSelector),
+        },
+        (None, None) => (extracted, SelectorType::NoSelector),
+    };
+
+    Ok((
+        input,
+        Import {
+            line_number,
+            prefix_section: prefix_section.to_string(),
+            suffix: selector,
+        },
+    ))
+}
+
+// END UTILITIES FOR IMPORT PARSING
+
+// START UTILITIES FOR PACAKGE PARSING
+fn extract_package_from_line(ln: &str) -> Result<&str> {
+    let (_, res) = map(
+        nom::combinator::all_consuming(tuple((
+            space0,
+            tag("package"),
+            space1,
+            nom::bytes::complete::take_while1(|chr: char| {
+                chr.is_alphanumeric() || chr == '.' || chr == '_'
+            }),
+            space0,
+            opt(tag(";")),
+            space0,
+            opt(tuple((
+                tag("//"),
+                nom::bytes::complete::take_while(not_end_of_line),
+            ))),
+        ))),
+        |tup| tup.3,
+    )(ln)?;
+    Ok(res)
+}
+
+fn extract_package_from_file(file_lines: &str) -> Result<Option<&str>> {
+    for ln in file_lines.lines() {
+        if ln.contains("package") {
+            if let Ok(pkg) = extract_package_from_line(ln) {
+                return Ok(Some(pkg));
+            }
+        }
+    }
+    Ok(None)
+}
+// END UTILITIES FOR PACAKGE PARSING
+
+// PUBLIC METHODS
+pub fn parse_imports(input: &str) -> Result<Vec<Import>> {
+    let mut results_vec = Vec::new();
+    let mut line_number = 1;
+    let mut remaining_input = input;
+    while remaining_input.len() > 3 {
+        match eat_till_end_of_line(remaining_input) {
+            Ok((r, (current_line, end_of_line_eaten))) => {
+                if !current_line.is_empty() && current_line.contains("import") {
+                    if let Ok((_, found)) = parse_import(line_number, current_line) {
+                        results_vec.push(found);
+                    }
+                }
+
+                // if we never found an end of line, must be end of file.
+                if !end_of_line_eaten.is_empty() {
+                    remaining_input = r;
+                } else {
+                    remaining_input = "";
+                }
+            }
+            Err(_) => {
+                remaining_input = "";
+            }
+        }
+        line_number += 1;
+    }
+
+    Ok(results_vec)
+}
+
+pub fn parse_file(input: &str) -> Result<ParsedFile> {
+    let package = extract_package_from_file(input)?;
+
+    let imports = parse_imports(input)?;
+
+    Ok(ParsedFile {
+        package_name: package.map(|e| e.to_string()),
+        imports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_header_line() {
+        assert_eq!(
+            extract_package_from_file(
+                "
+            @file:JvmName(\"Example\")
+            package foo.bar.baz // have end of line comments here
+            asdf"
+            )
+            .unwrap(),
+            Some("foo.bar.baz")
+        );
+
+        assert_eq!(
+            extract_package_from_file(
+                "
+            package foo.bar.baz i am totally invalid and not a package line
+            asdf"
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_multiple_lines_input() {
+        let sample_input = "
+        import com.twitter.scalding.RichDate
+        import com.twitter.scalding.*;
+
+        import com.twitter.scalding.RichDate as Date
+        ";
+        let expected_results = vec![
+            Import {
+                line_number: 2,
+                prefix_section: "com.twitter.scalding.RichDate".to_string(),
+                suffix: SelectorType::NoSelector,
+            },
+            Import {
+                line_number: 3,
+                prefix_section: "com.twitter.scalding".to_string(),
+                suffix: SelectorType::WildcardSelector,
+            },
+            Import {
+                line_number: 5,
+                prefix_section: "com.twitter.scalding".to_string(),
+                suffix: SelectorType::SelectorList(vec![(
+                    "RichDate".to_string(),
+                    Some("Date".to_string()),
+                )]),
+            },
+        ];
+
+        let parsed_result = parse_imports(sample_input).unwrap();
+        assert_eq!(parsed_result, expected_results);
+    }
+}
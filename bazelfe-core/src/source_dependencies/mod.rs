@@ -43,4 +43,6 @@ pub struct ParsedFile {
 }
 
 pub mod java;
+pub mod kotlin;
+pub mod python;
 pub mod scala;
@@ -0,0 +1,231 @@
+use crate::source_dependencies::parser_helpers::*;
+use crate::source_dependencies::{Import, ParsedFile, Result, SelectorType};
+
+use nom::branch::alt;
+use nom::bytes::complete::{take_while, take_while1};
+use nom::character::complete::{space0, space1};
+use nom::combinator::recognize;
+use nom::multi::{many0, separated_list, separated_nonempty_list};
+use nom::{
+    bytes::complete::tag,
+    combinator::{map, opt},
+    sequence::tuple,
+    IResult,
+};
+
+fn is_valid_import_segment_item(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn parse_identifier(input: &str) -> IResult<&str, &str> {
+    take_while1(is_valid_import_segment_item)(input)
+}
+
+fn parse_module(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        parse_identifier,
+        many0(tuple((tag("."), parse_identifier))),
+    )))(input)
+}
+
+// What's imported from can be relative to the file's package, .foo or just .
+fn parse_relative_module(input: &str) -> IResult<&str, &str> {
+    alt((
+        recognize(tuple((take_while(|c| c == '.'), parse_module))),
+        take_while1(|c| c == '.'),
+    ))(input)
+}
+
+fn parse_alias(input: &str) -> IResult<&str, Option<&str>> {
+    opt(map(
+        tuple((space1, tag("as"), space1, parse_identifier)),
+        |r| r.3,
+    ))(input)
+}
+
+fn parse_comma(input: &str) -> IResult<&str, ()> {
+    parser_to_unit(tuple((space0, tag(","), space0)))(input)
+}
+
+// import foo.bar, baz as qux
+// Like kotlin, renaming foo.bar is treated as selecting bar from foo.
+pub fn parse_import(line_number: u32, input: &str) -> IResult<&str, Vec<Import>> {
+    let (input, _) = tuple((space0, tag("import"), space1))(input)?;
+    let (input, modules) =
+        separated_nonempty_list(parse_comma, tuple((parse_module, parse_alias)))(input)?;
+
+    let imports = modules
+        .into_iter()
+        .map(|(module, opt_alias)| {
+            let (prefix_section, selector) = match (opt_alias, module.rfind('.')) {
+                (Some(alias), Some(idx)) => (
+                    &module[..idx],
+                    SelectorType::SelectorList(vec![(
+                        module[idx + 1..].to_string(),
+                        Some(alias.to_string()),
+                    )]),
+                ),
+                _ => (module, SelectorType::NoSelector),
+            };
+            Import {
+                line_number,
+                prefix_section: prefix_section.to_string(),
+                suffix: selector,
+            }
+        })
+        .collect();
+    Ok((input, imports))
+}
+
+// from foo.bar import baz, qux as quux
+// Only the first line of a parenthesized list spanning several is seen.
+pub fn parse_from_import(line_number: u32, input: &str) -> IResult<&str, Import> {
+    let (input, (_, _, _, module, _, _, _)) = tuple((
+        space0,
+        tag("from"),
+        space1,
+        parse_relative_module,
+        space1,
+        tag("import"),
+        space0,
+    ))(input)?;
+
+    let (input, selector) = alt((
+        map(tag("*"), |_| SelectorType::WildcardSelector),
+        map(
+            tuple((
+                opt(tuple((tag("("), space0))),
+                separated_list(parse_comma, tuple((parse_identifier, parse_alias))),
+            )),
+            |r| {
+                SelectorType::SelectorList(
+                    r.1.into_iter()
+                        .map(|(name, opt_alias)| {
+                            (name.to_string(), opt_alias.map(|e| e.to_string()))
+                        })
+                        .collect(),
+                )
+            },
+        ),
+    ))(input)?;
+
+    Ok((
+        input,
+        Import {
+            line_number,
+            prefix_section: module.to_string(),
+            suffix: selector,
+        },
+    ))
+}
+
+// PUBLIC METHODS
+pub fn parse_imports(input: &str) -> Result<Vec<Import>> {
+    let mut results_vec = Vec::new();
+    let mut line_number = 1;
+    let mut remaining_input = input;
+    while remaining_input.len() > 3 {
+        match eat_till_end_of_line(remaining_input) {
+            Ok((r, (current_line, end_of_line_eaten))) => {
+                if !current_line.is_empty() && current_line.contains("import") {
+                    if let Ok((_, found)) = parse_from_import(line_number, current_line) {
+                        results_vec.push(found);
+                    } else if let Ok((_, found)) = parse_import(line_number, current_line) {
+                        results_vec.extend(found);
+                    }
+                }
+
+                // if we never found an end of line, must be end of file.
+                if !end_of_line_eaten.is_empty() {
+                    remaining_input = r;
+                } else {
+                    remaining_input = "";
+                }
+            }
+            Err(_) => {
+                remaining_input = "";
+            }
+        }
+        line_number += 1;
+    }
+
+    Ok(results_vec)
+}
+
+// Python files don't declare the package they're in
+pub fn parse_file(input: &str) -> Result<ParsedFile> {
+    let imports = parse_imports(input)?;
+
+    Ok(ParsedFile {
+        package_name: None,
+        imports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_multiple_lines_input() {
+        let sample_input = "
+import os
+import company.util.strings as strings, json
+
+from company.api import client, models as m  # the api
+from .sibling import *
+from . import helpers
+def foo():
+    from company.lazy import (
+        thing,
+    )
+    return importlib.import_module('bar')
+        ";
+        let expected_results = vec![
+            Import {
+                line_number: 2,
+                prefix_section: "os".to_string(),
+                suffix: SelectorType::NoSelector,
+            },
+            Import {
+                line_number: 3,
+                prefix_section: "company.util".to_string(),
+                suffix: SelectorType::SelectorList(vec![(
+                    "strings".to_string(),
+                    Some("strings".to_string()),
+                )]),
+            },
+            Import {
+                line_number: 3,
+                prefix_section: "json".to_string(),
+                suffix: SelectorType::NoSelector,
+            },
+            Import {
+                line_number: 5,
+                prefix_section: "company.api".to_string(),
+                suffix: SelectorType::SelectorList(vec![
+                    ("client".to_string(), None),
+                    ("models".to_string(), Some("m".to_string())),
+                ]),
+            },
+            Import {
+                line_number: 6,
+                prefix_section: ".sibling".to_string(),
+                suffix: SelectorType::WildcardSelector,
+            },
+            Import {
+                line_number: 7,
+                prefix_section: ".".to_string(),
+                suffix: SelectorType::SelectorList(vec![("helpers".to_string(), None)]),
+            },
+            Import {
+                line_number: 9,
+                prefix_section: "company.lazy".to_string(),
+                suffix: SelectorType::SelectorList(vec![]),
+            },
+        ];
+
+        let parsed_result = parse_imports(sample_input).unwrap();
+        assert_eq!(parsed_result, expected_results);
+    }
+}
@@ -1,5 +1,4 @@
 use crate::app::App;
-use std::time::Duration;
 
 use tui::{
     backend::Backend,
@@ -7,10 +6,7 @@ use tui::{
     style::{Color, Modifier, Style},
     symbols,
     text::{Span, Spans},
-    widgets::canvas::{Canvas, Line, Map, MapResolution, Rectangle},
-    widgets::{
-        Axis, BarChart, Block, Borders, Chart, Dataset, GraphType, List, ListItem, Row, Table, Tabs,
-    },
+    widgets::{BarChart, Block, Borders, List, ListItem, Paragraph, Tabs, Wrap},
     Frame,
 };
 
@@ -24,117 +20,82 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .iter()
         .map(|t| Spans::from(Span::styled(*t, Style::default().fg(Color::Green))))
         .collect();
+    let status = match app.exit_code {
+        None => format!("{} - attempt {}", app.title, app.attempt + 1),
+        Some(exit_code) => format!(
+            "{} - finished after {} attempts with exit code {}",
+            app.title,
+            app.attempt + 1,
+            exit_code
+        ),
+    };
     let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title(app.title))
+        .block(Block::default().borders(Borders::ALL).title(status))
         .highlight_style(Style::default().fg(Color::Yellow))
         .select(app.tabs.index);
     f.render_widget(tabs, chunks[0]);
     match app.tabs.index {
-        0 => draw_first_tab(f, app, chunks[1]),
-        1 => draw_second_tab(f, app, chunks[1]),
+        0 => draw_build_activity(f, app, chunks[1]),
+        1 => draw_failed_actions(f, app, chunks[1]),
+        2 => draw_fixes(f, app, chunks[1]),
         _ => {}
     };
 }
 
-fn draw_first_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+fn draw_build_activity<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
 where
     B: Backend,
 {
     let chunks = Layout::default()
-        .constraints(
-            [
-                Constraint::Length(15),
-                Constraint::Min(7),
-                Constraint::Length(7),
-            ]
-            .as_ref(),
-        )
+        .constraints([Constraint::Length(15), Constraint::Min(7)].as_ref())
         .split(area);
-    draw_top_bar(f, app, chunks[0]);
-    draw_text(f, app, chunks[2]);
-}
+    draw_action_counts(f, app, chunks[0]);
 
-fn draw_top_bar<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
-where
-    B: Backend,
-{
     let chunks = Layout::default()
-        .constraints(
-            vec![
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-            ]
-            .as_ref(),
-        )
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .direction(Direction::Horizontal)
-        .split(area);
-
-    let bar_gap = if chunks[0].width > 50 { 2 } else { 1 };
-    let bar_width = (chunks[0].width - bar_gap - 5) / (app.nemonics.len()) as u16;
-    let active_nemonics = BarChart::default()
-        .block(
-            Block::default()
-                .borders(Borders::RIGHT)
-                .title("Active Action Types:"),
-        )
-        .data(&app.nemonics)
-        .bar_width(bar_width)
-        .bar_gap(bar_gap)
-        .bar_set(if app.enhanced_graphics {
-            symbols::bar::NINE_LEVELS
-        } else {
-            symbols::bar::THREE_LEVELS
-        })
-        .value_style(
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Blue)
-                .add_modifier(Modifier::ITALIC),
-        )
-        .label_style(Style::default().fg(Color::Yellow))
-        .bar_style(Style::default().fg(Color::Blue));
-    f.render_widget(active_nemonics, chunks[0]);
-
-    let sub_chunks = Layout::default()
-        .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-        .direction(Direction::Vertical)
         .split(chunks[1]);
+    draw_targets(
+        f,
+        &format!(
+            "Running Targets ({}, {} completed)",
+            app.running_targets.len(),
+            app.completed_targets
+        ),
+        app.running_targets.iter(),
+        Style::default().fg(Color::Blue),
+        chunks[0],
+    );
+    draw_targets(
+        f,
+        &format!("Failed Targets ({})", app.failed_targets.len()),
+        app.failed_targets.iter(),
+        Style::default().fg(Color::Red),
+        chunks[1],
+    );
+}
 
-    let bar_gap = if sub_chunks[0].width > 50 { 2 } else { 1 };
-    let bar_width = (sub_chunks[0].width - bar_gap - 5) / (app.completed_actions.len()) as u16;
-    let completed_actions = BarChart::default()
-        .block(
-            Block::default()
-                .borders(Borders::RIGHT)
-                .title("Launched Actions:"),
-        )
-        .data(&app.completed_actions)
-        .bar_width(bar_width)
-        .bar_gap(bar_gap)
-        .bar_set(if app.enhanced_graphics {
-            symbols::bar::NINE_LEVELS
-        } else {
-            symbols::bar::THREE_LEVELS
-        })
-        .value_style(
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Green)
-                .add_modifier(Modifier::ITALIC),
-        )
-        .label_style(Style::default().fg(Color::Yellow))
-        .bar_style(Style::default().fg(Color::Green));
-    f.render_widget(completed_actions, sub_chunks[0]);
-    let bar_gap = if sub_chunks[1].width > 50 { 2 } else { 1 };
-    let bar_width = (sub_chunks[1].width - bar_gap - 5) / (app.completed_actions.len()) as u16;
-    let completed_actions = BarChart::default()
+fn draw_action_counts<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+where
+    B: Backend,
+{
+    let action_counts: Vec<(&str, u64)> = app
+        .action_counts
+        .iter()
+        .map(|(mnemonic, count)| (mnemonic.as_str(), *count))
+        .collect();
+    let bar_gap = if area.width > 50 { 2 } else { 1 };
+    let bar_width = match action_counts.len() {
+        0 => 1,
+        n => (area.width.saturating_sub(bar_gap + 5) / n as u16).clamp(1, 12),
+    };
+    let chart = BarChart::default()
         .block(
             Block::default()
-                .borders(Borders::RIGHT)
-                .title("Completed Actions:"),
+                .borders(Borders::ALL)
+                .title("Actions Completed:"),
         )
-        .data(&app.completed_actions)
+        .data(&action_counts)
         .bar_width(bar_width)
         .bar_gap(bar_gap)
         .bar_set(if app.enhanced_graphics {
@@ -150,163 +111,102 @@ where
         )
         .label_style(Style::default().fg(Color::Yellow))
         .bar_style(Style::default().fg(Color::Green));
-    f.render_widget(completed_actions, sub_chunks[1]);
-
-    let data: Vec<(f64, f64)> = app
-        .sparkline
-        .points
-        .iter()
-        .enumerate()
-        .map(|(k, v)| (k as f64, *v as f64 % 20.0))
-        .collect();
-
-    let datasets = vec![Dataset::default()
-        .graph_type(GraphType::Line)
-        .marker(symbols::Marker::Braille)
-        .style(Style::default().fg(Color::Yellow))
-        .data(&data)];
-
-    let chart = Chart::new(datasets)
-        .block(
-            Block::default().title(Span::styled(
-                "Pending Actions",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            )),
-        )
-        .x_axis(
-            Axis::default()
-                .title(Span::styled("X Axis", Style::default().fg(Color::Red)))
-                .style(Style::default().fg(Color::White))
-                .bounds([0.0, data.len() as f64])
-                .labels(
-                    ["0.0", "5.0", "10.0"]
-                        .iter()
-                        .cloned()
-                        .map(Span::from)
-                        .collect(),
-                ),
-        )
-        .y_axis(
-            Axis::default()
-                .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, 20.0])
-                .labels(vec![
-                    Span::styled("0", Style::default().add_modifier(Modifier::BOLD)),
-                    Span::styled("20", Style::default().add_modifier(Modifier::BOLD)),
-                ]),
-        );
-    f.render_widget(chart, chunks[2]);
+    f.render_widget(chart, area);
 }
 
-fn draw_text<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+fn draw_targets<'a, B, I>(f: &mut Frame<B>, title: &str, labels: I, style: Style, area: Rect)
 where
     B: Backend,
+    I: Iterator<Item = &'a String>,
 {
-    let action_style = Style::default().fg(Color::Blue);
-    let target_style = Style::default().fg(Color::Yellow);
-    let test_style = Style::default().fg(Color::Magenta);
-    let unknown_style = Style::default().fg(Color::Red);
-    let logs: Vec<ListItem> = app
-        .action_logs
-        .items
-        .iter()
-        .map(|&(level, evt, run_time)| {
-            let s = match level {
-                "ACTION" => action_style,
-                "TARGET" => target_style,
-                "TEST" => test_style,
-                _ => unknown_style,
-            };
-            let content = vec![Spans::from(vec![
-                Span::styled(format!("{:<9}", level), s),
-                Span::raw(evt),
-                Span::raw(format!(" in {:?}", Duration::from_secs(run_time.into()))),
-            ])];
-            ListItem::new(content)
-        })
+    let items: Vec<ListItem> = labels
+        .map(|label| ListItem::new(Span::styled(label.as_str(), style)))
         .collect();
-    let logs = List::new(logs).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title("Completion events"),
-    );
-    f.render_stateful_widget(logs, area, &mut app.action_logs.state);
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
 }
 
-fn draw_second_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+fn draw_failed_actions<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
 where
     B: Backend,
 {
     let chunks = Layout::default()
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
         .direction(Direction::Horizontal)
         .split(area);
-    let up_style = Style::default().fg(Color::Green);
-    let failure_style = Style::default()
-        .fg(Color::Red)
-        .add_modifier(Modifier::RAPID_BLINK | Modifier::CROSSED_OUT);
-    let header = ["Server", "Location", "Status"];
-    let rows = app.servers.iter().map(|s| {
-        let style = if s.status == "Up" {
-            up_style
-        } else {
-            failure_style
-        };
-        Row::StyledData(vec![s.name, s.location, s.status].into_iter(), style)
-    });
-    let table = Table::new(header.iter(), rows)
-        .block(Block::default().title("Servers").borders(Borders::ALL))
-        .header_style(Style::default().fg(Color::Yellow))
-        .widths(&[
-            Constraint::Length(15),
-            Constraint::Length(15),
-            Constraint::Length(10),
-        ]);
-    f.render_widget(table, chunks[0]);
 
-    let map = Canvas::default()
-        .block(Block::default().title("World").borders(Borders::ALL))
-        .paint(|ctx| {
-            ctx.draw(&Map {
-                color: Color::White,
-                resolution: MapResolution::High,
-            });
-            ctx.layer();
-            ctx.draw(&Rectangle {
-                x: 0.0,
-                y: 30.0,
-                width: 10.0,
-                height: 10.0,
-                color: Color::Yellow,
-            });
-            for (i, s1) in app.servers.iter().enumerate() {
-                for s2 in &app.servers[i + 1..] {
-                    ctx.draw(&Line {
-                        x1: s1.coords.1,
-                        y1: s1.coords.0,
-                        y2: s2.coords.0,
-                        x2: s2.coords.1,
-                        color: Color::Yellow,
-                    });
-                }
-            }
-            for server in &app.servers {
-                let color = if server.status == "Up" {
-                    Color::Green
-                } else {
-                    Color::Red
-                };
-                ctx.print(server.coords.1, server.coords.0, "X", color);
-            }
+    let items: Vec<ListItem> = app
+        .failed_actions
+        .items
+        .iter()
+        .map(|action| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("#{:<3}", action.attempt + 1),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    format!("{:<10}", action.mnemonic.as_deref().unwrap_or("Test")),
+                    Style::default().fg(Color::Magenta),
+                ),
+                Span::raw(action.label.as_str()),
+            ]))
         })
-        .marker(if app.enhanced_graphics {
-            symbols::Marker::Braille
-        } else {
-            symbols::Marker::Dot
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Failed Actions"),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.failed_actions.state);
+
+    let output = match &app.inspecting {
+        Some(inspecting) => Paragraph::new(inspecting.output.as_str())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Output of {} (esc to close)", inspecting.label)),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((inspecting.scroll, 0)),
+        None => Paragraph::new("Press enter to view the output of the selected action")
+            .block(Block::default().borders(Borders::ALL).title("Output")),
+    };
+    f.render_widget(output, chunks[1]);
+}
+
+fn draw_fixes<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+where
+    B: Backend,
+{
+    let items: Vec<ListItem> = app
+        .fixes
+        .items
+        .iter()
+        .map(|fix| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("#{:<3}", fix.attempt + 1),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(fix.correction.edit.to_buildozer_command()),
+                Span::styled(
+                    format!("  ({})", fix.correction.src_fn),
+                    Style::default().fg(Color::Gray),
+                ),
+            ]))
         })
-        .x_bounds([-180.0, 180.0])
-        .y_bounds([-90.0, 90.0]);
-    f.render_widget(map, chunks[1]);
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Fixes Applied ({})", app.fixes.items.len())),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut app.fixes.state);
 }
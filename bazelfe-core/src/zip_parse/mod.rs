@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 pub fn extract_classes_from_zip(path: PathBuf) -> Vec<String> {
     if !path.exists() {
@@ -25,6 +26,39 @@ pub fn extract_classes_from_zip(path: PathBuf) -> Vec<String> {
     // }
     results
 }
+// The label of the target a jar was built for, which bazel stamps into the manifests of
+// the jars it compiles against as Target-Label
+pub fn jar_target_label(path: &Path) -> Option<String> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+    let mut manifest = String::new();
+    archive
+        .by_name("META-INF/MANIFEST.MF")
+        .ok()?
+        .read_to_string(&mut manifest)
+        .ok()?;
+    manifest_attribute(&manifest, "Target-Label")
+}
+
+// Manifest lines are wrapped at 72 bytes, with each continuation starting with a space
+fn manifest_attribute(manifest: &str, name: &str) -> Option<String> {
+    let mut lines = manifest.lines().peekable();
+    while let Some(ln) = lines.next() {
+        if let Some(value) = ln
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(':'))
+        {
+            let mut value = value.trim_start().to_string();
+            while let Some(continuation) = lines.peek().and_then(|e| e.strip_prefix(' ')) {
+                value.push_str(continuation);
+                lines.next();
+            }
+            return Some(value);
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,4 +84,25 @@ mod tests {
         ];
         assert_eq!(extract_classes_from_zip(d), expected);
     }
+
+    #[test]
+    fn test_manifest_attribute() {
+        let manifest = "Manifest-Version: 1.0\r
+Created-By: bazel\r
+Target-Label: @third_party_jvm//3rdparty/jvm/com/google/guava:guava_with_a_long\r
+ _name\r
+Injecting-Rule-Kind: java_import\r
+";
+        assert_eq!(
+            manifest_attribute(manifest, "Target-Label"),
+            Some(String::from(
+                "@third_party_jvm//3rdparty/jvm/com/google/guava:guava_with_a_long_name"
+            ))
+        );
+        assert_eq!(
+            manifest_attribute(manifest, "Injecting-Rule-Kind"),
+            Some(String::from("java_import"))
+        );
+        assert_eq!(manifest_attribute(manifest, "Main-Class"), None);
+    }
 }
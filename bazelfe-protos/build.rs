@@ -22,11 +22,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         &[
             "proto/bazel_tools/request_files_service.proto",
             "proto/bazel_tools/upstream_service.proto",
+            "proto/bazel_tools/daemon_service.proto",
         ],
         &["proto/remote-apis", "proto/bazel_tools", "proto/googleapis"],
     )?;
 
     tonic_build::configure().compile(&["proto/devtools/buildozer/api.proto"], &["proto"])?;
 
+    tonic_build::configure().compile(&["proto/src/main/protobuf/deps.proto"], &["proto"])?;
+
     Ok(())
 }
@@ -22,6 +22,10 @@ pub mod blaze {
     }
 }
 
+pub mod blaze_deps {
+    tonic::include_proto!("blaze_deps");
+}
+
 pub mod failure_details {
     tonic::include_proto!("failure_details");
 }